@@ -2,9 +2,22 @@ use anyhow::{anyhow, Result};
 use csv::Trim::All;
 use env_logger::Env;
 use log::error;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 use std::{env, io};
 
+use trx_service::trx_engine::account_store::FileAccountStore;
+use trx_service::trx_engine::event_store::FileEventStore;
+use trx_service::trx_engine::fees::{FeeRule, FeeSchedule};
+use trx_service::trx_engine::journal::{FileJournal, Journal, SyncPolicy};
+use trx_service::trx_engine::limits::CreditLimits;
 use trx_service::trx_engine::processor;
+use trx_service::trx_engine::processor::{ErrorPolicy, ProcessOptions};
+use trx_service::trx_engine::rates::CsvRateProvider;
+use trx_service::trx_engine::rounding::Rounding;
+use trx_service::trx_engine::transaction_store::DiskTransactionStore;
 
 //pub mod trx_engine;
 fn main() -> Result<()> {
@@ -13,7 +26,8 @@ fn main() -> Result<()> {
 
     // as per the pdf, one argument is expected for the correct behaviour of the CLI.
     // if in the future more args are added, consider using CLAP for a fine grain control of validations and defaults.
-    let filepath = match env::args().nth(1) {
+    let mut args = env::args().skip(1);
+    let filepath = match args.next() {
         Some(file_path) => Ok(file_path),
         None => {
             error!("At least one argument is expected!");
@@ -31,7 +45,256 @@ fn main() -> Result<()> {
         }
     }?;
     
+    // optional flags follow the input file.
+    let mut options = ProcessOptions::default();
+    let mut currency_decimal_places = Vec::new();
+    let mut house_account = None;
+    let mut fees = FeeSchedule::new(0);
+    let mut journal = None;
+    let mut journal_sync = SyncPolicy::default();
+    let mut account_store = None;
+    let mut snapshot = None;
+    let mut events = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rejections" => {
+                let Some(path) = args.next() else {
+                    error!("--rejections expects a file path");
+                    return Err(anyhow!("--rejections expects a file path"));
+                };
+                options.rejections = Some(Box::new(File::create(path)?));
+            }
+            "--strict" => options.error_policy = ErrorPolicy::Strict,
+            "--max-rejections" => {
+                let Some(max) = args.next().and_then(|max| max.parse().ok()) else {
+                    error!("--max-rejections expects a number of lines");
+                    return Err(anyhow!("--max-rejections expects a number of lines"));
+                };
+                options.error_policy = ErrorPolicy::MaxRejections(max);
+            }
+            "--max-rejected-percent" => {
+                let Some(max) = args
+                    .next()
+                    .and_then(|max| max.parse().ok())
+                    .filter(|max: &f64| (0.0..=100.0).contains(max)) else {
+                    error!("--max-rejected-percent expects a percentage between 0 and 100");
+                    return Err(anyhow!("--max-rejected-percent expects a percentage between 0 and 100"));
+                };
+                options.error_policy = ErrorPolicy::MaxRejectedPercent(max);
+            }
+            "--dispute-hold-lifetime" => {
+                let Some(lifetime) = args.next().and_then(|lifetime| lifetime.parse().ok()) else {
+                    error!("--dispute-hold-lifetime expects a number of seconds");
+                    return Err(anyhow!("--dispute-hold-lifetime expects a number of seconds"));
+                };
+                options.ledger.hold_lifetimes.dispute = Some(lifetime);
+            }
+            "--authorization-hold-lifetime" => {
+                let Some(lifetime) = args.next().and_then(|lifetime| lifetime.parse().ok()) else {
+                    error!("--authorization-hold-lifetime expects a number of seconds");
+                    return Err(anyhow!("--authorization-hold-lifetime expects a number of seconds"));
+                };
+                options.ledger.hold_lifetimes.authorization = Some(lifetime);
+            }
+            "--dispute-window" => {
+                let Some(window) = args.next().and_then(|window| window.parse().ok()) else {
+                    error!("--dispute-window expects a number of seconds");
+                    return Err(anyhow!("--dispute-window expects a number of seconds"));
+                };
+                options.ledger.dispute_window = Some(window);
+            }
+            "--rates" => {
+                let Some(path) = args.next() else {
+                    error!("--rates expects a file path");
+                    return Err(anyhow!("--rates expects a file path"));
+                };
+                let rdr = csv::ReaderBuilder::new().trim(All).from_path(path)?;
+                options.rates = Some(Box::new(CsvRateProvider::from_reader(rdr)?));
+            }
+            "--credit-limits" => {
+                let Some(path) = args.next() else {
+                    error!("--credit-limits expects a file path");
+                    return Err(anyhow!("--credit-limits expects a file path"));
+                };
+                let rdr = csv::ReaderBuilder::new().trim(All).from_path(path)?;
+                options.ledger.credit_limits = CreditLimits::from_reader(rdr)?;
+            }
+            "--transaction-store" => {
+                let Some(dir) = args.next() else {
+                    error!("--transaction-store expects a directory path");
+                    return Err(anyhow!("--transaction-store expects a directory path"));
+                };
+                options.transaction_store = Some(Box::new(DiskTransactionStore::create(dir)?));
+            }
+            "--account-store" => {
+                let Some(path) = args.next() else {
+                    error!("--account-store expects a file path");
+                    return Err(anyhow!("--account-store expects a file path"));
+                };
+                account_store = Some(path);
+            }
+            "--restore" => {
+                let Some(path) = args.next() else {
+                    error!("--restore expects a file path");
+                    return Err(anyhow!("--restore expects a file path"));
+                };
+                options.restore = Some(Box::new(BufReader::new(File::open(path)?)));
+            }
+            "--snapshot" => {
+                let Some(path) = args.next() else {
+                    error!("--snapshot expects a file path");
+                    return Err(anyhow!("--snapshot expects a file path"));
+                };
+                snapshot = Some(path);
+            }
+            "--journal" => {
+                let Some(path) = args.next() else {
+                    error!("--journal expects a file path");
+                    return Err(anyhow!("--journal expects a file path"));
+                };
+                journal = Some(path);
+            }
+            "--journal-sync" => {
+                let Some(policy) = args.next().and_then(|policy| policy.parse().ok()) else {
+                    error!("--journal-sync expects always, never or a number of entries");
+                    return Err(anyhow!("--journal-sync expects always, never or a number of entries"));
+                };
+                journal_sync = policy;
+            }
+            "--events" => {
+                let Some(path) = args.next() else {
+                    error!("--events expects a file path");
+                    return Err(anyhow!("--events expects a file path"));
+                };
+                events = Some(path);
+            }
+            "--rounding" => {
+                let Some(mode) = args.next().and_then(|mode| mode.parse().ok()) else {
+                    error!("--rounding expects bankers, half-up or truncate");
+                    return Err(anyhow!("--rounding expects bankers, half-up or truncate"));
+                };
+                options.ledger.precision.default.mode = mode;
+            }
+            "--decimal-places" => {
+                let Some(decimal_places) = args.next().and_then(|decimal_places| decimal_places.parse().ok()) else {
+                    error!("--decimal-places expects a number of decimal places");
+                    return Err(anyhow!("--decimal-places expects a number of decimal places"));
+                };
+                options.ledger.precision.default.decimal_places = decimal_places;
+            }
+            "--currency-decimal-places" => {
+                // e.g. JPY=0, the currency uses the default rounding mode.
+                // applied once all flags are read, so the flags can be given in any order.
+                let Some((currency, decimal_places)) = args.next().and_then(|precision| {
+                    let (currency, decimal_places) = precision.split_once('=')?;
+                    Some((currency.parse().ok()?, decimal_places.parse().ok()?))
+                }) else {
+                    error!("--currency-decimal-places expects a currency and a number of decimal places, e.g. JPY=0");
+                    return Err(anyhow!("--currency-decimal-places expects a currency and a number of decimal places, e.g. JPY=0"));
+                };
+                currency_decimal_places.push((currency, decimal_places));
+            }
+            "--house-account" => {
+                let Some(client) = args.next().and_then(|client| client.parse().ok()) else {
+                    error!("--house-account expects a client id");
+                    return Err(anyhow!("--house-account expects a client id"));
+                };
+                house_account = Some(client);
+            }
+            "--withdrawal-fee" | "--chargeback-fee" => {
+                let Some(fee) = args.next().and_then(|fee| fee.parse().ok()) else {
+                    error!("{} expects a non-negative flat amount or percentage, e.g. 1.5 or 1.5%", arg);
+                    return Err(anyhow!("{} expects a non-negative flat amount or percentage, e.g. 1.5 or 1.5%", arg));
+                };
+                let rule = Some(FeeRule::new(fee));
+                match arg.as_str() {
+                    "--withdrawal-fee" => fees.withdrawal = rule,
+                    _ => fees.chargeback = rule,
+                }
+            }
+            _ => {
+                error!("unknown argument: {}", arg);
+                return Err(anyhow!("unknown argument: {}", arg));
+            }
+        }
+    }
+
+    for (currency, decimal_places) in currency_decimal_places {
+        let rounding = Rounding {
+            decimal_places,
+            ..options.ledger.precision.default
+        };
+        options.ledger.precision.currencies.insert(currency, rounding);
+    }
+
+    // fees are only charged once the house account collecting them is known.
+    if fees.withdrawal.is_some() || fees.chargeback.is_some() {
+        let Some(house_account) = house_account else {
+            error!("fees require a --house-account");
+            return Err(anyhow!("fees require a --house-account"));
+        };
+        options.ledger.fees = Some(FeeSchedule { house_account, ..fees });
+    }
+
+    // the stores are opened once every flag is read, so the flags can be given in any order.
+    if let Some(path) = account_store {
+        options.account_store = Some(Box::new(FileAccountStore::open(path)?));
+    }
+    if let Some(path) = events {
+        options.event_store = Some(Box::new(FileEventStore::open(path)?));
+    }
+
+    // a journal left by a previous run is replayed, and the run resumes after its last entry.
+    // the journal is emptied once the run completes, so it can be reused for the next file.
+    if let Some(path) = &journal {
+        match File::open(path) {
+            Ok(file) => options.recover = Some(Box::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        options.journal = Some(Box::new(FileJournal::open(path, journal_sync)?));
+    }
+
+    // the snapshot is written aside and only renamed over the target once the file is processed,
+    // so an aborted run keeps the previous snapshot, which may also be the one restored by this run.
+    let snapshot = match snapshot {
+        Some(path) => {
+            let written = format!("{path}.tmp");
+            let file = File::create(&written)?;
+            Some((path, written, file))
+        }
+        None => None,
+    };
+    if let Some((_, _, file)) = &snapshot {
+        options.snapshot = Some(Box::new(BufWriter::new(file.try_clone()?)));
+    }
+
     let output = io::stdout();
-    processor::process_transactions_file(rdr, output)
+    if let Err(e) = processor::process_transactions_file_with_options(rdr, output, options) {
+        if let Some((_, written, _)) = &snapshot {
+            let _ = fs::remove_file(written);
+        }
+        return Err(e.into());
+    }
+
+    if let Some((path, written, file)) = snapshot {
+        file.sync_all()?;
+        fs::rename(written, &path)?;
+
+        // the rename is only durable once the directory holding the snapshot is synced.
+        let dir = match Path::new(&path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+
+    // the journal is only emptied once the stores and the snapshot hold the changes of its inputs,
+    // so a crash before this point is still recovered on the next run.
+    if let Some(path) = journal {
+        FileJournal::open(path, journal_sync)?.truncate()?;
+    }
+
+    Ok(())
 
 }
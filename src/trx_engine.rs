@@ -1,5 +1,17 @@
 pub mod account;
-pub(crate) mod errors;
-pub(crate) mod ledger;
+pub mod account_store;
+pub mod currency;
+pub mod errors;
+pub mod event_store;
+pub mod events;
+pub mod fees;
+pub mod journal;
+pub mod ledger;
+pub mod limits;
 pub mod processor;
-pub(crate) mod transaction;
+pub mod rates;
+pub mod rounding;
+pub mod snapshot;
+pub mod transaction;
+pub mod transaction_store;
+pub(crate) mod unit_of_work;
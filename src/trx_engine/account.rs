@@ -1,142 +1,825 @@
+use crate::trx_engine::currency::Currency;
 use crate::trx_engine::errors::EngineError;
-use anyhow::anyhow;
+use crate::trx_engine::events::{Event, Flow};
+use crate::trx_engine::rounding::PrecisionPolicy;
+use crate::trx_engine::transaction::Type;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::collections::BTreeMap;
+
+/// Status of an account, see `AccountStatus::transition` for the operations allowed in each status.
+#[derive(
+    Debug, Default, PartialEq, Eq, Hash, Copy, Clone, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// Set by a chargeback, only an administrative unlock or close is accepted.
+    Locked,
+    /// Set by an administrative freeze, disputes can still be opened and settled and authorizations voided,
+    /// but no funds move in or out.
+    Frozen,
+    /// Set by an administrative close, no operation is accepted anymore.
+    Closed,
+}
+
+impl AccountStatus {
+    /// Transition table of the status of an account.
+    /// Every operation on an account must go through this table before mutating it,
+    /// the match is exhaustive so no operation can be left undefined in any status.
+    ///
+    /// | from     | deposit / withdrawal / transfer / exchange / authorize / capture | dispute / resolve / void | chargeback    | unlock           | freeze        | unfreeze         | close         |
+    /// |----------|------------------------------------------------------------------|--------------------------|---------------|------------------|---------------|------------------|---------------|
+    /// | Active   | Active                                                           | Active                   | Locked        | AccountNotLocked | Frozen        | AccountNotFrozen | Closed        |
+    /// | Locked   | AccountLocked                                                    | AccountLocked            | AccountLocked | Active           | AccountLocked | AccountNotFrozen | Closed        |
+    /// | Frozen   | AccountFrozen                                                    | Frozen                   | Locked        | AccountNotLocked | AccountFrozen | Active           | Closed        |
+    /// | Closed   | AccountClosed                                                    | AccountClosed            | AccountClosed | AccountClosed    | AccountClosed | AccountClosed    | AccountClosed |
+    ///
+    /// # Errors
+    /// * An error is returned if the operation is not allowed in the current status.
+    ///
+    pub(crate) fn transition(
+        client: u16,
+        from: AccountStatus,
+        action: Type,
+    ) -> Result<AccountStatus, EngineError> {
+        match (from, action) {
+            (Self::Closed, _) => Err(EngineError::AccountClosed { client }),
+            (_, Type::Close) => Ok(Self::Closed),
+
+            (
+                Self::Active,
+                Type::Deposit
+                | Type::Withdrawal
+                | Type::Transfer
+                | Type::Exchange
+                | Type::Authorize
+                | Type::Capture
+                | Type::Dispute
+                | Type::Resolve
+                | Type::Void,
+            ) => Ok(Self::Active),
+            (Self::Active | Self::Frozen, Type::Chargeback) => Ok(Self::Locked),
+            (Self::Active | Self::Frozen, Type::Unlock) => {
+                Err(EngineError::AccountNotLocked { client })
+            }
+            (Self::Active, Type::Freeze) => Ok(Self::Frozen),
+            (Self::Active | Self::Locked, Type::Unfreeze) => {
+                Err(EngineError::AccountNotFrozen { client })
+            }
+
+            (Self::Locked, Type::Unlock) => Ok(Self::Active),
+            (
+                Self::Locked,
+                Type::Deposit
+                | Type::Withdrawal
+                | Type::Transfer
+                | Type::Exchange
+                | Type::Authorize
+                | Type::Capture
+                | Type::Dispute
+                | Type::Resolve
+                | Type::Void
+                | Type::Chargeback
+                | Type::Freeze,
+            ) => Err(EngineError::AccountLocked { client }),
+
+            (
+                Self::Frozen,
+                Type::Deposit
+                | Type::Withdrawal
+                | Type::Transfer
+                | Type::Exchange
+                | Type::Authorize
+                | Type::Capture
+                | Type::Freeze,
+            ) => Err(EngineError::AccountFrozen { client }),
+            (Self::Frozen, Type::Dispute | Type::Resolve | Type::Void) => Ok(Self::Frozen),
+            (Self::Frozen, Type::Unfreeze) => Ok(Self::Active),
+        }
+    }
+}
+
+/// Balance holds the funds of an account in a single currency.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Balance {
+    pub(crate) available: Decimal,
+    pub(crate) held: Decimal,
+    pub(crate) total: Decimal,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            available: dec!(0.0),
+            held: dec!(0.0),
+            total: dec!(0.0),
+        }
+    }
+}
+
+impl Balance {
+    pub fn available(&self) -> Decimal {
+        self.available
+    }
+
+    pub fn held(&self) -> Decimal {
+        self.held
+    }
+
+    pub fn total(&self) -> Decimal {
+        self.total
+    }
+
+    fn is_empty(&self) -> bool {
+        self.total.is_zero() && self.held.is_zero()
+    }
+}
 
 /// Account represents an account of a client.
 /// All operations that mutate an Account should be done through the provided methods.
-/// The output result is achieved by using the serde serializer.
+/// The funds are kept per currency, amounts without a currency are kept in their own balance.
+/// The credit limit lets withdrawals take the available funds of every balance down to `-credit_limit`.
+/// The output result is achieved by serializing the `records` of the account.
 /// No business logic is validated in this op wrapper (for example calling a dispute on a deposit)
 /// that is up to the consumer (ledger) to ensure.
-#[derive(Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+/// Accounts are serialized whole by the persistent stores, the report is made of `AccountRecord`s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
 pub struct Account {
+    pub client: u16,
+    #[serde(with = "balances_as_entries")]
+    pub(crate) balances: BTreeMap<Option<Currency>, Balance>,
+    pub(crate) status: AccountStatus,
+    #[serde(default)]
+    pub(crate) credit_limit: Decimal,
+}
+
+/// Balances are serialized as a list of `(currency, balance)` entries, as formats like json only accept string keys.
+mod balances_as_entries {
+    use super::{Balance, Currency};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub(super) fn serialize<S: Serializer>(
+        balances: &BTreeMap<Option<Currency>, Balance>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(balances)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Option<Currency>, Balance>, D::Error> {
+        let entries = Vec::<(Option<Currency>, Balance)>::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+/// AccountRecord is a row of the accounts report: the balance of a client in one currency.
+/// The `locked` column is kept for the consumers of the original report format,
+/// reports without a `status` column are read as active or locked accounts
+/// and reports without a `currency` column as balances without a currency.
+/// `over_limit` flags a balance whose available funds are below the credit limit of the account,
+/// which only chargebacks and their fees can cause.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct AccountRecord {
     #[serde(rename = "client")]
     pub client: u16,
 
     #[serde(rename = "available")]
-    pub(crate) available: Decimal,
+    pub available: Decimal,
 
     #[serde(rename = "held")]
-    pub(crate) held: Decimal,
+    pub held: Decimal,
 
     #[serde(rename = "total")]
-    pub(crate) total: Decimal,
+    pub total: Decimal,
 
     #[serde(rename = "locked")]
-    pub(crate) locked: bool,
+    pub locked: bool,
+
+    #[serde(default, rename = "status")]
+    pub status: Option<AccountStatus>,
+
+    #[serde(default, rename = "currency")]
+    pub currency: Option<Currency>,
+
+    #[serde(default, rename = "over_limit")]
+    pub over_limit: bool,
+}
+
+impl AccountRecord {
+    /// Status of the account, falling back to the `locked` column for reports without a status.
+    pub fn status(&self) -> AccountStatus {
+        match (self.status, self.locked) {
+            (Some(status), _) => status,
+            (None, true) => AccountStatus::Locked,
+            (None, false) => AccountStatus::Active,
+        }
+    }
 }
 
 impl Account {
     pub(crate) fn new(id: u16) -> Self {
         Self {
             client: id,
-            available: dec!(0.0),
-            held: dec!(0.0),
-            total: dec!(0.0),
-            locked: false,
+            balances: BTreeMap::new(),
+            status: AccountStatus::Active,
+            credit_limit: Decimal::ZERO,
         }
     }
 
-    /*pub(crate) fn format_account_precision_of_decimals_for_report(&self) -> Self {
-        Self {
+    /// Balance of the account in the provided currency, empty if the currency was never used.
+    pub fn balance(&self, currency: Option<Currency>) -> Balance {
+        self.balances.get(&currency).copied().unwrap_or_default()
+    }
+
+    pub fn balances(&self) -> impl Iterator<Item = (Option<Currency>, &Balance)> {
+        self.balances
+            .iter()
+            .map(|(currency, balance)| (*currency, balance))
+    }
+
+    /// An account is locked when it does not accept deposits nor withdrawals, whatever the reason.
+    pub fn locked(&self) -> bool {
+        self.status != AccountStatus::Active
+    }
+
+    pub fn status(&self) -> AccountStatus {
+        self.status
+    }
+
+    pub fn credit_limit(&self) -> Decimal {
+        self.credit_limit
+    }
+
+    /// Funds of the balance in the provided currency that can be withdrawn, the credit limit included.
+    pub fn spendable(&self, currency: Option<Currency>) -> Decimal {
+        self.balance(currency)
+            .available
+            .saturating_add(self.credit_limit)
+    }
+
+    /// Whether the available funds in the provided currency are below the credit limit of the account.
+    pub fn over_limit(&self, currency: Option<Currency>) -> bool {
+        self.spendable(currency).is_sign_negative()
+    }
+
+    /// Rows of the accounts report, one per currency the account holds.
+    /// An account that never held any funds is reported with an empty balance without a currency.
+    pub fn records(&self) -> Vec<AccountRecord> {
+        let record = |currency: Option<Currency>, balance: &Balance| AccountRecord {
             client: self.client,
-            available: self.available.round_dp(4),
-            held: self.held.round_dp(4),
-            total: self.total.round_dp(4),
-            locked: self.locked,
+            available: balance.available,
+            held: balance.held,
+            total: balance.total,
+            locked: self.locked(),
+            status: Some(self.status),
+            currency,
+            over_limit: self.over_limit(currency),
+        };
+
+        if self.balances.is_empty() {
+            return vec![record(None, &Balance::default())];
         }
-    }*/
 
-    pub(crate) fn deposit(&mut self, amount: Decimal) -> anyhow::Result<()> {
-        is_amount_negative(&amount)?;
+        self.balances()
+            .map(|(currency, balance)| record(currency, balance))
+            .collect()
+    }
+
+    /// Copy of the account with every balance rounded to the precision of its currency.
+    pub(crate) fn format_account_precision_of_decimals_for_report(
+        &self,
+        precision: &PrecisionPolicy,
+    ) -> Self {
+        let balances = self.balances.iter().map(|(currency, balance)| {
+            let rounding = precision.rounding(*currency);
+            let balance = Balance {
+                available: rounding.round(balance.available),
+                held: rounding.round(balance.held),
+                total: rounding.round(balance.total),
+            };
+            (*currency, balance)
+        });
 
-        self.is_account_locked()?;
+        Self {
+            client: self.client,
+            balances: balances.collect(),
+            status: self.status,
+            credit_limit: self.credit_limit,
+        }
+    }
 
-        self.available += amount;
-        self.total += amount;
+    /// Applies an event to the account. This is the only place the balances and the status of an account change:
+    /// the operations below validate the change, then apply and return the events recording it.
+    /// Every field is computed before the account is mutated, so an overflow leaves the account untouched.
+    /// # Errors
+    /// * An error is returned if a balance overflows.
+    ///
+    pub(crate) fn apply(&mut self, event: &Event) -> Result<(), EngineError> {
+        let client = self.client;
+        let add = |balance: Decimal, amount: Decimal| checked_add(client, balance, amount);
+        let sub = |balance: Decimal, amount: Decimal| checked_sub(client, balance, amount);
+
+        match *event {
+            Event::FundsDeposited {
+                currency, amount, ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.available = add(balance.available, amount)?;
+                balance.total = add(balance.total, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::FundsWithdrawn {
+                currency, amount, ..
+            }
+            | Event::FeeCharged {
+                currency, amount, ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.available = sub(balance.available, amount)?;
+                balance.total = sub(balance.total, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::FundsExchanged {
+                from,
+                amount,
+                to,
+                converted,
+                ..
+            } => {
+                let mut debited = self.balance(Some(from));
+                let mut credited = self.balance(Some(to));
+                debited.available = sub(debited.available, amount)?;
+                debited.total = sub(debited.total, amount)?;
+                credited.available = add(credited.available, converted)?;
+                credited.total = add(credited.total, converted)?;
+                self.balances.insert(Some(from), debited);
+                self.balances.insert(Some(to), credited);
+            }
+            Event::FundsAuthorized {
+                currency, amount, ..
+            }
+            | Event::DisputeOpened {
+                currency,
+                amount,
+                flow: Flow::Incoming,
+                ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.available = sub(balance.available, amount)?;
+                balance.held = add(balance.held, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::AuthorizationCaptured {
+                currency,
+                authorized,
+                captured,
+                ..
+            } => {
+                let mut balance = self.balance(currency);
+                let released = sub(authorized, captured)?;
+                balance.held = sub(balance.held, authorized)?;
+                balance.available = add(balance.available, released)?;
+                balance.total = sub(balance.total, captured)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::AuthorizationVoided {
+                currency, amount, ..
+            }
+            | Event::DisputeResolved {
+                currency,
+                amount,
+                flow: Flow::Incoming,
+                ..
+            }
+            | Event::ChargedBack {
+                currency,
+                amount,
+                flow: Flow::Outgoing,
+                ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.held = sub(balance.held, amount)?;
+                balance.available = add(balance.available, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::DisputeOpened {
+                currency,
+                amount,
+                flow: Flow::Outgoing,
+                ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.held = add(balance.held, amount)?;
+                balance.total = add(balance.total, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::DisputeResolved {
+                currency,
+                amount,
+                flow: Flow::Outgoing,
+                ..
+            }
+            | Event::ChargedBack {
+                currency,
+                amount,
+                flow: Flow::Incoming,
+                ..
+            } => {
+                let mut balance = self.balance(currency);
+                balance.held = sub(balance.held, amount)?;
+                balance.total = sub(balance.total, amount)?;
+                self.balances.insert(currency, balance);
+            }
+            Event::AccountLocked { .. } => self.status = AccountStatus::Locked,
+            Event::StatusChanged { status, .. } => self.status = status,
+        }
 
         Ok(())
     }
 
-    pub(crate) fn withdrawal(&mut self, amount: Decimal) -> anyhow::Result<()> {
-        is_amount_negative(&amount)?;
+    pub(crate) fn deposit(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Deposit)?;
+
+        self.record(vec![Event::FundsDeposited {
+            client: self.client,
+            currency,
+            amount,
+        }])
+    }
+
+    pub(crate) fn withdrawal(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
 
-        self.is_account_locked()?;
+        self.next_status(Type::Withdrawal)?;
 
-        // if the available balance is not enough for the withdrawal
+        // if the available balance, credit included, is not enough for the withdrawal
         // return error and do not perform operation.
-        if amount > self.available {
-            return Err(anyhow!(EngineError::InsufficientFunds));
-        }
+        self.has_funds(currency, amount)?;
+
+        self.record(vec![Event::FundsWithdrawn {
+            client: self.client,
+            currency,
+            amount,
+        }])
+    }
 
-        self.available -= amount;
-        self.total -= amount;
+    pub(crate) fn dispute(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
 
-        Ok(())
+        self.next_status(Type::Dispute)?;
+
+        self.record(vec![Event::DisputeOpened {
+            client: self.client,
+            currency,
+            amount,
+            flow: Flow::Incoming,
+        }])
     }
 
-    pub(crate) fn dispute(&mut self, amount: Decimal) -> anyhow::Result<()> {
-        is_amount_negative(&amount)?;
+    pub(crate) fn resolve(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
 
-        self.is_account_locked()?;
+        self.next_status(Type::Resolve)?;
 
-        self.available -= amount;
-        self.held += amount;
+        self.record(vec![Event::DisputeResolved {
+            client: self.client,
+            currency,
+            amount,
+            flow: Flow::Incoming,
+        }])
+    }
 
-        Ok(())
+    pub(crate) fn chargeback(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Chargeback)?;
+
+        self.record(vec![
+            Event::ChargedBack {
+                client: self.client,
+                currency,
+                amount,
+                flow: Flow::Incoming,
+            },
+            Event::AccountLocked {
+                client: self.client,
+            },
+        ])
     }
 
-    pub(crate) fn resolve(&mut self, amount: Decimal) -> anyhow::Result<()> {
-        is_amount_negative(&amount)?;
+    /// Disputing a withdrawal provisionally credits the withdrawn amount back to the account.
+    /// The amount is held (and therefore part of the total) until the dispute is settled.
+    pub(crate) fn dispute_withdrawal(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
 
-        self.is_account_locked()?;
+        self.next_status(Type::Dispute)?;
 
-        self.available += amount;
-        self.held -= amount;
+        self.record(vec![Event::DisputeOpened {
+            client: self.client,
+            currency,
+            amount,
+            flow: Flow::Outgoing,
+        }])
+    }
 
-        Ok(())
+    /// Resolving a disputed withdrawal confirms it, so the provisional credit is removed.
+    pub(crate) fn resolve_withdrawal(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Resolve)?;
+
+        self.record(vec![Event::DisputeResolved {
+            client: self.client,
+            currency,
+            amount,
+            flow: Flow::Outgoing,
+        }])
+    }
+
+    /// A chargeback of a disputed withdrawal reverses it: the provisional credit becomes available
+    /// and, as for any chargeback, the account is locked.
+    pub(crate) fn chargeback_withdrawal(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Chargeback)?;
+
+        self.record(vec![
+            Event::ChargedBack {
+                client: self.client,
+                currency,
+                amount,
+                flow: Flow::Outgoing,
+            },
+            Event::AccountLocked {
+                client: self.client,
+            },
+        ])
     }
 
-    pub(crate) fn chargeback(&mut self, amount: Decimal) -> anyhow::Result<()> {
-        is_amount_negative(&amount)?;
+    /// Charging a fee debits it from the available funds as part of the provided operation,
+    /// so it is allowed in the same statuses. Like a chargeback, a fee can leave the available funds negative.
+    pub(crate) fn charge_fee(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+        action: Type,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
 
-        self.is_account_locked()?;
+        self.next_status(action)?;
 
-        self.total -= amount;
-        self.held -= amount;
-        self.locked = true;
+        self.record(vec![Event::FeeCharged {
+            client: self.client,
+            currency,
+            amount,
+        }])
+    }
 
-        Ok(())
+    /// Exchanging debits the amount from the balance in `from` and credits the converted amount to the balance in `to`.
+    pub(crate) fn exchange(
+        &mut self,
+        from: Currency,
+        amount: Decimal,
+        to: Currency,
+        converted: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+        is_amount_negative(self.client, &converted)?;
+
+        self.next_status(Type::Exchange)?;
+
+        self.has_funds(Some(from), amount)?;
+
+        self.record(vec![Event::FundsExchanged {
+            client: self.client,
+            from,
+            amount,
+            to,
+            converted,
+        }])
+    }
+
+    /// Authorizing moves the amount from the available to the held funds until it is captured or voided.
+    pub(crate) fn authorize(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Authorize)?;
+
+        self.has_funds(currency, amount)?;
+
+        self.record(vec![Event::FundsAuthorized {
+            client: self.client,
+            currency,
+            amount,
+        }])
     }
 
-    fn is_account_locked(&self) -> anyhow::Result<()> {
-        if self.locked {
-            return Err(anyhow!(EngineError::AccountLocked));
+    /// Capturing settles the captured amount out of the account.
+    /// The whole authorization is released from the held funds, so the part that is not captured becomes available again.
+    pub(crate) fn capture(
+        &mut self,
+        currency: Option<Currency>,
+        authorized: Decimal,
+        captured: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &captured)?;
+
+        self.next_status(Type::Capture)?;
+
+        self.record(vec![Event::AuthorizationCaptured {
+            client: self.client,
+            currency,
+            authorized,
+            captured,
+        }])
+    }
+
+    /// Voiding releases the authorized amount back to the available funds.
+    pub(crate) fn void(
+        &mut self,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Vec<Event>, EngineError> {
+        is_amount_negative(self.client, &amount)?;
+
+        self.next_status(Type::Void)?;
+
+        self.record(vec![Event::AuthorizationVoided {
+            client: self.client,
+            currency,
+            amount,
+        }])
+    }
+
+    /// Administrative unlock of an account locked by a chargeback.
+    pub(crate) fn unlock(&mut self) -> Result<Vec<Event>, EngineError> {
+        let status = self.next_status(Type::Unlock)?;
+
+        self.change_status(status)
+    }
+
+    /// Administrative freeze: no funds can move in or out of the account until it is unfrozen,
+    /// but disputes can still be opened, resolved and chargeback.
+    pub(crate) fn freeze(&mut self) -> Result<Vec<Event>, EngineError> {
+        let status = self.next_status(Type::Freeze)?;
+
+        self.change_status(status)
+    }
+
+    pub(crate) fn unfreeze(&mut self) -> Result<Vec<Event>, EngineError> {
+        let status = self.next_status(Type::Unfreeze)?;
+
+        self.change_status(status)
+    }
+
+    /// Administrative close. Only an account without funds, available or held, in any currency can be closed.
+    pub(crate) fn close(&mut self) -> Result<Vec<Event>, EngineError> {
+        let status = self.next_status(Type::Close)?;
+
+        if let Some(balance) = self.balances.values().find(|balance| !balance.is_empty()) {
+            return Err(EngineError::AccountNotEmpty {
+                client: self.client,
+                held: balance.held,
+                total: balance.total,
+            });
+        }
+
+        self.change_status(status)
+    }
+
+    /// Records the new status set by an administrative operation.
+    fn change_status(&mut self, status: AccountStatus) -> Result<Vec<Event>, EngineError> {
+        self.record(vec![Event::StatusChanged {
+            client: self.client,
+            status,
+        }])
+    }
+
+    /// Applies the events of a validated operation and returns them.
+    /// Only the first event of an operation moves funds, so a failure leaves the account untouched.
+    /// # Errors
+    /// * An error is returned if a balance overflows.
+    ///
+    fn record(&mut self, events: Vec<Event>) -> Result<Vec<Event>, EngineError> {
+        for event in &events {
+            self.apply(event)?;
+        }
+
+        Ok(events)
+    }
+
+    /// Helper function to validate that the amount can be debited from the balance in the provided currency.
+    /// # Errors
+    /// * An error is returned if the amount is greater than the available funds, credit limit included.
+    ///
+    fn has_funds(&self, currency: Option<Currency>, amount: Decimal) -> Result<(), EngineError> {
+        let spendable = self.spendable(currency);
+        if amount > spendable {
+            return Err(EngineError::InsufficientFunds {
+                client: self.client,
+                available: spendable,
+                amount,
+            });
         }
 
         Ok(())
     }
+
+    /// Helper function to validate an operation against the status of the account.
+    /// # Errors
+    /// * An error is returned if the operation is not allowed in the current status.
+    ///
+    fn next_status(&self, action: Type) -> Result<AccountStatus, EngineError> {
+        AccountStatus::transition(self.client, self.status, action)
+    }
 }
 
 /// Helper function to validate if an amount.
 /// # Errors
 /// * An error is returned if the amount provided is negative.
 ///
-fn is_amount_negative(amount: &Decimal) -> anyhow::Result<()> {
+fn is_amount_negative(client: u16, amount: &Decimal) -> Result<(), EngineError> {
     if amount.is_sign_negative() {
-        return Err(anyhow!(EngineError::NegativeAmount));
+        return Err(EngineError::NegativeAmount {
+            client,
+            amount: *amount,
+        });
     }
 
     Ok(())
 }
 
+/// Helper function to add an amount to a balance.
+/// # Errors
+/// * An error is returned if the result does not fit in a `Decimal`.
+///
+fn checked_add(client: u16, balance: Decimal, amount: Decimal) -> Result<Decimal, EngineError> {
+    balance
+        .checked_add(amount)
+        .ok_or(EngineError::Overflow { client, amount })
+}
+
+/// Helper function to subtract an amount from a balance.
+/// # Errors
+/// * An error is returned if the result does not fit in a `Decimal`.
+///
+fn checked_sub(client: u16, balance: Decimal, amount: Decimal) -> Result<Decimal, EngineError> {
+    balance
+        .checked_sub(amount)
+        .ok_or(EngineError::Overflow { client, amount })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trx_engine::rounding::{Rounding, RoundingMode};
+    use std::collections::HashMap;
     #[test]
     fn is_amount_negative_when_provided_positive() {
         let positive: Decimal = dec!(1234);
-        let result = is_amount_negative(&positive);
+        let result = is_amount_negative(1234, &positive);
 
         assert!(result.is_ok());
     }
@@ -144,21 +827,47 @@ mod tests {
     #[test]
     fn is_amount_negative_when_provided_negative() {
         let positive: Decimal = dec!(-123);
-        let result = is_amount_negative(&positive);
+        let result = is_amount_negative(1234, &positive);
 
         assert!(result.is_err());
     }
 
+    #[test]
+    fn checked_add_when_overflows() {
+        let result = checked_add(1234, Decimal::MAX, dec!(1));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: 1234,
+                amount: dec!(1)
+            }
+        );
+    }
+
+    #[test]
+    fn checked_sub_when_overflows() {
+        let result = checked_sub(1234, Decimal::MIN, dec!(1));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: 1234,
+                amount: dec!(1)
+            }
+        );
+    }
+
     #[test]
     fn account_new() {
         let account_id: u16 = 1234;
         let account = Account::new(account_id);
 
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked)
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked())
     }
 
     #[test]
@@ -167,14 +876,14 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let deposit_amount: Decimal = dec!(1500);
-        let result = account.deposit(deposit_amount);
+        let result = account.deposit(None, deposit_amount);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, deposit_amount);
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, deposit_amount);
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, deposit_amount);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, deposit_amount);
+        assert!(!account.locked());
     }
 
     #[test]
@@ -183,18 +892,21 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let deposit_amount: Decimal = dec!(-1500);
-        let result = account.deposit(deposit_amount);
+        let result = account.deposit(None, deposit_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: deposit_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
     }
 
     #[test]
@@ -202,21 +914,21 @@ mod tests {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
         // mutate private field to cater to the test. This should not be possible with the public API.
-        account.locked = true;
+        account.status = AccountStatus::Locked;
 
         let deposit_amount: Decimal = dec!(1500);
-        let result = account.deposit(deposit_amount);
+        let result = account.deposit(None, deposit_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
     }
 
     #[test]
@@ -226,17 +938,25 @@ mod tests {
 
         // adding have balance to enable withdrawal
         let deposit_amount: Decimal = dec!(1500);
-        account.deposit(deposit_amount).expect("failed to deposit");
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
 
         let withdrawal_amount: Decimal = dec!(500);
-        let result = account.withdrawal(withdrawal_amount);
+        let result = account.withdrawal(None, withdrawal_amount);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, deposit_amount - withdrawal_amount);
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, deposit_amount - withdrawal_amount);
-        assert!(!account.locked);
+        assert_eq!(
+            account.balance(None).available,
+            deposit_amount - withdrawal_amount
+        );
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(
+            account.balance(None).total,
+            deposit_amount - withdrawal_amount
+        );
+        assert!(!account.locked());
     }
 
     #[test]
@@ -245,18 +965,21 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let withdrawal_amount: Decimal = dec!(-500);
-        let result = account.withdrawal(withdrawal_amount);
+        let result = account.withdrawal(None, withdrawal_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: withdrawal_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
     }
 
     #[test]
@@ -264,21 +987,21 @@ mod tests {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
         // mutate private field to cater to the test. This should not be possible with the public API.
-        account.locked = true;
+        account.status = AccountStatus::Locked;
 
         let withdrawal_amount: Decimal = dec!(500);
-        let result = account.withdrawal(withdrawal_amount);
+        let result = account.withdrawal(None, withdrawal_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
     }
 
     #[test]
@@ -287,18 +1010,87 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let withdrawal_amount: Decimal = dec!(500);
-        let result = account.withdrawal(withdrawal_amount);
+        let result = account.withdrawal(None, withdrawal_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::InsufficientFunds.to_string()
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client: account_id,
+                available: dec!(0),
+                amount: withdrawal_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_withdrawal_successful_within_credit_limit() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private field to cater to the test. Limits are set by the ledger when the account is opened.
+        account.credit_limit = dec!(100);
+
+        account.deposit(None, dec!(50)).expect("failed to deposit");
+        account
+            .withdrawal(None, dec!(150))
+            .expect("failed to withdrawal");
+
+        assert_eq!(account.balance(None).available, dec!(-100));
+        assert_eq!(account.balance(None).total, dec!(-100));
+        assert_eq!(account.spendable(None), dec!(0));
+        assert!(!account.over_limit(None));
+    }
+
+    #[test]
+    fn account_withdrawal_fail_when_credit_limit_exceeded() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private field to cater to the test. Limits are set by the ledger when the account is opened.
+        account.credit_limit = dec!(100);
+
+        account.deposit(None, dec!(50)).expect("failed to deposit");
+        let result = account.withdrawal(None, dec!(150.01));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client: account_id,
+                available: dec!(150),
+                amount: dec!(150.01)
+            }
+        );
+        assert_eq!(account.balance(None).available, dec!(50));
+        assert_eq!(account.balance(None).total, dec!(50));
+    }
+
+    #[test]
+    fn account_records_flag_over_limit() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private field to cater to the test. Limits are set by the ledger when the account is opened.
+        account.credit_limit = dec!(100);
+
+        account.deposit(None, dec!(50)).expect("failed to deposit");
+        account
+            .withdrawal(None, dec!(150))
+            .expect("failed to withdrawal");
+        assert!(!account.records()[0].over_limit);
+
+        // the deposit is charged back after its funds were withdrawn.
+        account.dispute(None, dec!(50)).expect("failed to dispute");
+        account
+            .chargeback(None, dec!(50))
+            .expect("failed to chargeback");
+
+        assert_eq!(account.balance(None).available, dec!(-150));
+        assert!(account.over_limit(None));
+        assert_eq!(account.records().len(), 1);
+        assert!(account.records()[0].over_limit);
     }
 
     #[test]
@@ -308,17 +1100,22 @@ mod tests {
 
         // adding have balance to enable withdrawal
         let deposit_amount: Decimal = dec!(1500);
-        account.deposit(deposit_amount).expect("failed to deposit");
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
 
         let dispute_amount: Decimal = dec!(500);
-        let result = account.dispute(dispute_amount);
+        let result = account.dispute(None, dispute_amount);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, deposit_amount - dispute_amount);
-        assert_eq!(account.held, dispute_amount);
-        assert_eq!(account.total, deposit_amount);
-        assert!(!account.locked);
+        assert_eq!(
+            account.balance(None).available,
+            deposit_amount - dispute_amount
+        );
+        assert_eq!(account.balance(None).held, dispute_amount);
+        assert_eq!(account.balance(None).total, deposit_amount);
+        assert!(!account.locked());
     }
 
     #[test]
@@ -327,18 +1124,21 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let dispute_amount: Decimal = dec!(-500);
-        let result = account.dispute(dispute_amount);
+        let result = account.dispute(None, dispute_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: dispute_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
     }
 
     #[test]
@@ -346,21 +1146,21 @@ mod tests {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
         // mutate private field to cater to the test. This should not be possible with the public API.
-        account.locked = true;
+        account.status = AccountStatus::Locked;
 
         let dispute_amount: Decimal = dec!(500);
-        let result = account.dispute(dispute_amount);
+        let result = account.dispute(None, dispute_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
     }
 
     #[test]
@@ -370,23 +1170,27 @@ mod tests {
 
         // adding have balance to enable withdrawal
         let deposit_amount: Decimal = dec!(1500);
-        account.deposit(deposit_amount).expect("failed to deposit");
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
         // disputing a transaction to enable resolve
         let dispute_amount: Decimal = dec!(500);
-        account.dispute(dispute_amount).expect("failed to dispute");
+        account
+            .dispute(None, dispute_amount)
+            .expect("failed to dispute");
 
         let resolve_amount: Decimal = dec!(500);
-        let result = account.resolve(resolve_amount);
+        let result = account.resolve(None, resolve_amount);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
         assert_eq!(
-            account.available,
+            account.balance(None).available,
             deposit_amount - dispute_amount + resolve_amount
         );
-        assert_eq!(account.held, dispute_amount - resolve_amount);
-        assert_eq!(account.total, deposit_amount);
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).held, dispute_amount - resolve_amount);
+        assert_eq!(account.balance(None).total, deposit_amount);
+        assert!(!account.locked());
     }
 
     #[test]
@@ -395,18 +1199,21 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let resolve_amount: Decimal = dec!(-500);
-        let result = account.resolve(resolve_amount);
+        let result = account.resolve(None, resolve_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: resolve_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
     }
 
     #[test]
@@ -414,21 +1221,21 @@ mod tests {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
         // mutate private field to cater to the test. This should not be possible with the public API.
-        account.locked = true;
+        account.status = AccountStatus::Locked;
 
         let resolve_amount: Decimal = dec!(500);
-        let result = account.resolve(resolve_amount);
+        let result = account.resolve(None, resolve_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
     }
 
     #[test]
@@ -438,29 +1245,38 @@ mod tests {
 
         // adding have balance to enable withdrawal
         let deposit_amount: Decimal = dec!(1500);
-        account.deposit(deposit_amount).expect("failed to deposit");
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
         // disputing a transaction to enable resolve
         let dispute_amount: Decimal = dec!(500);
-        account.dispute(dispute_amount).expect("failed to dispute");
+        account
+            .dispute(None, dispute_amount)
+            .expect("failed to dispute");
         // resolving a transaction to enable a chargeback
         let resolve_amount: Decimal = dec!(500);
-        account.resolve(resolve_amount).expect("failed to resolve");
+        account
+            .resolve(None, resolve_amount)
+            .expect("failed to resolve");
 
         let chargeback_amount: Decimal = dec!(500);
-        let result = account.chargeback(chargeback_amount);
+        let result = account.chargeback(None, chargeback_amount);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
         assert_eq!(
-            account.available,
+            account.balance(None).available,
             deposit_amount - dispute_amount + resolve_amount
         );
         assert_eq!(
-            account.held,
+            account.balance(None).held,
             dispute_amount - resolve_amount - chargeback_amount
         );
-        assert_eq!(account.total, deposit_amount - chargeback_amount);
-        assert!(account.locked);
+        assert_eq!(
+            account.balance(None).total,
+            deposit_amount - chargeback_amount
+        );
+        assert!(account.locked());
     }
 
     #[test]
@@ -469,17 +1285,20 @@ mod tests {
         let mut account = Account::new(account_id);
 
         let resolve_amount: Decimal = dec!(-500);
-        let result = account.chargeback(resolve_amount);
+        let result = account.chargeback(None, resolve_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: resolve_amount
+            }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
     }
 
     #[test]
@@ -487,54 +1306,728 @@ mod tests {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
         // mutate private field to cater to the test. This should not be possible with the public API.
-        account.locked = true;
+        account.status = AccountStatus::Locked;
 
         let resolve_amount: Decimal = dec!(500);
-        let result = account.chargeback(resolve_amount);
+        let result = account.chargeback(None, resolve_amount);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+    }
+
+    #[test]
+    fn account_dispute_withdrawal_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+
+        let deposit_amount: Decimal = dec!(1500);
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
+        let withdrawal_amount: Decimal = dec!(500);
+        account
+            .withdrawal(None, withdrawal_amount)
+            .expect("failed to withdrawal");
+
+        let result = account.dispute_withdrawal(None, withdrawal_amount);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            account.balance(None).available,
+            deposit_amount - withdrawal_amount
+        );
+        assert_eq!(account.balance(None).held, withdrawal_amount);
+        assert_eq!(account.balance(None).total, deposit_amount);
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_dispute_withdrawal_fail_when_account_is_locked() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private field to cater to the test. This should not be possible with the public API.
+        account.status = AccountStatus::Locked;
+
+        let dispute_amount: Decimal = dec!(500);
+        let result = account.dispute_withdrawal(None, dispute_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
+        );
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+    }
+
+    #[test]
+    fn account_resolve_withdrawal_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+
+        let deposit_amount: Decimal = dec!(1500);
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
+        let withdrawal_amount: Decimal = dec!(500);
+        account
+            .withdrawal(None, withdrawal_amount)
+            .expect("failed to withdrawal");
+        account
+            .dispute_withdrawal(None, withdrawal_amount)
+            .expect("failed to dispute");
+
+        let result = account.resolve_withdrawal(None, withdrawal_amount);
+
+        assert!(result.is_ok());
+        assert_eq!(
+            account.balance(None).available,
+            deposit_amount - withdrawal_amount
+        );
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(
+            account.balance(None).total,
+            deposit_amount - withdrawal_amount
+        );
+        assert!(!account.locked());
     }
 
     #[test]
-    fn account_is_account_locked_when_account_locked() {
+    fn account_chargeback_withdrawal_successful() {
         let account_id: u16 = 1234;
         let mut account = Account::new(account_id);
-        account.locked = true;
 
-        let result = account.is_account_locked();
+        let deposit_amount: Decimal = dec!(1500);
+        account
+            .deposit(None, deposit_amount)
+            .expect("failed to deposit");
+        let withdrawal_amount: Decimal = dec!(500);
+        account
+            .withdrawal(None, withdrawal_amount)
+            .expect("failed to withdrawal");
+        account
+            .dispute_withdrawal(None, withdrawal_amount)
+            .expect("failed to dispute");
+
+        let result = account.chargeback_withdrawal(None, withdrawal_amount);
+
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, deposit_amount);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, deposit_amount);
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn account_chargeback_withdrawal_fail_when_provided_negative_amount() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+
+        let chargeback_amount: Decimal = dec!(-500);
+        let result = account.chargeback_withdrawal(None, chargeback_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client: account_id,
+                amount: chargeback_amount
+            }
+        );
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_next_status_when_account_locked() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.status = AccountStatus::Locked;
+
+        let result = account.next_status(Type::Deposit);
 
         assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::AccountLocked.to_string()
+            result.unwrap_err(),
+            EngineError::AccountLocked { client: account_id }
         );
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
     }
 
     #[test]
-    fn account_is_account_locked_when_account_not_locked() {
+    fn account_next_status_when_account_not_locked() {
         let account_id: u16 = 1234;
         let account = Account::new(account_id);
 
-        let result = account.is_account_locked();
+        let result = account.next_status(Type::Deposit);
 
         assert!(result.is_ok());
         assert_eq!(account.client, account_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(!account.locked);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_deposit_fail_when_balance_overflows() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account
+            .deposit(None, Decimal::MAX)
+            .expect("failed to deposit");
+
+        let deposit_amount: Decimal = dec!(1);
+        let result = account.deposit(None, deposit_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: account_id,
+                amount: deposit_amount
+            }
+        );
+        assert_eq!(account.balance(None).available, Decimal::MAX);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, Decimal::MAX);
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_dispute_fail_when_held_overflows() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private fields to cater to the test. This should not be possible with the public API.
+        account.balances.entry(None).or_default().available = dec!(1);
+        account.balances.entry(None).or_default().held = Decimal::MAX;
+
+        let dispute_amount: Decimal = dec!(1);
+        let result = account.dispute(None, dispute_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: account_id,
+                amount: dispute_amount
+            }
+        );
+        // the available balance was computed before held and must not be partially applied.
+        assert_eq!(account.balance(None).available, dec!(1));
+        assert_eq!(account.balance(None).held, Decimal::MAX);
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_resolve_fail_when_available_overflows() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account
+            .deposit(None, Decimal::MAX)
+            .expect("failed to deposit");
+
+        let resolve_amount: Decimal = dec!(1);
+        let result = account.resolve(None, resolve_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: account_id,
+                amount: resolve_amount
+            }
+        );
+        assert_eq!(account.balance(None).available, Decimal::MAX);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, Decimal::MAX);
+    }
+
+    #[test]
+    fn account_chargeback_fail_when_total_overflows() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        // mutate private field to cater to the test. This should not be possible with the public API.
+        account.balances.entry(None).or_default().total = Decimal::MIN;
+
+        let chargeback_amount: Decimal = dec!(1);
+        let result = account.chargeback(None, chargeback_amount);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client: account_id,
+                amount: chargeback_amount
+            }
+        );
+        assert_eq!(account.balance(None).total, Decimal::MIN);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn account_status_transition_when_closed() {
+        let actions = [
+            Type::Deposit,
+            Type::Withdrawal,
+            Type::Dispute,
+            Type::Resolve,
+            Type::Chargeback,
+            Type::Unlock,
+            Type::Freeze,
+            Type::Unfreeze,
+            Type::Close,
+        ];
+
+        for action in actions {
+            assert_eq!(
+                AccountStatus::transition(1, AccountStatus::Closed, action).unwrap_err(),
+                EngineError::AccountClosed { client: 1 }
+            );
+        }
+    }
+
+    #[test]
+    fn account_status_transition_when_frozen() {
+        let allowed = [
+            (Type::Dispute, AccountStatus::Frozen),
+            (Type::Resolve, AccountStatus::Frozen),
+            (Type::Chargeback, AccountStatus::Locked),
+            (Type::Unfreeze, AccountStatus::Active),
+            (Type::Close, AccountStatus::Closed),
+        ];
+        for (action, status) in allowed {
+            assert_eq!(
+                AccountStatus::transition(1, AccountStatus::Frozen, action).unwrap(),
+                status
+            );
+        }
+
+        let rejected = [
+            (Type::Deposit, EngineError::AccountFrozen { client: 1 }),
+            (Type::Withdrawal, EngineError::AccountFrozen { client: 1 }),
+            (Type::Freeze, EngineError::AccountFrozen { client: 1 }),
+            (Type::Unlock, EngineError::AccountNotLocked { client: 1 }),
+        ];
+        for (action, error) in rejected {
+            assert_eq!(
+                AccountStatus::transition(1, AccountStatus::Frozen, action).unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn account_unlock_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+        account.dispute(None, dec!(10)).expect("failed to dispute");
+        account
+            .chargeback(None, dec!(10))
+            .expect("failed to chargeback");
+        assert_eq!(account.status, AccountStatus::Locked);
+
+        let result = account.unlock();
+
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Active);
+        assert!(account.deposit(None, dec!(5)).is_ok());
+    }
+
+    #[test]
+    fn account_unlock_fail_when_account_not_locked() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+
+        let result = account.unlock();
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotLocked { client: account_id }
+        );
+        assert_eq!(account.status, AccountStatus::Active);
+    }
+
+    #[test]
+    fn account_freeze_still_accepts_disputes() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+
+        account.freeze().expect("failed to freeze");
+        assert_eq!(account.status, AccountStatus::Frozen);
+        assert!(account.locked());
+
+        assert_eq!(
+            account.deposit(None, dec!(5)).unwrap_err(),
+            EngineError::AccountFrozen { client: account_id }
+        );
+        assert_eq!(
+            account.withdrawal(None, dec!(5)).unwrap_err(),
+            EngineError::AccountFrozen { client: account_id }
+        );
+
+        account.dispute(None, dec!(10)).expect("failed to dispute");
+        assert_eq!(account.status, AccountStatus::Frozen);
+        account
+            .chargeback(None, dec!(10))
+            .expect("failed to chargeback");
+        assert_eq!(account.status, AccountStatus::Locked);
+        assert_eq!(account.balance(None).total, dec!(0));
+    }
+
+    #[test]
+    fn account_unfreeze_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.freeze().expect("failed to freeze");
+
+        let result = account.unfreeze();
+
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Active);
+        assert_eq!(
+            account.unfreeze().unwrap_err(),
+            EngineError::AccountNotFrozen { client: account_id }
+        );
+    }
+
+    #[test]
+    fn account_close_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+        account
+            .withdrawal(None, dec!(10))
+            .expect("failed to withdrawal");
+
+        let result = account.close();
+
+        assert!(result.is_ok());
+        assert_eq!(account.status, AccountStatus::Closed);
+        assert_eq!(
+            account.deposit(None, dec!(10)).unwrap_err(),
+            EngineError::AccountClosed { client: account_id }
+        );
+    }
+
+    #[test]
+    fn account_close_fail_when_account_holds_funds() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+        account.dispute(None, dec!(4)).expect("failed to dispute");
+
+        let result = account.close();
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotEmpty {
+                client: account_id,
+                held: dec!(4),
+                total: dec!(10)
+            }
+        );
+        assert_eq!(account.status, AccountStatus::Active);
+    }
+
+    #[test]
+    fn account_serialize_reports_status() {
+        let mut account = Account::new(1);
+        account.status = AccountStatus::Frozen;
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for record in account.records() {
+            writer.serialize(record).expect("failed to serialize");
+        }
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            "client,available,held,total,locked,status,currency,over_limit\n1,0.0,0.0,0.0,true,frozen,,false\n"
+        );
+    }
+
+    #[test]
+    fn account_serialize_one_record_per_currency() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let mut account = Account::new(1);
+        account.deposit(None, dec!(1.5)).expect("failed to deposit");
+        account
+            .deposit(Some(eur), dec!(2.5))
+            .expect("failed to deposit");
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for record in account.records() {
+            writer.serialize(record).expect("failed to serialize");
+        }
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            "client,available,held,total,locked,status,currency,over_limit\n1,1.5,0.0,1.5,false,active,,false\n1,2.5,0.0,2.5,false,active,EUR,false\n"
+        );
+    }
+
+    #[test]
+    fn format_account_precision_of_decimals_for_report_per_currency() {
+        let jpy: Currency = "JPY".parse().unwrap();
+        let precision = PrecisionPolicy {
+            default: Rounding {
+                decimal_places: 2,
+                mode: RoundingMode::HalfUp,
+            },
+            currencies: HashMap::from([(
+                jpy,
+                Rounding {
+                    decimal_places: 0,
+                    mode: RoundingMode::Truncate,
+                },
+            )]),
+        };
+        let mut account = Account::new(1);
+        account
+            .deposit(None, dec!(1.005))
+            .expect("failed to deposit");
+        account
+            .deposit(Some(jpy), dec!(10.9))
+            .expect("failed to deposit");
+        account
+            .dispute(Some(jpy), dec!(0.5))
+            .expect("failed to dispute");
+
+        let formatted = account.format_account_precision_of_decimals_for_report(&precision);
+
+        assert_eq!(formatted.balance(None).available, dec!(1.01));
+        assert_eq!(formatted.balance(None).total, dec!(1.01));
+        assert_eq!(formatted.balance(Some(jpy)).available, dec!(10));
+        assert_eq!(formatted.balance(Some(jpy)).held, dec!(0));
+        assert_eq!(formatted.balance(Some(jpy)).total, dec!(10));
+        assert_eq!(formatted.status, account.status);
+    }
+
+    #[test]
+    fn account_deserialize_without_status() {
+        let input = "client,available,held,total,locked\n1,0.0,0.0,0.0,true\n2,0.0,0.0,0.0,false\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+
+        let records: Vec<AccountRecord> = reader
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .expect("failed to deserialize");
+
+        assert_eq!(records[0].status(), AccountStatus::Locked);
+        assert_eq!(records[1].status(), AccountStatus::Active);
+        assert_eq!(records[0].currency, None);
+    }
+
+    #[test]
+    fn account_balances_are_kept_per_currency() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+        let mut account = Account::new(1);
+        account
+            .deposit(Some(eur), dec!(10))
+            .expect("failed to deposit");
+        account
+            .deposit(Some(usd), dec!(5))
+            .expect("failed to deposit");
+
+        let result = account.withdrawal(Some(usd), dec!(6));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client: 1,
+                available: dec!(5),
+                amount: dec!(6)
+            }
+        );
+        assert!(account.dispute(Some(eur), dec!(4)).is_ok());
+        assert_eq!(account.balance(Some(eur)).available, dec!(6));
+        assert_eq!(account.balance(Some(eur)).held, dec!(4));
+        assert_eq!(account.balance(Some(usd)).available, dec!(5));
+        assert_eq!(account.balance(Some(usd)).held, dec!(0));
+        assert_eq!(account.balance(None), Balance::default());
+    }
+
+    #[test]
+    fn account_exchange_successful() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+        let mut account = Account::new(1);
+        account
+            .deposit(Some(eur), dec!(10))
+            .expect("failed to deposit");
+
+        let result = account.exchange(eur, dec!(4), usd, dec!(4.4));
+
+        assert!(result.is_ok());
+        assert_eq!(account.balance(Some(eur)).available, dec!(6));
+        assert_eq!(account.balance(Some(eur)).total, dec!(6));
+        assert_eq!(account.balance(Some(usd)).available, dec!(4.4));
+        assert_eq!(account.balance(Some(usd)).total, dec!(4.4));
+    }
+
+    #[test]
+    fn account_exchange_fails_when_account_frozen() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+        let mut account = Account::new(1);
+        account
+            .deposit(Some(eur), dec!(10))
+            .expect("failed to deposit");
+        account.status = AccountStatus::Frozen;
+
+        let result = account.exchange(eur, dec!(4), usd, dec!(4.4));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountFrozen { client: 1 }
+        );
+        assert_eq!(account.balance(Some(eur)).available, dec!(10));
+        assert_eq!(account.balance(Some(usd)), Balance::default());
+    }
+
+    #[test]
+    fn account_charge_fee_allowed_with_operation() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(1)).expect("failed to deposit");
+        account.status = AccountStatus::Frozen;
+
+        let result = account.charge_fee(None, dec!(2), Type::Withdrawal);
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountFrozen { client: account_id }
+        );
+
+        let result = account.charge_fee(None, dec!(2), Type::Chargeback);
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, dec!(-1));
+        assert_eq!(account.balance(None).total, dec!(-1));
+        // unlike the chargeback itself, the fee does not change the status.
+        assert_eq!(account.status, AccountStatus::Frozen);
+    }
+
+    #[test]
+    fn account_close_fails_with_funds_in_any_currency() {
+        let eur: Currency = "EUR".parse().unwrap();
+        let mut account = Account::new(1);
+        account
+            .deposit(Some(eur), dec!(10))
+            .expect("failed to deposit");
+
+        let result = account.close();
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotEmpty {
+                client: 1,
+                held: dec!(0),
+                total: dec!(10)
+            }
+        );
+    }
+
+    #[test]
+    fn account_authorize_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(100)).expect("failed to deposit");
+
+        let result = account.authorize(None, dec!(40));
+
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, dec!(60));
+        assert_eq!(account.balance(None).held, dec!(40));
+        assert_eq!(account.balance(None).total, dec!(100));
+    }
+
+    #[test]
+    fn account_authorize_fail_when_insufficient_funds() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+
+        let result = account.authorize(None, dec!(40));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client: account_id,
+                available: dec!(10),
+                amount: dec!(40)
+            }
+        );
+        assert_eq!(account.balance(None).available, dec!(10));
+        assert_eq!(account.balance(None).held, dec!(0));
+    }
+
+    #[test]
+    fn account_capture_partial_releases_the_rest() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(100)).expect("failed to deposit");
+        account
+            .authorize(None, dec!(40))
+            .expect("failed to authorize");
+
+        let result = account.capture(None, dec!(40), dec!(25));
+
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, dec!(75));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(75));
+    }
+
+    #[test]
+    fn account_capture_fail_when_account_is_frozen() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(100)).expect("failed to deposit");
+        account
+            .authorize(None, dec!(40))
+            .expect("failed to authorize");
+        account.freeze().expect("failed to freeze");
+
+        let result = account.capture(None, dec!(40), dec!(40));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountFrozen { client: account_id }
+        );
+        assert_eq!(account.balance(None).held, dec!(40));
+
+        // a frozen account can still release its authorizations.
+        assert!(account.void(None, dec!(40)).is_ok());
+        assert_eq!(account.balance(None).available, dec!(100));
+        assert_eq!(account.balance(None).held, dec!(0));
+    }
+
+    #[test]
+    fn account_void_successful() {
+        let account_id: u16 = 1234;
+        let mut account = Account::new(account_id);
+        account.deposit(None, dec!(100)).expect("failed to deposit");
+        account
+            .authorize(None, dec!(40))
+            .expect("failed to authorize");
+
+        let result = account.void(None, dec!(40));
+
+        assert!(result.is_ok());
+        assert_eq!(account.balance(None).available, dec!(100));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(100));
     }
 }
@@ -0,0 +1,303 @@
+use crate::trx_engine::account::Account;
+use crate::trx_engine::journal::read_lines;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Storage of the accounts of the ledger.
+/// The ledger only writes to the store once an input was processed successfully.
+pub trait AccountStore {
+    /// Returns the account of the provided client, if any.
+    fn get(&self, client: u16) -> io::Result<Option<Cow<'_, Account>>>;
+
+    /// Stores a new account, or a new version of an existing one, under its client id.
+    fn insert(&mut self, account: Account) -> io::Result<()>;
+
+    /// Removes the account of the provided client, if any.
+    /// Only used by the ledger to roll back an account opened by an input whose changes could not all be stored.
+    fn remove(&mut self, client: u16) -> io::Result<()>;
+
+    /// Returns every account of the store, in no particular order.
+    fn accounts(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Account>>> + '_>;
+
+    /// Makes the changes stored since the last call outlive the ledger, along with the sequence of the last input
+    /// they hold, see `Ledger::checkpoint`. Stores keeping their accounts for the lifetime of the ledger only have nothing to do.
+    fn persist(&mut self, _sequence: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Sequence of the last input whose changes were persisted, so a recovery does not apply them again,
+    /// see `Ledger::recover`. Stores keeping their accounts for the lifetime of the ledger only hold none.
+    fn sequence(&self) -> u64 {
+        0
+    }
+}
+
+/// The default store, accounts are kept in memory for the lifetime of the ledger.
+impl AccountStore for HashMap<u16, Account> {
+    fn get(&self, client: u16) -> io::Result<Option<Cow<'_, Account>>> {
+        Ok(HashMap::get(self, &client).map(Cow::Borrowed))
+    }
+
+    fn insert(&mut self, account: Account) -> io::Result<()> {
+        HashMap::insert(self, account.client, account);
+        Ok(())
+    }
+
+    fn remove(&mut self, client: u16) -> io::Result<()> {
+        HashMap::remove(self, &client);
+        Ok(())
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Account>>> + '_> {
+        Box::new(self.values().map(|account| Ok(Cow::Borrowed(account))))
+    }
+}
+
+/// Store persisting the accounts to a file, so a later run of the engine continues from the balances
+/// and statuses left by the previous one.
+///
+/// The accounts are kept in memory, clients being identified by a `u16` the store never holds more than 65536 accounts.
+/// Changes only reach the file when they are persisted, e.g. once a file was processed successfully,
+/// so a run that is aborted or crashes leaves the accounts of the previous one.
+/// The file holds the sequence of the last input persisted, then the latest version of every account, as lines of json.
+/// It is written aside and renamed over the previous one, so it always holds the accounts of a single persist.
+#[derive(Debug)]
+pub struct FileAccountStore {
+    path: PathBuf,
+    accounts: HashMap<u16, Account>,
+    /// Sequence of the last input whose changes were persisted.
+    sequence: u64,
+    /// Whether accounts were changed since they were last persisted.
+    changed: bool,
+}
+
+/// Line of the file of a `FileAccountStore`: an account, or the sequence of the last input persisted.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum Line<'a> {
+    Account(Cow<'a, Account>),
+    Sequence { sequence: u64 },
+}
+
+impl FileAccountStore {
+    /// Opens the store persisted in the provided file, or creates an empty one if the file does not exist.
+    /// # Errors
+    /// * An error is returned if the file cannot be read, or any line cannot be parsed.
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let mut accounts = HashMap::new();
+        let mut sequence = 0;
+        match File::open(path) {
+            Ok(file) => {
+                for line in read_lines::<_, Line>(file) {
+                    match line? {
+                        Line::Account(account) => {
+                            let account = account.into_owned();
+                            accounts.insert(account.client, account);
+                        }
+                        Line::Sequence {
+                            sequence: persisted,
+                        } => sequence = persisted,
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            accounts,
+            sequence,
+            changed: false,
+        })
+    }
+}
+
+/// Replaces the file of the store with the provided sequence and accounts.
+/// The file is written aside, synced and renamed over the previous one, then the rename itself is synced,
+/// so a crash leaves either the previous accounts or the new ones.
+fn write_accounts(path: &Path, sequence: u64, accounts: &HashMap<u16, Account>) -> io::Result<()> {
+    let mut written = path.as_os_str().to_owned();
+    written.push(".tmp");
+    let written = PathBuf::from(written);
+    let mut writer = BufWriter::new(File::create(&written)?);
+    let lines = std::iter::once(Line::Sequence { sequence }).chain(
+        accounts
+            .values()
+            .map(|account| Line::Account(Cow::Borrowed(account))),
+    );
+    for line in lines {
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.into_inner()?.sync_all()?;
+    fs::rename(&written, path)?;
+
+    // the rename is only durable once the directory holding the file is synced.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+impl AccountStore for FileAccountStore {
+    fn get(&self, client: u16) -> io::Result<Option<Cow<'_, Account>>> {
+        Ok(self.accounts.get(&client).map(Cow::Borrowed))
+    }
+
+    fn insert(&mut self, account: Account) -> io::Result<()> {
+        self.accounts.insert(account.client, account);
+        self.changed = true;
+        Ok(())
+    }
+
+    fn remove(&mut self, client: u16) -> io::Result<()> {
+        if self.accounts.remove(&client).is_some() {
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    fn accounts(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Account>>> + '_> {
+        Box::new(
+            self.accounts
+                .values()
+                .map(|account| Ok(Cow::Borrowed(account))),
+        )
+    }
+
+    fn persist(&mut self, sequence: u64) -> io::Result<()> {
+        if !self.changed && sequence == self.sequence {
+            return Ok(());
+        }
+
+        write_accounts(&self.path, sequence, &self.accounts)?;
+        self.sequence = sequence;
+        self.changed = false;
+        Ok(())
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trx_engine::account::AccountStatus;
+    use crate::trx_engine::currency::Currency;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn hash_map_store_insert_and_get() {
+        let mut store: HashMap<u16, Account> = HashMap::new();
+
+        AccountStore::insert(&mut store, Account::new(1)).expect("failed to insert");
+
+        assert!(AccountStore::get(&store, 1)
+            .expect("failed to get")
+            .is_some());
+        assert!(AccountStore::get(&store, 2)
+            .expect("failed to get")
+            .is_none());
+        assert_eq!(store.accounts().count(), 1);
+    }
+
+    #[test]
+    fn file_store_reopens_persisted_accounts() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("accounts.jsonl");
+        let eur: Currency = "EUR".parse().unwrap();
+
+        let mut account = Account::new(1);
+        account
+            .deposit(None, dec!(10.5))
+            .expect("failed to deposit");
+        account
+            .deposit(Some(eur), dec!(3))
+            .expect("failed to deposit");
+
+        let mut store = FileAccountStore::open(&path).expect("failed to open store");
+        store.insert(account.clone()).expect("failed to insert");
+        account.status = AccountStatus::Locked;
+        store.insert(account.clone()).expect("failed to insert");
+        store.insert(Account::new(2)).expect("failed to insert");
+        store.persist(3).expect("failed to persist");
+        drop(store);
+
+        let store = FileAccountStore::open(&path).expect("failed to reopen store");
+
+        assert_eq!(
+            store.get(1).expect("failed to get").as_deref(),
+            Some(&account)
+        );
+        assert!(store.get(2).expect("failed to get").is_some());
+        assert_eq!(store.accounts().count(), 2);
+        assert_eq!(store.sequence(), 3);
+        // the file holds the sequence, then a line per account.
+        let lines = fs::read_to_string(&path).expect("failed to read store");
+        assert_eq!(lines.lines().count(), 3);
+    }
+
+    #[test]
+    fn file_store_does_not_keep_changes_not_persisted() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("accounts.jsonl");
+
+        let mut store = FileAccountStore::open(&path).expect("failed to open store");
+        store.insert(Account::new(1)).expect("failed to insert");
+        store.persist(1).expect("failed to persist");
+        store.insert(Account::new(2)).expect("failed to insert");
+        store.remove(1).expect("failed to remove");
+        drop(store);
+
+        let store = FileAccountStore::open(&path).expect("failed to reopen store");
+
+        assert!(store.get(1).expect("failed to get").is_some());
+        assert!(store.get(2).expect("failed to get").is_none());
+        assert_eq!(store.sequence(), 1);
+    }
+
+    #[test]
+    fn file_store_remove_is_persisted() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("accounts.jsonl");
+
+        let mut store = FileAccountStore::open(&path).expect("failed to open store");
+        store.insert(Account::new(1)).expect("failed to insert");
+        store.insert(Account::new(2)).expect("failed to insert");
+        store.persist(2).expect("failed to persist");
+        store.remove(1).expect("failed to remove");
+        store.persist(3).expect("failed to persist");
+        drop(store);
+
+        let store = FileAccountStore::open(&path).expect("failed to reopen store");
+
+        assert!(store.get(1).expect("failed to get").is_none());
+        assert!(store.get(2).expect("failed to get").is_some());
+        assert!(!dir.path().join("accounts.jsonl.tmp").exists());
+    }
+
+    #[test]
+    fn file_store_open_fails_on_invalid_line() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("accounts.jsonl");
+        fs::write(&path, "not an account\n").expect("failed to write store");
+
+        assert!(FileAccountStore::open(&path).is_err());
+        assert_eq!(
+            fs::read_to_string(&path).expect("failed to read store"),
+            "not an account\n"
+        );
+    }
+}
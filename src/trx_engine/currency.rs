@@ -0,0 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// ISO 4217 alphabetic code of a currency, for example `EUR`.
+/// Codes are read case-insensitively and always stored and written in uppercase.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub fn code(&self) -> &str {
+        // only ascii letters are accepted when parsing, so the code is always valid utf-8.
+        std::str::from_utf8(&self.0).expect("currency code is ascii")
+    }
+}
+
+/// Reason for a currency code to be rejected while parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCurrency(String);
+
+impl fmt::Display for InvalidCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid currency code, three letters are expected: {:?}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidCurrency {}
+
+impl FromStr for Currency {
+    type Err = InvalidCurrency;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            [a, b, c] if s.bytes().all(|byte| byte.is_ascii_alphabetic()) => Ok(Self([
+                a.to_ascii_uppercase(),
+                b.to_ascii_uppercase(),
+                c.to_ascii_uppercase(),
+            ])),
+            _ => Err(InvalidCurrency(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl serde::Serialize for Currency {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Currency {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_currency_successful() {
+        let currency: Currency = "EUR".parse().expect("failed to parse currency");
+        assert_eq!(currency.code(), "EUR");
+        assert_eq!(currency.to_string(), "EUR");
+    }
+
+    #[test]
+    fn parse_currency_is_case_insensitive() {
+        let lowercase: Currency = "usd".parse().expect("failed to parse currency");
+        let uppercase: Currency = "USD".parse().expect("failed to parse currency");
+        assert_eq!(lowercase, uppercase);
+        assert_eq!(lowercase.code(), "USD");
+    }
+
+    #[test]
+    fn parse_currency_fails_on_invalid_code() {
+        for code in ["", "EU", "EURO", "E1R", "€UR"] {
+            assert_eq!(
+                code.parse::<Currency>(),
+                Err(InvalidCurrency(code.to_string())),
+                "{code:?} should be rejected"
+            );
+        }
+    }
+}
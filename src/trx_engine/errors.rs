@@ -1,29 +1,387 @@
+use crate::trx_engine::currency::Currency;
+use rust_decimal::Decimal;
+
+/// Reasons for the engine to reject an input.
+/// Every variant carries the identifiers needed to trace the rejection back to the input that caused it.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum EngineError {
-    InsufficientFunds,
-    NegativeAmount,
-    TrxAlreadyProcessed,
-    TrxInvalidAmount,
-    TrxNotFound,
-    TrxNotInDisputableState,
-    TrxNotInDispute,
-    TrxNotDisputable,
-    TrxClientIdInconsistency,
-    AccountLocked,
+    /// `available` includes the credit limit of the account.
+    InsufficientFunds {
+        client: u16,
+        available: Decimal,
+        amount: Decimal,
+    },
+    NegativeAmount {
+        client: u16,
+        amount: Decimal,
+    },
+    TrxAlreadyProcessed {
+        tx: u32,
+    },
+    TrxInvalidAmount {
+        tx: u32,
+    },
+    TrxNotFound {
+        tx: u32,
+    },
+    TrxNotInDisputableState {
+        tx: u32,
+    },
+    TrxNotInDispute {
+        tx: u32,
+    },
+    TrxAlreadyInDispute {
+        tx: u32,
+    },
+    TrxAlreadyChargedBack {
+        tx: u32,
+    },
+    TrxNotDisputable {
+        tx: u32,
+    },
+    TrxInvalidDestination {
+        tx: u32,
+    },
+    TrxInvalidExchange {
+        tx: u32,
+    },
+    ExchangeRateNotFound {
+        tx: u32,
+        from: Currency,
+        to: Currency,
+    },
+    ExchangeRateInvalid {
+        tx: u32,
+        rate: Decimal,
+    },
+    TrxNotAuthorized {
+        tx: u32,
+    },
+    TrxAuthorizationClosed {
+        tx: u32,
+    },
+    TrxAlreadyCaptured {
+        tx: u32,
+    },
+    CaptureAmountExceeded {
+        tx: u32,
+        amount: Decimal,
+        authorized: Decimal,
+    },
+    AmountPrecisionExceeded {
+        tx: u32,
+        amount: Decimal,
+        decimal_places: u32,
+    },
+    DisputeAmountExceeded {
+        tx: u32,
+        amount: Decimal,
+        remaining: Decimal,
+    },
+    DisputeWindowExpired {
+        tx: u32,
+        processed_at: u64,
+        disputed_at: u64,
+    },
+    TrxCurrencyMismatch {
+        tx: u32,
+    },
+    /// The transaction store failed to read or write a transaction, e.g. an I/O error of a disk backed store.
+    TrxStoreFailure {
+        tx: u32,
+        reason: String,
+    },
+    /// The journal failed to record the input, which is rejected as it could not be recovered.
+    JournalFailure {
+        tx: u32,
+        reason: String,
+    },
+    /// The event store failed to record an event of the input, which is rejected as its changes could not be traced.
+    EventStoreFailure {
+        tx: u32,
+        reason: String,
+    },
+    TrxClientIdInconsistency {
+        client: u16,
+        tx: u32,
+        trx_client: u16,
+    },
+    AccountLocked {
+        client: u16,
+    },
+    AccountFrozen {
+        client: u16,
+    },
+    AccountClosed {
+        client: u16,
+    },
+    AccountNotLocked {
+        client: u16,
+    },
+    AccountNotFrozen {
+        client: u16,
+    },
+    AccountNotEmpty {
+        client: u16,
+        held: Decimal,
+        total: Decimal,
+    },
+    AccountNotFound {
+        client: u16,
+    },
+    /// The account store failed to read or write an account, e.g. an I/O error of a persistent store.
+    AccountStoreFailure {
+        client: u16,
+        reason: String,
+    },
+    Overflow {
+        client: u16,
+        amount: Decimal,
+    },
 }
+
+impl EngineError {
+    /// Stable, machine-readable identifier of the rejection reason.
+    /// Unlike the `Display` message, codes never change once released and are safe to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InsufficientFunds { .. } => "insufficient_funds",
+            Self::NegativeAmount { .. } => "negative_amount",
+            Self::TrxAlreadyProcessed { .. } => "trx_already_processed",
+            Self::TrxInvalidAmount { .. } => "trx_invalid_amount",
+            Self::TrxNotFound { .. } => "trx_not_found",
+            Self::TrxNotInDisputableState { .. } => "trx_not_in_disputable_state",
+            Self::TrxNotInDispute { .. } => "trx_not_in_dispute",
+            Self::TrxAlreadyInDispute { .. } => "trx_already_in_dispute",
+            Self::TrxAlreadyChargedBack { .. } => "trx_already_charged_back",
+            Self::TrxNotDisputable { .. } => "trx_not_disputable",
+            Self::TrxInvalidDestination { .. } => "trx_invalid_destination",
+            Self::TrxInvalidExchange { .. } => "trx_invalid_exchange",
+            Self::ExchangeRateNotFound { .. } => "exchange_rate_not_found",
+            Self::ExchangeRateInvalid { .. } => "exchange_rate_invalid",
+            Self::TrxNotAuthorized { .. } => "trx_not_authorized",
+            Self::TrxAuthorizationClosed { .. } => "trx_authorization_closed",
+            Self::TrxAlreadyCaptured { .. } => "trx_already_captured",
+            Self::CaptureAmountExceeded { .. } => "capture_amount_exceeded",
+            Self::AmountPrecisionExceeded { .. } => "amount_precision_exceeded",
+            Self::TrxStoreFailure { .. } => "trx_store_failure",
+            Self::JournalFailure { .. } => "journal_failure",
+            Self::EventStoreFailure { .. } => "event_store_failure",
+            Self::DisputeAmountExceeded { .. } => "dispute_amount_exceeded",
+            Self::DisputeWindowExpired { .. } => "dispute_window_expired",
+            Self::TrxCurrencyMismatch { .. } => "trx_currency_mismatch",
+            Self::TrxClientIdInconsistency { .. } => "trx_client_id_inconsistency",
+            Self::AccountLocked { .. } => "account_locked",
+            Self::AccountFrozen { .. } => "account_frozen",
+            Self::AccountClosed { .. } => "account_closed",
+            Self::AccountNotLocked { .. } => "account_not_locked",
+            Self::AccountNotFrozen { .. } => "account_not_frozen",
+            Self::AccountNotEmpty { .. } => "account_not_empty",
+            Self::AccountNotFound { .. } => "account_not_found",
+            Self::AccountStoreFailure { .. } => "account_store_failure",
+            Self::Overflow { .. } => "overflow",
+        }
+    }
+}
+
 impl std::fmt::Display for EngineError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InsufficientFunds => write!(f, "insufficient funds to execute transaction"),
-            Self::NegativeAmount => write!(f, "negative transaction amount"),
-            Self::TrxAlreadyProcessed => write!(f, "transaction already processed"),
-            Self::TrxInvalidAmount => write!(f, "transaction contains an invalid amount to process"),
-            Self::TrxNotFound => write!(f, "transaction not found in ledger"),
-            Self::TrxNotInDisputableState => write!(f, "transaction not in a disputable state"),
-            Self::TrxNotInDispute => write!(f, "transaction not in dispute"),
-            Self::TrxNotDisputable => write!(f, "transaction type is not disputable"),
-            Self::TrxClientIdInconsistency => write!(f, "client id present in transaction is not consistent with the related transaction"),
-            Self::AccountLocked => write!(f, "account in locked state"),
+            Self::InsufficientFunds {
+                client,
+                available,
+                amount,
+            } => write!(
+                f,
+                "insufficient funds to execute transaction (client: {client}, available: {available}, amount: {amount})"
+            ),
+            Self::NegativeAmount { client, amount } => write!(
+                f,
+                "negative transaction amount (client: {client}, amount: {amount})"
+            ),
+            Self::TrxAlreadyProcessed { tx } => {
+                write!(f, "transaction already processed (tx: {tx})")
+            }
+            Self::TrxInvalidAmount { tx } => write!(
+                f,
+                "transaction contains an invalid amount to process (tx: {tx})"
+            ),
+            Self::TrxNotFound { tx } => write!(f, "transaction not found in ledger (tx: {tx})"),
+            Self::TrxNotInDisputableState { tx } => {
+                write!(f, "transaction not in a disputable state (tx: {tx})")
+            }
+            Self::TrxNotInDispute { tx } => write!(f, "transaction not in dispute (tx: {tx})"),
+            Self::TrxAlreadyInDispute { tx } => {
+                write!(f, "transaction already in dispute (tx: {tx})")
+            }
+            Self::TrxAlreadyChargedBack { tx } => {
+                write!(f, "transaction already chargeback (tx: {tx})")
+            }
+            Self::TrxNotDisputable { tx } => {
+                write!(f, "transaction type is not disputable (tx: {tx})")
+            }
+            Self::TrxInvalidDestination { tx } => write!(
+                f,
+                "transaction contains an invalid destination client (tx: {tx})"
+            ),
+            Self::TrxInvalidExchange { tx } => write!(
+                f,
+                "transaction does not contain two different currencies to exchange (tx: {tx})"
+            ),
+            Self::ExchangeRateNotFound { tx, from, to } => write!(
+                f,
+                "exchange rate not provided nor known (tx: {tx}, from: {from}, to: {to})"
+            ),
+            Self::ExchangeRateInvalid { tx, rate } => write!(
+                f,
+                "exchange rate is not positive (tx: {tx}, rate: {rate})"
+            ),
+            Self::TrxNotAuthorized { tx } => {
+                write!(f, "transaction is not an authorization (tx: {tx})")
+            }
+            Self::TrxAuthorizationClosed { tx } => {
+                write!(f, "authorization already captured or voided (tx: {tx})")
+            }
+            Self::TrxAlreadyCaptured { tx } => {
+                write!(f, "authorization already captured, it can only be captured once (tx: {tx})")
+            }
+            Self::CaptureAmountExceeded {
+                tx,
+                amount,
+                authorized,
+            } => write!(
+                f,
+                "capture amount exceeds the authorized amount (tx: {tx}, amount: {amount}, authorized: {authorized})"
+            ),
+            Self::AmountPrecisionExceeded {
+                tx,
+                amount,
+                decimal_places,
+            } => write!(
+                f,
+                "amount has more decimal places than allowed for its currency (tx: {tx}, amount: {amount}, decimal places: {decimal_places})"
+            ),
+            Self::DisputeAmountExceeded {
+                tx,
+                amount,
+                remaining,
+            } => write!(
+                f,
+                "dispute amount exceeds the undisputed amount of the transaction (tx: {tx}, amount: {amount}, remaining: {remaining})"
+            ),
+            Self::DisputeWindowExpired {
+                tx,
+                processed_at,
+                disputed_at,
+            } => write!(
+                f,
+                "dispute window of the transaction expired (tx: {tx}, processed at: {processed_at}, disputed at: {disputed_at})"
+            ),
+            Self::TrxCurrencyMismatch { tx } => write!(
+                f,
+                "currency is not consistent with the related transaction (tx: {tx})"
+            ),
+            Self::TrxStoreFailure { tx, reason } => write!(
+                f,
+                "transaction store failed (tx: {tx}, reason: {reason})"
+            ),
+            Self::JournalFailure { tx, reason } => {
+                write!(f, "journal failed (tx: {tx}, reason: {reason})")
+            }
+            Self::EventStoreFailure { tx, reason } => {
+                write!(f, "event store failed (tx: {tx}, reason: {reason})")
+            }
+            Self::TrxClientIdInconsistency {
+                client,
+                tx,
+                trx_client,
+            } => write!(
+                f,
+                "client id present in transaction is not consistent with the related transaction (client: {client}, tx: {tx}, transaction client: {trx_client})"
+            ),
+            Self::AccountLocked { client } => {
+                write!(f, "account in locked state (client: {client})")
+            }
+            Self::AccountFrozen { client } => {
+                write!(f, "account in frozen state (client: {client})")
+            }
+            Self::AccountClosed { client } => {
+                write!(f, "account in closed state (client: {client})")
+            }
+            Self::AccountNotLocked { client } => {
+                write!(f, "account not in locked state (client: {client})")
+            }
+            Self::AccountNotFrozen { client } => {
+                write!(f, "account not in frozen state (client: {client})")
+            }
+            Self::AccountNotEmpty {
+                client,
+                held,
+                total,
+            } => write!(
+                f,
+                "account still holds funds (client: {client}, held: {held}, total: {total})"
+            ),
+            Self::AccountNotFound { client } => {
+                write!(f, "account not found in ledger (client: {client})")
+            }
+            Self::AccountStoreFailure { client, reason } => write!(
+                f,
+                "account store failed (client: {client}, reason: {reason})"
+            ),
+            Self::Overflow { client, amount } => write!(
+                f,
+                "balance overflow while applying transaction (client: {client}, amount: {amount})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// Reasons for a snapshot of the ledger to fail to be written or restored, see `snapshot::SNAPSHOT_VERSION`.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot could not be read or written.
+    Io(std::io::Error),
+    /// The snapshot is not a valid snapshot document.
+    Format(serde_json::Error),
+    /// The snapshot was written in a format version this engine cannot restore.
+    UnsupportedVersion { version: u32, supported: u32 },
+    /// A store of the ledger failed to save a restored account or transaction.
+    Store(std::io::Error),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read or write snapshot: {err}"),
+            Self::Format(err) => write!(f, "invalid snapshot: {err}"),
+            Self::UnsupportedVersion { version, supported } => write!(
+                f,
+                "unsupported snapshot version: {version} (supported up to: {supported})"
+            ),
+            Self::Store(err) => write!(f, "failed to store restored state: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) | Self::Store(err) => Some(err),
+            Self::Format(err) => Some(err),
+            Self::UnsupportedVersion { .. } => None,
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(err: serde_json::Error) -> Self {
+        if err.is_io() {
+            Self::Io(err.into())
+        } else {
+            Self::Format(err)
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::trx_engine::events::EventRecord;
+use crate::trx_engine::journal::{read_lines, LineFile, SyncPolicy};
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Storage of the events recorded by the ledger, in the order they were recorded: the history of the accounts,
+/// from which the accounts can be rebuilt but not the transactions, see `Ledger::from_events`.
+/// The ledger only writes to the store once an input was processed successfully.
+pub trait EventStore {
+    /// Appends the events recorded for an accepted input, all of them or none.
+    fn append(&mut self, records: Vec<EventRecord>) -> io::Result<()>;
+
+    /// Returns every event of the store, in the order they were appended.
+    fn events(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, EventRecord>>> + '_>;
+
+    /// Makes the events appended since the last call outlive the ledger, along with the sequence of the last input
+    /// they were recorded for, see `Ledger::checkpoint`. Stores keeping their events for the lifetime of the ledger
+    /// only have nothing to do.
+    fn persist(&mut self, _sequence: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Sequence of the last input whose events were persisted, so a recovery does not record them again,
+    /// see `Ledger::recover`. Stores keeping their events for the lifetime of the ledger only hold none.
+    fn sequence(&self) -> u64 {
+        0
+    }
+}
+
+/// Events kept in memory for the lifetime of the ledger, so the memory used grows with every accepted input.
+impl EventStore for Vec<EventRecord> {
+    fn append(&mut self, records: Vec<EventRecord>) -> io::Result<()> {
+        self.extend(records);
+        Ok(())
+    }
+
+    fn events(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, EventRecord>>> + '_> {
+        Box::new(self.iter().map(|record| Ok(Cow::Borrowed(record))))
+    }
+}
+
+/// Store appending the events of every input to a file as a line of json, so the history of the accounts can be kept
+/// across runs and the memory used does not grow with the number of events.
+/// The events of an input are written on a single line, so a crash never leaves only part of them behind.
+/// Events only outlive the run once they are persisted, e.g. once a file was processed successfully:
+/// `persist` syncs them and marks the end of the persisted lines with the sequence of the last input, the lines following the last mark are removed
+/// when the store is opened again, so a run that is aborted or crashes leaves the events of the previous one.
+#[derive(Debug)]
+pub struct FileEventStore {
+    path: PathBuf,
+    lines: LineFile,
+    /// Number of lines of events in the file, persisted or not.
+    inputs: u64,
+    /// Sequence of the last input whose events were persisted.
+    sequence: u64,
+}
+
+/// Line of the file of a `FileEventStore`: the events of an input, or the mark left by `persist`.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Events(Vec<EventRecord>),
+    /// Number of lines of events persisted before the mark, and the sequence of the last input they were recorded for.
+    Persisted {
+        persisted: u64,
+        #[serde(default)]
+        sequence: u64,
+    },
+}
+
+impl FileEventStore {
+    /// Opens the store persisted in the provided file, created if missing, new events are appended to the existing ones.
+    /// The events that were not persisted, including those left incomplete by a crash, are removed.
+    /// # Errors
+    /// * An error is returned if the file cannot be opened, read or truncated, or any line cannot be parsed.
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let mut mark = Mark::default();
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => {
+                mark = last_mark(&file)?;
+                if mark.len < file.metadata()?.len() {
+                    file.set_len(mark.len)?;
+                    file.sync_all()?;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            // lines are synced when they are persisted, the lines of a crashed run are removed anyway.
+            lines: LineFile::open(path, SyncPolicy::Never)?,
+            inputs: mark.inputs,
+            sequence: mark.sequence,
+        })
+    }
+}
+
+/// Last mark left by `persist` in the file of a `FileEventStore`.
+#[derive(Default)]
+struct Mark {
+    /// Length of the file up to the end of the mark.
+    len: u64,
+    /// Number of lines of events before the mark.
+    inputs: u64,
+    /// Sequence of the last input persisted.
+    sequence: u64,
+}
+
+/// Reads the file up to its last mark left by `persist`.
+fn last_mark(file: &File) -> io::Result<Mark> {
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut len = 0;
+    let mut mark = Mark::default();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || !line.ends_with(b"\n") {
+            return Ok(mark);
+        }
+        len += read as u64;
+
+        if let Line::Persisted {
+            persisted,
+            sequence,
+        } = serde_json::from_slice(&line)?
+        {
+            mark = Mark {
+                len,
+                inputs: persisted,
+                sequence,
+            };
+        }
+    }
+}
+
+impl EventStore for FileEventStore {
+    fn append(&mut self, records: Vec<EventRecord>) -> io::Result<()> {
+        self.lines.append(&records)?;
+        self.inputs += 1;
+        Ok(())
+    }
+
+    fn events(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, EventRecord>>> + '_> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        Box::new(read_lines::<_, Line>(file).flat_map(|line| {
+            match line {
+                Ok(Line::Events(records)) => records
+                    .into_iter()
+                    .map(|record| Ok(Cow::Owned(record)))
+                    .collect(),
+                Ok(Line::Persisted { .. }) => Vec::new(),
+                Err(e) => vec![Err(e)],
+            }
+        }))
+    }
+
+    fn persist(&mut self, sequence: u64) -> io::Result<()> {
+        self.lines.append(&Line::Persisted {
+            persisted: self.inputs,
+            sequence,
+        })?;
+        self.lines.sync()?;
+        self.sequence = sequence;
+        Ok(())
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trx_engine::events::Event;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn file_store_appends_to_existing_events() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("events.jsonl");
+        let deposit = |tx| EventRecord {
+            tx,
+            timestamp: Some(10),
+            event: Event::FundsDeposited {
+                client: 1,
+                currency: None,
+                amount: dec!(2.5),
+            },
+        };
+
+        let mut store = FileEventStore::open(&path).expect("failed to open store");
+        store.append(vec![deposit(1)]).expect("failed to append");
+        store.persist(1).expect("failed to persist");
+        drop(store);
+        let mut store = FileEventStore::open(&path).expect("failed to reopen store");
+        assert_eq!(store.sequence(), 1);
+        store
+            .append(vec![deposit(2), deposit(4)])
+            .expect("failed to append");
+
+        let events = store
+            .events()
+            .map(|record| record.map(Cow::into_owned))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read events");
+
+        assert_eq!(events, vec![deposit(1), deposit(2), deposit(4)]);
+    }
+
+    #[test]
+    fn file_store_removes_events_not_persisted() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("events.jsonl");
+        let deposit = |tx| EventRecord {
+            tx,
+            timestamp: None,
+            event: Event::FundsDeposited {
+                client: 1,
+                currency: None,
+                amount: dec!(1),
+            },
+        };
+
+        let mut store = FileEventStore::open(&path).expect("failed to open store");
+        store.append(vec![deposit(1)]).expect("failed to append");
+        store.persist(1).expect("failed to persist");
+        // the events of a run that stops before persisting them are removed, as is an incomplete line.
+        store.append(vec![deposit(2)]).expect("failed to append");
+        drop(store);
+        let mut torn = std::fs::read(&path).expect("failed to read store");
+        torn.extend_from_slice(br#"[{"tx":3,"times"#);
+        std::fs::write(&path, &torn).expect("failed to write store");
+
+        let mut store = FileEventStore::open(&path).expect("failed to reopen store");
+        assert_eq!(store.sequence(), 1);
+        store.append(vec![deposit(4)]).expect("failed to append");
+
+        let events = store
+            .events()
+            .map(|record| record.map(Cow::into_owned))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read events");
+
+        assert_eq!(events, vec![deposit(1), deposit(4)]);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::trx_engine::account::{Account, AccountStatus};
+use crate::trx_engine::currency::Currency;
+use crate::trx_engine::errors::EngineError;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Direction of the funds of a disputed transaction, for the account the dispute is applied to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flow {
+    /// Funds that entered the account, e.g. a deposit or a received transfer. They are held while disputed.
+    Incoming,
+    /// Funds that left the account, e.g. a withdrawal. They are provisionally credited back as held funds while disputed.
+    Outgoing,
+}
+
+/// Event is a change of an account, recorded for every operation accepted by the ledger.
+/// `Account::apply` is the only place an account changes, so folding the events of a client, starting from an empty
+/// account, rebuilds its account and shows how its balances were reached.
+/// Events only describe accounts: transactions, their disputes and holds are kept by the transaction store,
+/// so the state of record of the ledger remains its stores and snapshot, and events are a history of the accounts.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    FundsDeposited {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+    },
+    FundsWithdrawn {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+    },
+    FeeCharged {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+    },
+    FundsExchanged {
+        client: u16,
+        from: Currency,
+        amount: Decimal,
+        to: Currency,
+        converted: Decimal,
+    },
+    FundsAuthorized {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+    },
+    /// The whole authorization is released from the held funds and the captured amount leaves the account.
+    AuthorizationCaptured {
+        client: u16,
+        currency: Option<Currency>,
+        authorized: Decimal,
+        captured: Decimal,
+    },
+    AuthorizationVoided {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+    },
+    DisputeOpened {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+        flow: Flow,
+    },
+    DisputeResolved {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+        flow: Flow,
+    },
+    ChargedBack {
+        client: u16,
+        currency: Option<Currency>,
+        amount: Decimal,
+        flow: Flow,
+    },
+    /// Recorded with every chargeback.
+    AccountLocked { client: u16 },
+    /// Recorded by the administrative operations.
+    StatusChanged { client: u16, status: AccountStatus },
+}
+
+impl Event {
+    /// Client of the account the event applies to.
+    pub fn client(&self) -> u16 {
+        match self {
+            Self::FundsDeposited { client, .. }
+            | Self::FundsWithdrawn { client, .. }
+            | Self::FeeCharged { client, .. }
+            | Self::FundsExchanged { client, .. }
+            | Self::FundsAuthorized { client, .. }
+            | Self::AuthorizationCaptured { client, .. }
+            | Self::AuthorizationVoided { client, .. }
+            | Self::DisputeOpened { client, .. }
+            | Self::DisputeResolved { client, .. }
+            | Self::ChargedBack { client, .. }
+            | Self::AccountLocked { client }
+            | Self::StatusChanged { client, .. } => *client,
+        }
+    }
+}
+
+/// EventRecord represents an event as recorded by the ledger, with the input it was recorded for.
+/// Events released by an expired hold are recorded for the resolve or void releasing it.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct EventRecord {
+    #[serde(rename = "tx")]
+    pub tx: u32,
+
+    /// Time the input was processed at, if the ledger clock was known.
+    #[serde(default, rename = "timestamp")]
+    pub timestamp: Option<u64>,
+
+    #[serde(rename = "event")]
+    pub event: Event,
+}
+
+/// View of the ledger built by folding its events in the order they were recorded, see `Ledger::project`.
+pub trait Projection {
+    /// # Errors
+    /// * An error is returned if the event cannot be applied to the view.
+    ///
+    fn apply(&mut self, record: &EventRecord) -> Result<(), EngineError>;
+}
+
+/// The accounts of the ledger, each account is opened by its first event.
+impl Projection for HashMap<u16, Account> {
+    fn apply(&mut self, record: &EventRecord) -> Result<(), EngineError> {
+        let client = record.event.client();
+        self.entry(client)
+            .or_insert_with(|| Account::new(client))
+            .apply(&record.event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn record(tx: u32, event: Event) -> EventRecord {
+        EventRecord {
+            tx,
+            timestamp: None,
+            event,
+        }
+    }
+
+    #[test]
+    fn accounts_projection_folds_events() {
+        let mut accounts: HashMap<u16, Account> = HashMap::new();
+        let events = [
+            record(
+                1,
+                Event::FundsDeposited {
+                    client: 1,
+                    currency: None,
+                    amount: dec!(100),
+                },
+            ),
+            record(
+                1,
+                Event::DisputeOpened {
+                    client: 1,
+                    currency: None,
+                    amount: dec!(40),
+                    flow: Flow::Incoming,
+                },
+            ),
+            record(
+                1,
+                Event::ChargedBack {
+                    client: 1,
+                    currency: None,
+                    amount: dec!(40),
+                    flow: Flow::Incoming,
+                },
+            ),
+            record(1, Event::AccountLocked { client: 1 }),
+        ];
+
+        for event in &events {
+            accounts.apply(event).expect("failed to apply event");
+        }
+
+        let account = &accounts[&1];
+        assert_eq!(account.balance(None).available(), dec!(60));
+        assert_eq!(account.balance(None).held(), dec!(0));
+        assert_eq!(account.balance(None).total(), dec!(60));
+        assert_eq!(account.status(), AccountStatus::Locked);
+    }
+
+    #[test]
+    fn serialize_event_record() {
+        let record = record(
+            7,
+            Event::StatusChanged {
+                client: 2,
+                status: AccountStatus::Frozen,
+            },
+        );
+
+        let json = serde_json::to_string(&record).expect("failed to serialize record");
+
+        assert_eq!(
+            json,
+            r#"{"tx":7,"timestamp":null,"event":{"event":"status_changed","client":2,"status":"frozen"}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<EventRecord>(&json).expect("failed to deserialize record"),
+            record
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::trx_engine::currency::Currency;
+use crate::trx_engine::transaction::Type;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Fee charged for a transaction, computed from its amount.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fee {
+    Flat(Decimal),
+    /// Percentage of the amount, e.g. `dec!(1.5)` for 1.5%.
+    Percentage(Decimal),
+    /// The fee of the tier with the highest threshold reached by the amount.
+    /// Amounts below every threshold are not charged.
+    Tiered(Vec<Tier>),
+}
+
+impl FromStr for Fee {
+    type Err = String;
+
+    /// Parses a flat fee, e.g. `1.5`, or a percentage, e.g. `1.5%`.
+    /// Negative fees are rejected, they would credit the client and debit the house account.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let non_negative = |fee: &str| match fee.parse::<Decimal>() {
+            Ok(fee) if fee.is_sign_negative() => Err(format!("fee cannot be negative: {s}")),
+            Ok(fee) => Ok(fee),
+            Err(_) => Err(format!("invalid fee: {s}")),
+        };
+        match s.strip_suffix('%') {
+            Some(percentage) => non_negative(percentage).map(Self::Percentage),
+            None => non_negative(s).map(Self::Flat),
+        }
+    }
+}
+
+/// Tier of a `Fee::Tiered`, applied to the amounts greater than or equal to `from`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tier {
+    pub from: Decimal,
+    pub fee: Fee,
+}
+
+impl Fee {
+    /// Fee charged for the provided amount, or `None` if it does not fit in a `Decimal`.
+    pub fn compute(&self, amount: Decimal) -> Option<Decimal> {
+        match self {
+            Self::Flat(fee) => Some(*fee),
+            Self::Percentage(percentage) => amount
+                .checked_mul(*percentage)?
+                .checked_div(Decimal::ONE_HUNDRED),
+            Self::Tiered(tiers) => tiers
+                .iter()
+                .filter(|tier| tier.from <= amount)
+                .max_by_key(|tier| tier.from)
+                .map_or(Some(Decimal::ZERO), |tier| tier.fee.compute(amount)),
+        }
+    }
+}
+
+/// Fee charged for a type of transaction, capped by an optional minimum and maximum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeRule {
+    pub fee: Fee,
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+}
+
+impl FeeRule {
+    pub fn new(fee: Fee) -> Self {
+        Self {
+            fee,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Fee charged for the provided amount, before rounding to the precision of its currency,
+    /// or `None` if it does not fit in a `Decimal`.
+    pub fn compute(&self, amount: Decimal) -> Option<Decimal> {
+        let fee = self.fee.compute(amount)?;
+        let fee = self.min.map_or(fee, |min| fee.max(min));
+        Some(self.max.map_or(fee, |max| fee.min(max)))
+    }
+}
+
+/// Fees charged by the ledger, per type of transaction, and the house account collecting them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSchedule {
+    /// Client id of the account the fees are credited to. The account is opened with the ledger.
+    pub house_account: u16,
+    pub withdrawal: Option<FeeRule>,
+    /// Charged to the account that is charged back, on the amount of the dispute.
+    pub chargeback: Option<FeeRule>,
+}
+
+impl FeeSchedule {
+    pub fn new(house_account: u16) -> Self {
+        Self {
+            house_account,
+            withdrawal: None,
+            chargeback: None,
+        }
+    }
+
+    /// Rule of the provided type of transaction, if it is charged.
+    pub fn rule(&self, transaction_type: Type) -> Option<&FeeRule> {
+        match transaction_type {
+            Type::Withdrawal => self.withdrawal.as_ref(),
+            Type::Chargeback => self.chargeback.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// FeeTransaction represents a fee posted by the ledger: debited from the account of `client`
+/// and credited to the house account, in the currency of the transaction it was charged for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeTransaction {
+    pub(crate) tx: u32,
+    pub(crate) transaction_type: Type,
+    pub(crate) client: u16,
+    pub(crate) house_account: u16,
+    pub(crate) currency: Option<Currency>,
+    pub(crate) amount: Decimal,
+}
+
+impl FeeTransaction {
+    /// Id of the transaction the fee was charged for.
+    pub fn tx(&self) -> u32 {
+        self.tx
+    }
+
+    pub fn transaction_type(&self) -> Type {
+        self.transaction_type
+    }
+
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+
+    pub fn house_account(&self) -> u16 {
+        self.house_account
+    }
+
+    pub fn currency(&self) -> Option<Currency> {
+        self.currency
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn fee_compute_flat_and_percentage() {
+        assert_eq!(Fee::Flat(dec!(1.5)).compute(dec!(1000)), Some(dec!(1.5)));
+        assert_eq!(Fee::Percentage(dec!(1.5)).compute(dec!(200)), Some(dec!(3)));
+    }
+
+    #[test]
+    fn fee_compute_tiered() {
+        let fee = Fee::Tiered(vec![
+            Tier {
+                from: dec!(1000),
+                fee: Fee::Percentage(dec!(0.5)),
+            },
+            Tier {
+                from: dec!(100),
+                fee: Fee::Percentage(dec!(1)),
+            },
+            Tier {
+                from: dec!(0),
+                fee: Fee::Flat(dec!(2)),
+            },
+        ]);
+
+        assert_eq!(fee.compute(dec!(50)), Some(dec!(2)));
+        assert_eq!(fee.compute(dec!(100)), Some(dec!(1)));
+        assert_eq!(fee.compute(dec!(999)), Some(dec!(9.99)));
+        assert_eq!(fee.compute(dec!(2000)), Some(dec!(10)));
+    }
+
+    #[test]
+    fn fee_compute_tiered_below_every_threshold() {
+        let fee = Fee::Tiered(vec![Tier {
+            from: dec!(100),
+            fee: Fee::Flat(dec!(2)),
+        }]);
+
+        assert_eq!(fee.compute(dec!(99)), Some(dec!(0)));
+    }
+
+    #[test]
+    fn fee_compute_percentage_when_overflows() {
+        assert_eq!(Fee::Percentage(dec!(2)).compute(Decimal::MAX), None);
+        assert_eq!(
+            FeeRule::new(Fee::Percentage(dec!(2))).compute(Decimal::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_fee() {
+        assert_eq!("1.5".parse(), Ok(Fee::Flat(dec!(1.5))));
+        assert_eq!("1.5%".parse(), Ok(Fee::Percentage(dec!(1.5))));
+        assert!("1.5$".parse::<Fee>().is_err());
+    }
+
+    #[test]
+    fn parse_fee_fail_when_negative() {
+        assert_eq!(
+            "-1".parse::<Fee>(),
+            Err("fee cannot be negative: -1".to_string())
+        );
+        assert_eq!(
+            "-1.5%".parse::<Fee>(),
+            Err("fee cannot be negative: -1.5%".to_string())
+        );
+        assert_eq!("0".parse(), Ok(Fee::Flat(dec!(0))));
+    }
+
+    #[test]
+    fn fee_rule_compute_with_caps() {
+        let rule = FeeRule {
+            fee: Fee::Percentage(dec!(1)),
+            min: Some(dec!(0.5)),
+            max: Some(dec!(5)),
+        };
+
+        assert_eq!(rule.compute(dec!(10)), Some(dec!(0.5)));
+        assert_eq!(rule.compute(dec!(200)), Some(dec!(2)));
+        assert_eq!(rule.compute(dec!(1000)), Some(dec!(5)));
+    }
+
+    #[test]
+    fn fee_schedule_rule_per_type() {
+        let schedule = FeeSchedule {
+            withdrawal: Some(FeeRule::new(Fee::Flat(dec!(1)))),
+            ..FeeSchedule::new(0)
+        };
+
+        assert!(schedule.rule(Type::Withdrawal).is_some());
+        assert!(schedule.rule(Type::Chargeback).is_none());
+        assert!(schedule.rule(Type::Deposit).is_none());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::trx_engine::transaction::Input;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// JournalEntry represents an input accepted by the ledger, as recorded by the journal.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct JournalEntry {
+    /// Line of the input file the input was read from, if it was read from a file.
+    #[serde(default, rename = "line")]
+    pub line: Option<u64>,
+
+    /// Position of the input among every input accepted by the ledger, see `Ledger::sequence`.
+    /// Entries written by older versions have none, they are always replayed.
+    #[serde(default, rename = "sequence")]
+    pub sequence: Option<u64>,
+
+    /// The input as applied by the ledger, with the timestamp it was processed at.
+    #[serde(rename = "input")]
+    pub input: Input,
+}
+
+/// Journal of the inputs accepted by the ledger.
+/// An input is appended before its changes are written to the stores of the ledger, which are left untouched
+/// if it cannot be appended, and the entry is discarded if the changes cannot be written, so the journal holds
+/// exactly the accepted inputs and replaying it rebuilds the state of a ledger that stopped midway, see `Ledger::recover`.
+pub trait Journal {
+    fn append(&mut self, entry: &JournalEntry) -> io::Result<()>;
+
+    /// Removes the entry appended last, once the changes of its input could not be written to the stores.
+    fn discard(&mut self) -> io::Result<()>;
+
+    /// Removes every entry, once the inputs they record no longer need to be recovered, see `Ledger::truncate_journal`.
+    fn truncate(&mut self) -> io::Result<()>;
+}
+
+/// How often a `FileJournal` flushes its entries to the disk.
+/// Entries that are written but not synced survive a crash of the process, but not of the machine.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum SyncPolicy {
+    /// Every entry is synced as it is appended.
+    #[default]
+    Always,
+    /// Entries are synced once the provided number of them was written since the last sync.
+    Every(u64),
+    /// Syncing is left to the operating system.
+    Never,
+}
+
+impl FromStr for SyncPolicy {
+    type Err = String;
+
+    /// Parses `always`, `never` or a number of entries.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => match s.parse() {
+                Ok(entries) if entries > 0 => Ok(Self::Every(entries)),
+                _ => Err(format!("unknown sync policy: {s}")),
+            },
+        }
+    }
+}
+
+/// Journal appending every entry to a file as a line of json.
+#[derive(Debug)]
+pub struct FileJournal {
+    lines: LineFile,
+}
+
+impl FileJournal {
+    /// Opens the journal in the provided file, created if missing, new entries are appended to the existing ones.
+    /// An entry left incomplete by a crash is removed, its input is processed again by the recovered run.
+    /// # Errors
+    /// * An error is returned if the file cannot be opened, read or truncated.
+    ///
+    pub fn open<P: AsRef<Path>>(path: P, policy: SyncPolicy) -> io::Result<Self> {
+        Ok(Self {
+            lines: LineFile::open(path, policy)?,
+        })
+    }
+}
+
+impl Journal for FileJournal {
+    fn append(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        self.lines.append(entry)
+    }
+
+    fn discard(&mut self) -> io::Result<()> {
+        self.lines.remove_last()
+    }
+
+    fn truncate(&mut self) -> io::Result<()> {
+        self.lines.truncate()
+    }
+}
+
+/// File of json lines synced to the disk according to a `SyncPolicy`, shared by the journal and the `FileEventStore`.
+#[derive(Debug)]
+pub(crate) struct LineFile {
+    file: File,
+    policy: SyncPolicy,
+    /// Length of the complete lines of the file, where the next line is written.
+    len: u64,
+    /// Length of the file before the last line was appended, if it can still be removed.
+    last: Option<u64>,
+    /// Lines written since the last sync.
+    unsynced: u64,
+}
+
+impl LineFile {
+    /// Opens the provided file to append lines, created if missing.
+    /// A line left incomplete by a crash is removed, so the next line is not appended to it.
+    /// # Errors
+    /// * An error is returned if the file cannot be opened, read or truncated.
+    ///
+    pub(crate) fn open<P: AsRef<Path>>(path: P, policy: SyncPolicy) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let len = complete_len(&file)?;
+        if len < file.metadata()?.len() {
+            file.set_len(len)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            file,
+            policy,
+            len,
+            last: None,
+            unsynced: 0,
+        })
+    }
+
+    /// Appends the value as a line of json, synced if the policy requires it.
+    /// A line that fails to be written is removed, only a crash can leave part of it behind,
+    /// which is then ignored by `read_lines` and removed when the file is opened again.
+    /// # Errors
+    /// * An error is returned if the value cannot be serialized, or the line cannot be written or synced.
+    ///
+    pub(crate) fn append<T: serde::Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+        if let Err(e) = self.file.write_all(&line) {
+            // the error of the write is the one reported, the next open removes whatever is left.
+            let _ = self.file.set_len(self.len);
+            return Err(e);
+        }
+        self.last = Some(self.len);
+        self.len += line.len() as u64;
+
+        self.unsynced += 1;
+        let sync = match self.policy {
+            SyncPolicy::Always => true,
+            SyncPolicy::Every(lines) => self.unsynced >= lines,
+            SyncPolicy::Never => false,
+        };
+        if sync {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Syncs the lines written so far, whatever the policy.
+    pub(crate) fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Removes the line appended last, synced whatever the policy so it is not found again after a crash.
+    /// # Errors
+    /// * An error is returned if no line was appended since the file was opened, truncated or its last line removed,
+    ///   or the file cannot be truncated.
+    ///
+    pub(crate) fn remove_last(&mut self) -> io::Result<()> {
+        let Some(last) = self.last else {
+            return Err(io::Error::other("no line to remove"));
+        };
+
+        self.file.set_len(last)?;
+        self.file.sync_all()?;
+        self.len = last;
+        self.last = None;
+
+        Ok(())
+    }
+
+    /// Removes every line of the file.
+    pub(crate) fn truncate(&mut self) -> io::Result<()> {
+        // the file is opened to append, so the next line is written at the start of the emptied file.
+        self.file.set_len(0)?;
+        self.file.sync_all()?;
+        self.len = 0;
+        self.last = None;
+        self.unsynced = 0;
+
+        Ok(())
+    }
+}
+
+/// Length of the complete lines at the start of the file.
+fn complete_len(file: &File) -> io::Result<u64> {
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut complete = 0;
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line)? {
+            0 => return Ok(complete),
+            length if line.ends_with(b"\n") => complete += length as u64,
+            _ => return Ok(complete),
+        }
+    }
+}
+
+/// Reads the lines of json written by a `LineFile`, in the order they were appended.
+/// An incomplete last line, left by a crash while it was written, is ignored.
+pub(crate) fn read_lines<R: Read, T: serde::de::DeserializeOwned>(
+    reader: R,
+) -> impl Iterator<Item = io::Result<T>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    std::iter::from_fn(move || {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) if !line.ends_with(b"\n") => None,
+            Ok(_) => Some(serde_json::from_slice(&line).map_err(io::Error::from)),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// Reads the entries of a journal written by a `FileJournal`, in the order they were appended.
+/// An incomplete last line, left by a crash while it was written, is ignored.
+pub fn read_entries<R: Read>(reader: R) -> impl Iterator<Item = io::Result<JournalEntry>> {
+    read_lines(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use std::fs;
+
+    #[test]
+    fn parse_sync_policy() {
+        assert_eq!("always".parse(), Ok(SyncPolicy::Always));
+        assert_eq!("never".parse(), Ok(SyncPolicy::Never));
+        assert_eq!("100".parse(), Ok(SyncPolicy::Every(100)));
+        assert!("0".parse::<SyncPolicy>().is_err());
+        assert!("sometimes".parse::<SyncPolicy>().is_err());
+    }
+
+    #[test]
+    fn file_journal_append_and_read_entries() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
+
+        let mut journal =
+            FileJournal::open(&path, SyncPolicy::Every(2)).expect("failed to open journal");
+        for (line, sequence, input) in [
+            (Some(2), Some(1), Input::deposit(1, 1, dec!(10.5))),
+            (
+                None,
+                None,
+                Input::withdrawal(1, 2, dec!(1)).with_timestamp(5),
+            ),
+        ] {
+            journal
+                .append(&JournalEntry {
+                    line,
+                    sequence,
+                    input,
+                })
+                .expect("failed to append");
+        }
+
+        let entries = read_entries(File::open(&path).expect("failed to open journal"))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read entries");
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, Some(2));
+        assert_eq!(entries[0].sequence, Some(1));
+        assert_eq!(entries[0].input.amount(), Some(dec!(10.5)));
+        assert_eq!(entries[1].line, None);
+        assert_eq!(entries[1].sequence, None);
+        assert_eq!(entries[1].input.tx(), 2);
+        assert_eq!(entries[1].input.timestamp(), Some(5));
+    }
+
+    #[test]
+    fn file_journal_truncate() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
+
+        let mut journal =
+            FileJournal::open(&path, SyncPolicy::Always).expect("failed to open journal");
+        let entry = |tx| JournalEntry {
+            line: None,
+            sequence: None,
+            input: Input::deposit(1, tx, dec!(10)),
+        };
+        journal.append(&entry(1)).expect("failed to append");
+        journal.truncate().expect("failed to truncate");
+        journal.append(&entry(2)).expect("failed to append");
+
+        let entries = read_entries(File::open(&path).expect("failed to open journal"))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read entries");
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].input.tx(), 2);
+    }
+
+    #[test]
+    fn file_journal_discard_removes_last_entry() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
+
+        let mut journal =
+            FileJournal::open(&path, SyncPolicy::Never).expect("failed to open journal");
+        let entry = |tx| JournalEntry {
+            line: None,
+            sequence: Some(u64::from(tx)),
+            input: Input::deposit(1, tx, dec!(10)),
+        };
+        journal.append(&entry(1)).expect("failed to append");
+        journal.append(&entry(2)).expect("failed to append");
+        journal.discard().expect("failed to discard");
+        // only the entry appended last can be discarded.
+        assert!(journal.discard().is_err());
+        journal.append(&entry(3)).expect("failed to append");
+
+        let entries = read_entries(File::open(&path).expect("failed to open journal"))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read entries");
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].input.tx(), 1);
+        assert_eq!(entries[1].input.tx(), 3);
+    }
+
+    #[test]
+    fn file_journal_open_removes_incomplete_entry() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
+
+        let mut journal =
+            FileJournal::open(&path, SyncPolicy::Always).expect("failed to open journal");
+        journal
+            .append(&JournalEntry {
+                line: Some(2),
+                sequence: Some(1),
+                input: Input::deposit(1, 1, dec!(10)),
+            })
+            .expect("failed to append");
+        drop(journal);
+        let complete = fs::read(&path).expect("failed to read journal");
+        let mut torn = complete.clone();
+        torn.extend_from_slice(br#"{"line":3,"input":{"ty"#);
+        fs::write(&path, &torn).expect("failed to write journal");
+
+        // the incomplete entry is ignored when read, and removed when the journal is opened again.
+        assert_eq!(read_entries(torn.as_slice()).count(), 1);
+        FileJournal::open(&path, SyncPolicy::Always).expect("failed to reopen journal");
+        assert_eq!(fs::read(&path).expect("failed to read journal"), complete);
+    }
+}
@@ -1,631 +1,4559 @@
-use crate::trx_engine::account::Account;
-use crate::trx_engine::errors::EngineError;
-use crate::trx_engine::transaction::{Input, State, Transaction, Type};
-use anyhow::anyhow;
-use std::collections::HashMap;
-
-pub(crate) struct Ledger {
-    accounts: HashMap<u16, Account>,
-    trx: HashMap<u32, Transaction>,
+use crate::trx_engine::account::{Account, AccountStatus};
+use crate::trx_engine::account_store::AccountStore;
+use crate::trx_engine::currency::Currency;
+use crate::trx_engine::errors::{EngineError, SnapshotError};
+use crate::trx_engine::event_store::EventStore;
+use crate::trx_engine::events::{Event, EventRecord, Projection};
+use crate::trx_engine::fees::{FeeSchedule, FeeTransaction};
+use crate::trx_engine::journal::{Journal, JournalEntry};
+use crate::trx_engine::limits::CreditLimits;
+use crate::trx_engine::rates::RateProvider;
+use crate::trx_engine::rounding::PrecisionPolicy;
+use crate::trx_engine::snapshot;
+use crate::trx_engine::snapshot::{SnapshotInto, SnapshotOf};
+use crate::trx_engine::transaction::{Dispute, Input, State, Transaction, Type};
+use crate::trx_engine::transaction_store::TransactionStore;
+use crate::trx_engine::unit_of_work::UnitOfWork;
+use log::{error, warn};
+use rust_decimal::Decimal;
+use serde::de::DeserializeSeed;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::io::{Read, Write};
+
+/// Outcome of an input successfully applied to the ledger.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    Deposited {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+    },
+    Withdrawn {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+    },
+    Transferred {
+        client: u16,
+        destination: u16,
+        tx: u32,
+        amount: Decimal,
+    },
+    /// The amount left the balance in the currency of the exchange and the converted amount was credited to the target currency.
+    Exchanged {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        rate: Decimal,
+        converted: Decimal,
+    },
+    Authorized {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+    },
+    /// The captured amount left the account, the rest of the authorization was released.
+    Captured {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+        released: Decimal,
+    },
+    Voided {
+        client: u16,
+        tx: u32,
+        amount: Decimal,
+    },
+    DisputeOpened {
+        client: u16,
+        tx: u32,
+        dispute: u32,
+        amount: Decimal,
+    },
+    DisputeResolved {
+        client: u16,
+        tx: u32,
+        dispute: u32,
+        amount: Decimal,
+    },
+    ChargedBack {
+        client: u16,
+        tx: u32,
+        dispute: u32,
+        amount: Decimal,
+    },
+    /// Synthetic event recorded by the ledger when a hold outlives its lifetime, see `HoldLifetimes`.
+    /// `dispute` names the expired dispute, or is `None` for an expired authorization.
+    HoldExpired {
+        client: u16,
+        tx: u32,
+        dispute: Option<u32>,
+        amount: Decimal,
+    },
+    /// An administrative operation changed the status of the account.
+    StatusChanged {
+        client: u16,
+        tx: u32,
+        status: AccountStatus,
+    },
+}
+
+/// Policy deciding which inputs are allowed to open an account for a client unknown to the ledger.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum AccountCreation {
+    /// Only a successful deposit opens an account. Any other input for an unknown client is rejected.
+    #[default]
+    OnDeposit,
+    /// Any successfully processed input opens an account.
+    OnAnyTransaction,
+}
+
+/// Policy deciding which transactions can be disputed.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum DisputePolicy {
+    /// Only deposits can be disputed.
+    #[default]
+    DepositsOnly,
+    /// Deposits and withdrawals can be disputed. A disputed withdrawal is provisionally credited
+    /// back as held funds, which become available on chargeback or are removed on resolve.
+    /// Transfers, being withdrawals for the client disputing them, can be disputed as well.
+    DepositsAndWithdrawals,
+}
+
+/// Lifetime of the holds placed on the funds of an account, in the unit of the input timestamps.
+/// Once the logical clock of the ledger (the latest processed timestamp) reaches the expiry of a hold,
+/// the hold is released as if the client sent the matching resolve or void.
+/// Holds placed while the clock is unknown, or without a lifetime, stay until they are settled.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub struct HoldLifetimes {
+    /// Expired disputes are resolved.
+    pub dispute: Option<u64>,
+    /// Expired authorizations are voided.
+    pub authorization: Option<u64>,
+}
+
+/// Configuration of the business rules applied by the ledger.
+/// Use `LedgerConfig::default()` and override the required fields to stay compatible with new options.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct LedgerConfig {
+    pub account_creation: AccountCreation,
+    pub dispute_policy: DisputePolicy,
+    pub hold_lifetimes: HoldLifetimes,
+    /// Time after which a transaction can no longer be disputed, in the unit of the input timestamps.
+    /// Disputes of transactions processed, or opened, while the clock was unknown are always accepted.
+    pub dispute_window: Option<u64>,
+    /// Number of decimal places allowed in the amounts of each currency. Inputs with more decimal places are rejected,
+    /// and amounts computed by the ledger, e.g. the converted amount of an exchange, are rounded to it.
+    pub precision: PrecisionPolicy,
+    /// Fees charged on withdrawals and chargebacks, no fee is charged without a schedule.
+    pub fees: Option<FeeSchedule>,
+    /// How far below zero withdrawals can take the available funds of each client.
+    /// Chargebacks are applied whatever the limit, an account taken over its limit is flagged in the report.
+    pub credit_limits: CreditLimits,
+}
+
+/// Expiry of an open dispute, or of an authorization if `dispute` is `None`.
+/// Holds are ordered by expiry, so the expired ones are found without scanning every transaction.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+struct Hold {
+    expires_at: u64,
+    tx: u32,
+    dispute: Option<u32>,
+}
+
+/// Versions of the transactions and accounts replaced by a commit, in the order they were written,
+/// `None` for those it created. They are restored if the commit fails, see `Ledger::commit`.
+#[derive(Default)]
+struct Rollback {
+    trx: Vec<(u32, Option<Transaction>)>,
+    accounts: Vec<(u16, Option<Account>)>,
+}
+
+/// Ledger is the entry point of the engine. It keeps track of the accounts of every client
+/// and of the transactions that can still be referenced by a dispute, a resolve or a chargeback.
+/// Inputs are applied one at a time through `process_trx`.
+pub struct Ledger {
+    config: LedgerConfig,
+    /// Accounts of every client, kept in memory unless another store is provided.
+    accounts: Box<dyn AccountStore>,
+    /// Transactions that can still be referenced by later inputs, kept in memory unless another store is provided.
+    trx: Box<dyn TransactionStore>,
+    /// Latest timestamp processed, the ledger has no notion of time until an input carries one.
+    clock: Option<u64>,
+    /// Holds that may expire. Entries are not removed when a hold is settled,
+    /// they are validated against the transaction once expired.
+    holds: BTreeSet<Hold>,
+    /// Events generated by the ledger itself since they were last drained.
+    events: Vec<Outcome>,
+    /// Rates of the exchanges that do not carry their own rate.
+    rates: Option<Box<dyn RateProvider>>,
+    /// Fees posted since they were last drained.
+    fees: Vec<FeeTransaction>,
+    /// Journal recording every input before its changes are written to the stores, if any.
+    /// The entry is discarded if the changes cannot be written, so it only holds accepted inputs.
+    journal: Option<Box<dyn Journal>>,
+    /// Number of inputs accepted by the ledger, including those of the state it continues from.
+    sequence: u64,
+    /// Domain events of every change of the accounts, discarded unless a store is provided.
+    event_store: Option<Box<dyn EventStore>>,
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A ledger is serialized as a snapshot of its state, see `snapshot::SNAPSHOT_VERSION` for the format.
+impl Serialize for Ledger {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SnapshotOf {
+            clock: self.clock,
+            sequence: self.sequence,
+            accounts: self.accounts.as_ref(),
+            trx: self.trx.as_ref(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A ledger deserialized from a snapshot has the default configuration and keeps its state in memory,
+/// use `Ledger::restore` to restore a snapshot into a configured ledger.
+impl<'de> serde::Deserialize<'de> for Ledger {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut ledger = Self::new();
+        ledger.restore_from(deserializer, &mut None, &mut None)?;
+        Ok(ledger)
+    }
 }
 
 impl Ledger {
-    pub(crate) fn new() -> Self {
-        Self {
-            accounts: HashMap::new(),
-            trx: HashMap::new(),
+    pub fn new() -> Self {
+        Self::with_config(LedgerConfig::default())
+    }
+
+    pub fn with_config(config: LedgerConfig) -> Self {
+        let mut ledger = Self {
+            config,
+            accounts: Box::new(HashMap::new()),
+            trx: Box::new(HashMap::new()),
+            clock: None,
+            holds: BTreeSet::new(),
+            events: Vec::new(),
+            rates: None,
+            fees: Vec::new(),
+            journal: None,
+            sequence: 0,
+            event_store: None,
+        };
+        ledger.open_house_account();
+        ledger
+    }
+
+    /// Sets the source of the rates of the exchanges that do not carry their own rate.
+    pub fn with_rate_provider(mut self, rates: Box<dyn RateProvider>) -> Self {
+        self.rates = Some(rates);
+        self
+    }
+
+    /// Sets the store of the processed transactions, e.g. a `DiskTransactionStore` to process files
+    /// with more transactions than fit in memory. The store should be set before any input is processed.
+    pub fn with_transaction_store(mut self, trx: Box<dyn TransactionStore>) -> Self {
+        self.trx = trx;
+        self
+    }
+
+    /// Sets the store of the accounts, e.g. a `FileAccountStore` to continue from the accounts left by a previous run.
+    /// The store should be set before any input is processed, its changes are persisted by `checkpoint`.
+    /// The ledger continues from the sequence of the last input the store persisted.
+    pub fn with_account_store(mut self, accounts: Box<dyn AccountStore>) -> Self {
+        self.sequence = accounts.sequence();
+        self.accounts = accounts;
+        self.open_house_account();
+        self
+    }
+
+    /// Sets the journal recording every accepted input, e.g. a `FileJournal` to recover from a crash.
+    /// The journal should be set once the ledger is restored and recovered, before any input is processed.
+    pub fn with_journal(mut self, journal: Box<dyn Journal>) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Persists the changes of the account and event stores, e.g. once the file the inputs were read from
+    /// was processed completely. Stores outliving the ledger only keep the changes of the inputs processed
+    /// before a checkpoint, so a run that is aborted leaves them as they were.
+    /// The journal is left as is, see `truncate_journal`.
+    /// # Errors
+    /// * An error is returned if a store cannot be persisted.
+    ///
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        self.accounts.persist(self.sequence)?;
+        match &mut self.event_store {
+            Some(event_store) => event_store.persist(self.sequence),
+            None => Ok(()),
         }
     }
 
-    pub(crate) fn get_accounts(self) -> HashMap<u16, Account> {
-        self.accounts
+    /// Number of inputs accepted by the ledger, including those of the state it continues from:
+    /// the sequence persisted by its account or event store, or the one of the snapshot it restored.
+    /// Each accepted input is journaled with the sequence it brings the ledger to, see `recover`.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
     }
 
-    pub(crate) fn process_trx(&mut self, input: &Input) -> anyhow::Result<()> {
-        // fetch account or create new record
-        let account = self
-            .accounts
-            .entry(input.client)
-            .or_insert(Account::new(input.client));
+    /// Removes every entry of the journal, if any, once the inputs it recorded no longer need to be recovered.
+    /// It should only be called once the state holding them is durable, i.e. after a `checkpoint`
+    /// and once the snapshot of the ledger, if any, is in place.
+    /// # Errors
+    /// * An error is returned if the journal cannot be truncated.
+    ///
+    pub fn truncate_journal(&mut self) -> io::Result<()> {
+        match &mut self.journal {
+            Some(journal) => journal.truncate(),
+            None => Ok(()),
+        }
+    }
 
-        match input.transaction_type {
-            Type::Deposit => {
-                // if this transaction is already present in the ledger there is an inconsistent behaviour.
-                if self.trx.contains_key(&input.tx) {
-                    return Err(anyhow!(EngineError::TrxAlreadyProcessed));
-                }
+    /// Sets the store of the domain events, e.g. a `FileEventStore` to keep the history of the accounts across runs.
+    /// The store should be set before any input is processed, use `from_events` to continue from the events it holds.
+    pub fn with_event_store(mut self, event_store: Box<dyn EventStore>) -> Self {
+        self.event_store = Some(event_store);
+        self
+    }
 
-                // validate that the amount has a workable value.
-                let Some(amount) = input.amount else {
-                    return Err(anyhow!(EngineError::TrxInvalidAmount));
-                };
+    /// Creates a ledger whose accounts are rebuilt by applying the events of the provided store, in the order
+    /// they were recorded, e.g. to continue from the history a `FileEventStore` kept across runs.
+    /// The events of the accepted inputs are then appended to the same store.
+    /// Only the accounts are rebuilt: transactions, holds and the clock start empty, as for a new ledger,
+    /// so an input referring to a transaction of a previous run, e.g. a dispute, is rejected as unknown.
+    /// The ledger continues from the sequence of the last input the store persisted.
+    /// # Errors
+    /// * An error is returned if an event cannot be read or cannot be applied to its account.
+    ///
+    pub fn from_events(config: LedgerConfig, event_store: Box<dyn EventStore>) -> io::Result<Self> {
+        let mut accounts: HashMap<u16, Account> = HashMap::new();
+        project(event_store.as_ref(), &mut accounts)?;
 
-                account.deposit(amount)?;
-                self.trx.insert(input.tx, Transaction::new(input));
-            }
+        let mut ledger = Self::with_config(config).with_account_store(Box::new(accounts));
+        ledger.sequence = event_store.sequence();
+        Ok(ledger.with_event_store(event_store))
+    }
 
-            Type::Withdrawal => {
-                // if this transaction is already present in the ledger there is an inconsistent behaviour.
-                if self.trx.contains_key(&input.tx) {
-                    return Err(anyhow!(EngineError::TrxAlreadyProcessed));
-                }
+    /// Opens the house account collecting the fees, if missing,
+    /// so it is part of the report even before it collects any fee.
+    fn open_house_account(&mut self) {
+        let Some(schedule) = &self.config.fees else {
+            return;
+        };
 
-                // validate that the amount has a workable value.
-                let Some(amount) = input.amount else {
-                    return Err(anyhow!(EngineError::TrxInvalidAmount));
-                };
+        let house_account = schedule.house_account;
+        let result = match self.accounts.get(house_account) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => self.accounts.insert(Account::new(house_account)),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!(
+                "failed to open house account: {} with error: {:?}",
+                house_account, e
+            );
+        }
+    }
 
-                account.withdrawal(amount)?;
-                self.trx.insert(input.tx, Transaction::new(input));
-            }
+    pub(crate) fn account_store(&self) -> &dyn AccountStore {
+        self.accounts.as_ref()
+    }
 
-            Type::Dispute => {
-                // find the transaction to be disputed and if no transaction is found,
-                // assume error from the banking partner. Continue processing the rest of the CSV.
-                let disputed_trx = match self.trx.get_mut(&input.tx) {
-                    Some(trx) => trx,
-                    None => return Err(anyhow!(EngineError::TrxNotFound)),
-                };
+    /// Returns the account of the provided client, if the ledger has any record of it.
+    /// An account the store fails to read is logged and reported as missing.
+    pub fn account(&self, client: u16) -> Option<Cow<'_, Account>> {
+        self.accounts.get(client).unwrap_or_else(|e| {
+            warn!("failed to read account: {} with error: {:?}", client, e);
+            None
+        })
+    }
 
-                // validate that the retrieved transaction belongs to the same client.
-                if input.client != disputed_trx.client {
-                    return Err(anyhow!(EngineError::TrxClientIdInconsistency));
-                }
+    /// Returns an iterator over all the accounts of the ledger, in no particular order.
+    /// Accounts the store fails to read are logged and skipped.
+    pub fn accounts(&self) -> impl Iterator<Item = Cow<'_, Account>> {
+        self.accounts.accounts().filter_map(|account| {
+            account
+                .inspect_err(|e| warn!("failed to read account with error: {:?}", e))
+                .ok()
+        })
+    }
 
-                // validate that the transaction to be disputed is a deposit or a withdrawal
-                match disputed_trx.transaction_type {
-                    Type::Withdrawal | Type::Resolve | Type::Dispute | Type::Chargeback  => {
-                        return Err(anyhow!(EngineError::TrxNotDisputable))
-                    }
-                    _ => {}
-                }
+    /// Returns the deposit, withdrawal or administrative operation stored under the provided transaction id.
+    /// A transaction the store fails to read is logged and reported as missing.
+    pub fn transaction(&self, tx: u32) -> Option<Cow<'_, Transaction>> {
+        self.trx.get(tx).unwrap_or_else(|e| {
+            warn!("failed to read transaction: {} with error: {:?}", tx, e);
+            None
+        })
+    }
 
-                // validate that the transaction to be disputed is not already under dispute or if it was chargeback.
-                if disputed_trx.state != State::Ok {
-                    return Err(anyhow!(EngineError::TrxNotInDisputableState));
-                }
+    /// Returns the logical clock of the ledger: the latest timestamp processed, if any input carried one.
+    pub fn clock(&self) -> Option<u64> {
+        self.clock
+    }
 
-                // validate that the amount of the disputed trx has a workable value.
-                let Some(amount) = disputed_trx.amount else {
-                    return Err(anyhow!(EngineError::TrxInvalidAmount));
-                };
+    /// Returns the fees posted by the ledger since they were last drained, in the order they were charged.
+    pub fn fee_transactions(&self) -> &[FeeTransaction] {
+        &self.fees
+    }
+
+    /// Removes and returns the fees posted by the ledger, in the order they were charged.
+    /// The ledger keeps every fee until it is drained, so a long running ledger should drain them regularly.
+    pub fn drain_fee_transactions(&mut self) -> impl Iterator<Item = FeeTransaction> + '_ {
+        self.fees.drain(..)
+    }
 
-                account.dispute(amount)?;
-                // mark transaction as being disputed.
-                disputed_trx.open_dispute();
+    /// Returns the events generated by the ledger itself since they were last drained, in the order they were applied.
+    pub fn synthetic_events(&self) -> &[Outcome] {
+        &self.events
+    }
+
+    /// Removes and returns the events generated by the ledger itself, in the order they were applied.
+    /// The ledger keeps every event until it is drained, so a long running ledger should drain them regularly.
+    pub fn drain_synthetic_events(&mut self) -> impl Iterator<Item = Outcome> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Returns the domain events recorded by the ledger, in the order they were recorded.
+    /// Events record how every balance was reached: folding them, e.g. through `project`, rebuilds the accounts
+    /// changed since the ledger started from its restored state, or from empty accounts if nothing was restored.
+    /// The house account opened by the fee schedule has no event until it collects a fee.
+    /// Nothing is returned unless an event store was provided, events are discarded by default.
+    pub fn events(&self) -> impl Iterator<Item = io::Result<Cow<'_, EventRecord>>> {
+        self.event_store.iter().flat_map(|store| store.events())
+    }
+
+    /// Folds the domain events recorded by the ledger into the provided projection, in the order they were recorded.
+    /// # Errors
+    /// * An error is returned if an event cannot be read, or the projection fails to apply it.
+    ///
+    pub fn project<P: Projection>(&self, projection: &mut P) -> io::Result<()> {
+        match &self.event_store {
+            Some(store) => project(store.as_ref(), projection),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes a snapshot of the state of the ledger: its clock, accounts and transactions.
+    /// The fees posted and the synthetic events record what happened during the run, they are not part of it.
+    /// # Errors
+    /// * An error is returned if the snapshot cannot be written, or a store fails to read the state.
+    ///
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+        serde_json::to_writer(&mut writer, self)?;
+        // a buffered writer would otherwise only report a failure of its last write when dropped, i.e. never.
+        writer.flush().map_err(SnapshotError::Io)
+    }
+
+    /// Restores a snapshot written by `write_snapshot`, e.g. to resume from the closing snapshot of the previous day.
+    /// The snapshot should be restored before any input is processed, once the stores of the ledger are set.
+    /// The configuration of the ledger applies to the restored state, holds expire according to its lifetimes.
+    /// # Errors
+    /// * An error is returned if the snapshot cannot be read, is invalid, was written in an unsupported
+    ///   version of the format, or a store fails to save the restored state.
+    ///   Only the version is validated before restoring, so the ledger may be partially restored.
+    ///
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        let mut version = None;
+        let mut failure = None;
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let result = self
+            .restore_from(&mut deserializer, &mut version, &mut failure)
+            .and_then(|_| deserializer.end());
+
+        let Err(e) = result else {
+            return Ok(());
+        };
+        match (version, failure) {
+            (Some(version), _) if !snapshot::is_supported(version) => {
+                Err(SnapshotError::UnsupportedVersion {
+                    version,
+                    supported: snapshot::SNAPSHOT_VERSION,
+                })
             }
+            (_, Some(failure)) => Err(SnapshotError::Store(failure)),
+            _ => Err(SnapshotError::from(e)),
+        }
+    }
 
-            Type::Resolve => {
-                // find the transaction to be resolved and if no transaction is found,
-                // assume error from the banking partner. Continue processing the rest of the CSV.
-                let resolved_trx = match self.trx.get_mut(&input.tx) {
-                    Some(trx) => trx,
-                    None => return Err(anyhow!(EngineError::TrxNotFound)),
-                };
+    /// Restores a snapshot into the stores of the ledger and registers the holds it has open.
+    fn restore_from<'de, D: serde::Deserializer<'de>>(
+        &mut self,
+        deserializer: D,
+        version: &mut Option<u32>,
+        failure: &mut Option<io::Error>,
+    ) -> Result<(), D::Error> {
+        let mut clock = None;
+        let mut sequence = self.sequence;
+        let mut open = Vec::new();
+        let accounts = &mut self.accounts;
+        let trx = &mut self.trx;
 
-                // validate that the transaction to be resolved is under dispute.
-                if resolved_trx.state != State::Disputed {
-                    return Err(anyhow!(EngineError::TrxNotInDispute));
+        SnapshotInto {
+            clock: &mut clock,
+            sequence: &mut sequence,
+            account: &mut |account| accounts.insert(account),
+            transaction: &mut |transaction: Transaction| {
+                let disputed = transaction
+                    .disputes()
+                    .any(|(_, dispute)| dispute.state == State::Disputed);
+                if disputed || transaction.state == State::Authorized {
+                    open.push(transaction.clone());
                 }
+                trx.insert(transaction)
+            },
+            version,
+            failure,
+        }
+        .deserialize(deserializer)?;
 
-                // validate that the retrieved transaction belongs to the same client.
-                if input.client != resolved_trx.client {
-                    return Err(anyhow!(EngineError::TrxClientIdInconsistency));
-                }
+        self.clock = self.clock.max(clock);
+        self.sequence = sequence;
+        self.register_holds(open.iter());
+        Ok(())
+    }
 
-                // validate that the amount of the resolved trx has a workable value.
-                let Some(amount) = resolved_trx.amount else {
-                    return Err(anyhow!(EngineError::TrxInvalidAmount));
-                };
+    /// Applies a single input to the ledger.
+    /// # Errors
+    /// * An `EngineError` describing the reason is returned if the input is rejected,
+    ///   in which case the ledger is left untouched.
+    ///
+    pub fn process_trx(&mut self, input: &Input) -> Result<Outcome, EngineError> {
+        self.process(input, None)
+    }
+
+    /// Applies a single input read from the provided line of an input file.
+    /// The line is recorded by the journal, so a recovered ledger knows where to resume reading the file.
+    /// # Errors
+    /// * An `EngineError` describing the reason is returned if the input is rejected,
+    ///   in which case the ledger is left untouched.
+    ///
+    pub fn process_trx_at_line(
+        &mut self,
+        input: &Input,
+        line: u64,
+    ) -> Result<Outcome, EngineError> {
+        self.process(input, Some(line))
+    }
 
-                account.resolve(amount)?;
-                // mark disputed transaction as resolved.
-                resolved_trx.resolve_dispute();
+    /// Replays the entries of a journal, rebuilding the state of a ledger that stopped midway.
+    /// The ledger should have the configuration and stores the journal was written with, and the state
+    /// the run that wrote it continued from, e.g. the same snapshot or the stores it persisted.
+    /// Entries up to the sequence of the ledger are already held by that state, e.g. by stores persisted
+    /// before the journal was truncated, so they are skipped, every other entry is applied again.
+    /// Entries are not journaled again, and an entry rejected on replay is logged and skipped.
+    /// Returns the line of the input file the last replayed entry was read from, if any, to resume reading from.
+    /// # Errors
+    /// * An error is returned if an entry cannot be read, the entries replayed so far stay applied.
+    ///
+    pub fn recover<I: IntoIterator<Item = io::Result<JournalEntry>>>(
+        &mut self,
+        entries: I,
+    ) -> io::Result<Option<u64>> {
+        let mut last_line = None;
+        for entry in entries {
+            let entry = entry?;
+            last_line = entry.line.or(last_line);
+            if entry
+                .sequence
+                .is_some_and(|sequence| sequence <= self.sequence)
+            {
+                continue;
             }
 
-            Type::Chargeback => {
-                // find the transaction to be chargeback and if no transaction is found,
-                // assume error from the banking partner. Continue processing the rest of the CSV.
-                let chargeback_trx = match self.trx.get_mut(&input.tx) {
-                    Some(trx) => trx,
-                    None => return Err(anyhow!(EngineError::TrxNotFound)),
-                };
+            if let Err(e) = self.process(&entry.input, None) {
+                warn!(
+                    "failed to replay journal entry: {:?} with error: {:?}",
+                    entry, e
+                );
+            }
+            // the ledger keeps the sequence of the journal, even if the entry was rejected on replay.
+            if let Some(sequence) = entry.sequence {
+                self.sequence = sequence;
+            }
+        }
 
-                // validate that the retrieved transaction belongs to the same client.
-                if input.client != chargeback_trx.client {
-                    return Err(anyhow!(EngineError::TrxClientIdInconsistency));
-                }
+        Ok(last_line)
+    }
 
-                // validate that the amount of the chargeback trx has a workable value.
-                let Some(amount) = chargeback_trx.amount else {
-                    return Err(anyhow!(EngineError::TrxInvalidAmount));
-                };
+    /// Applies a single input, see `process_trx`.
+    fn process(&mut self, input: &Input, line: Option<u64>) -> Result<Outcome, EngineError> {
+        // inputs without a timestamp happen at the current time of the logical clock.
+        let input = &Input {
+            timestamp: input.timestamp.or(self.clock),
+            ..input.clone()
+        };
+        let clock = self.clock.max(input.timestamp);
 
-                // perform the necessary calculations for chargeback and lock account.
-                account.chargeback(amount)?;
+        // every change is staged in the unit of work and only applied to the ledger
+        // once the whole input was processed successfully, expired holds included.
+        let mut work = UnitOfWork::new(self.accounts.as_ref(), self.trx.as_ref());
+        let expired = self.release_expired_holds(&mut work, clock);
+        let outcome = self.apply(&mut work, input)?;
+
+        let changes = work.into_changes();
+
+        // writing to the journal and the stores are the only other steps that can fail once the input was applied,
+        // so they are done before any other change reaches the ledger.
+        self.commit(input, line, &changes.trx, changes.accounts, changes.events)?;
+
+        if let Some(clock) = clock {
+            self.holds = self.holds.split_off(&Hold {
+                expires_at: clock.saturating_add(1),
+                tx: 0,
+                dispute: None,
+            });
+        }
+        self.register_holds(changes.trx.values());
+        self.events.extend(expired);
+        self.fees.extend(changes.fees);
+        self.clock = clock;
+
+        Ok(outcome)
+    }
+
+    /// Records the input in the journal, then writes the staged transactions and accounts to their stores
+    /// and the events of the input, all or nothing: nothing is written if the journal fails, and if a store fails,
+    /// every version written so far is replaced by the version it overwrote, or removed if it was new,
+    /// and the journal entry is discarded, so the stores and the journal are left as they were before the input.
+    /// The events are written last and all at once, so they are only recorded for stored changes.
+    /// # Errors
+    /// * An error is returned if the journal fails to record the input,
+    ///   or a store fails to read or write a staged transaction, account or the events.
+    ///
+    fn commit(
+        &mut self,
+        input: &Input,
+        line: Option<u64>,
+        trx: &HashMap<u32, Transaction>,
+        accounts: HashMap<u16, Account>,
+        events: Vec<EventRecord>,
+    ) -> Result<(), EngineError> {
+        let sequence = self.sequence + 1;
+        self.journal(input, line, sequence)?;
 
-                // mark transaction as chargeback.
-                chargeback_trx.chargeback_dispute();
+        let mut rollback = Rollback::default();
+        let result = self
+            .write_changes(trx, accounts, &mut rollback)
+            .and_then(|()| self.record_events(input, events));
+        match result {
+            Ok(()) => self.sequence = sequence,
+            Err(_) => {
+                self.rollback(rollback);
+                self.discard_journal_entry(input);
             }
         }
+
+        result
+    }
+
+    /// Writes the staged changes, recording the version each one replaced in `rollback`.
+    fn write_changes(
+        &mut self,
+        trx: &HashMap<u32, Transaction>,
+        accounts: HashMap<u16, Account>,
+        rollback: &mut Rollback,
+    ) -> Result<(), EngineError> {
+        for staged in trx.values() {
+            let trx_failure = |e: io::Error| EngineError::TrxStoreFailure {
+                tx: staged.tx,
+                reason: e.to_string(),
+            };
+            let previous = self
+                .trx
+                .get(staged.tx)
+                .map_err(trx_failure)?
+                .map(Cow::into_owned);
+            self.trx.insert(staged.clone()).map_err(trx_failure)?;
+            rollback.trx.push((staged.tx, previous));
+        }
+        for (client, account) in accounts {
+            let account_failure = |e: io::Error| EngineError::AccountStoreFailure {
+                client,
+                reason: e.to_string(),
+            };
+            let previous = self
+                .accounts
+                .get(client)
+                .map_err(account_failure)?
+                .map(Cow::into_owned);
+            self.accounts.insert(account).map_err(account_failure)?;
+            rollback.accounts.push((client, previous));
+        }
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rust_decimal::prelude::ToPrimitive;
-    use rust_decimal::Decimal;
-    use rust_decimal_macros::dec;
-    use std::ops::Neg;
+    /// Appends the events recorded for the input to the event store, if any and if it recorded any.
+    fn record_events(
+        &mut self,
+        input: &Input,
+        events: Vec<EventRecord>,
+    ) -> Result<(), EngineError> {
+        let Some(event_store) = &mut self.event_store else {
+            return Ok(());
+        };
+        if events.is_empty() {
+            return Ok(());
+        }
 
-    /// helper func to provide an input fixture to use in the tests
-    fn input(trx_type: Type, client: u16, tx: u32, amount: Option<Decimal>) -> Input {
-        Input {
-            transaction_type: trx_type,
-            client,
-            tx,
-            amount,
+        event_store
+            .append(events)
+            .map_err(|e| EngineError::EventStoreFailure {
+                tx: input.tx,
+                reason: e.to_string(),
+            })
+    }
+
+    /// Records the input in the journal, if any, with the sequence it brings the ledger to.
+    fn journal(
+        &mut self,
+        input: &Input,
+        line: Option<u64>,
+        sequence: u64,
+    ) -> Result<(), EngineError> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(());
+        };
+
+        let entry = JournalEntry {
+            line,
+            sequence: Some(sequence),
+            input: input.clone(),
+        };
+        journal
+            .append(&entry)
+            .map_err(|e| EngineError::JournalFailure {
+                tx: input.tx,
+                reason: e.to_string(),
+            })
+    }
+
+    /// Discards the journal entry of an input whose changes could not be written, if any.
+    /// A journal failing to discard it would replay the input on recovery, which is logged.
+    fn discard_journal_entry(&mut self, input: &Input) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+
+        if let Err(e) = journal.discard() {
+            error!(
+                "failed to discard journal entry of transaction: {} with error: {:?}",
+                input.tx, e
+            );
         }
     }
 
-    #[test]
-    fn process_trx_successful_deposit_dispute_resolve_chargeback() {
-        let client_id = 0;
-        let tx_id = 1;
-        let amount = dec!(1500);
+    /// Restores the versions replaced by a failed commit, in the reverse order they were written.
+    /// A store failing to restore a version is left inconsistent, which is logged.
+    fn rollback(&mut self, rollback: Rollback) {
+        for (client, previous) in rollback.accounts.into_iter().rev() {
+            let result = match previous {
+                Some(account) => self.accounts.insert(account),
+                None => self.accounts.remove(client),
+            };
+            if let Err(e) = result {
+                error!(
+                    "failed to roll back account: {} with error: {:?}",
+                    client, e
+                );
+            }
+        }
+        for (tx, previous) in rollback.trx.into_iter().rev() {
+            let result = match previous {
+                Some(trx) => self.trx.insert(trx),
+                None => self.trx.remove(tx),
+            };
+            if let Err(e) = result {
+                error!(
+                    "failed to roll back transaction: {} with error: {:?}",
+                    tx, e
+                );
+            }
+        }
+    }
 
-        let trxs = vec![
-            input(Type::Deposit, client_id, tx_id, Some(amount)),
-            input(Type::Dispute, client_id, tx_id, None),
-            input(Type::Resolve, client_id, tx_id, None),
-            input(Type::Chargeback, client_id, tx_id, None),
-        ];
+    /// Releases every hold expired at the provided time and returns the matching synthetic events.
+    /// A release goes through the same path as a resolve or void sent by the client,
+    /// so a release rejected by the account (e.g. a locked account) leaves the hold in place
+    /// until the client settles it.
+    fn release_expired_holds(&self, work: &mut UnitOfWork, clock: Option<u64>) -> Vec<Outcome> {
+        let Some(clock) = clock else {
+            return Vec::new();
+        };
 
-        let mut ledger = Ledger::new();
+        let expired = self.holds.range(
+            ..=Hold {
+                expires_at: clock,
+                tx: u32::MAX,
+                dispute: Some(u32::MAX),
+            },
+        );
 
-        for t in trxs.into_iter() {
-            _ = ledger.process_trx(&t)
+        let mut events = Vec::new();
+        for hold in expired {
+            let Some((release, event)) = self.hold_release(work, hold) else {
+                continue;
+            };
+
+            match self.apply(work, &release) {
+                Ok(_) => events.push(event),
+                Err(e) => warn!(
+                    "failed to release expired hold: {:?} with error: {:?}",
+                    hold, e
+                ),
+            }
         }
 
-        let account = ledger.accounts.get(&client_id).expect("account not found");
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.available, amount);
-        assert_eq!(account.held, amount.neg());
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+        events
     }
 
-    #[test]
-    fn process_trx_successful_after_dispute_resolve() {
-        let client_id = 0;
-        let tx = 1;
-        let amount_deposit = dec!(1500);
-        let amount_withdrawal = dec!(500);
+    /// Returns the input releasing the hold and the event recording it,
+    /// or `None` if the hold was settled, or opened again, since it was registered.
+    fn hold_release(&self, work: &UnitOfWork, hold: &Hold) -> Option<(Input, Outcome)> {
+        let trx = match work.transaction(hold.tx) {
+            Ok(trx) => trx?,
+            Err(e) => {
+                warn!(
+                    "failed to read expired hold: {:?} with error: {:?}",
+                    hold, e
+                );
+                return None;
+            }
+        };
 
-        let trxs = vec![
-            input(Type::Deposit, client_id, tx, Some(amount_deposit)),
-            input(Type::Withdrawal, client_id, 2, Some(amount_withdrawal)),
-            input(Type::Dispute, client_id, tx, None),
-            input(Type::Resolve, client_id, tx, None),
-            input(Type::Deposit, client_id, 3, Some(amount_deposit)),
-            input(Type::Withdrawal, client_id, 4, Some(amount_withdrawal)),
-        ];
+        let (opened_at, lifetime, amount, release) = match hold.dispute {
+            Some(id) => {
+                let dispute = trx.dispute(id).filter(|d| d.state == State::Disputed)?;
+                (
+                    dispute.opened_at?,
+                    self.config.hold_lifetimes.dispute?,
+                    dispute.amount,
+                    Input::resolve(trx.client, trx.tx).with_dispute(id),
+                )
+            }
+            None if trx.state == State::Authorized => (
+                trx.timestamp?,
+                self.config.hold_lifetimes.authorization?,
+                trx.amount?,
+                Input::void(trx.client, trx.tx),
+            ),
+            None => return None,
+        };
 
-        let mut ledger = Ledger::new();
+        if opened_at.saturating_add(lifetime) != hold.expires_at {
+            return None;
+        }
 
-        for trx in trxs.into_iter() {
-            _ = ledger.process_trx(&trx)
+        let event = Outcome::HoldExpired {
+            client: trx.client,
+            tx: trx.tx,
+            dispute: hold.dispute,
+            amount,
+        };
+        Some((release.with_timestamp(hold.expires_at), event))
+    }
+
+    /// Indexes the expiry of the open disputes and authorizations of the provided transactions.
+    fn register_holds<'t>(&mut self, trx: impl Iterator<Item = &'t Transaction>) {
+        let lifetimes = self.config.hold_lifetimes;
+
+        for trx in trx {
+            if let (State::Authorized, Some(lifetime), Some(opened_at)) =
+                (trx.state, lifetimes.authorization, trx.timestamp)
+            {
+                self.holds.insert(Hold {
+                    expires_at: opened_at.saturating_add(lifetime),
+                    tx: trx.tx,
+                    dispute: None,
+                });
+            }
+
+            let Some(lifetime) = lifetimes.dispute else {
+                continue;
+            };
+            for (id, dispute) in trx.disputes() {
+                if let (State::Disputed, Some(opened_at)) = (dispute.state, dispute.opened_at) {
+                    self.holds.insert(Hold {
+                        expires_at: opened_at.saturating_add(lifetime),
+                        tx: trx.tx,
+                        dispute: Some(id),
+                    });
+                }
+            }
         }
+    }
 
-        let account = ledger.accounts.get(&client_id).expect("account not found");
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.available, dec!(2000));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(2000));
-        assert!(!account.locked);
+    /// Returns the staged account of the provided client.
+    /// Unknown clients only get a new account if the account creation policy allows the input to open one.
+    fn account_mut<'w>(
+        &self,
+        work: &'w mut UnitOfWork<'_>,
+        client: u16,
+        transaction_type: Type,
+    ) -> Result<&'w mut Account, EngineError> {
+        let may_open = match self.config.account_creation {
+            AccountCreation::OnDeposit => transaction_type == Type::Deposit,
+            AccountCreation::OnAnyTransaction => true,
+        };
 
-        assert_eq!(4, ledger.trx.len());
-        let trx1 = ledger.trx.get(&tx).expect("transaction 1 not found");
-        assert_eq!(trx1.amount, Some(amount_deposit));
-        assert_eq!(trx1.state, State::Ok);
-        assert_eq!(trx1.client, client_id);
+        let account = if may_open {
+            work.open_account_mut(client)?
+        } else {
+            work.account_mut(client)?
+                .ok_or(EngineError::AccountNotFound { client })?
+        };
 
-        let trx2 = ledger.trx.get(&2).expect("transaction 2 not found");
-        assert_eq!(trx2.amount, Some(amount_withdrawal));
-        assert_eq!(trx2.state, State::Ok);
-        assert_eq!(trx2.client, client_id);
+        // the limits of the current configuration apply, even to accounts opened by a previous run.
+        account.credit_limit = self.config.credit_limits.limit(client);
+        Ok(account)
+    }
 
-        let trx3 = ledger.trx.get(&3).expect("transaction 3 not found");
-        assert_eq!(trx3.amount, Some(amount_deposit));
-        assert_eq!(trx3.state, State::Ok);
-        assert_eq!(trx3.client, client_id);
+    /// Returns the staged account that received the funds of a transfer.
+    fn destination_mut<'w>(
+        work: &'w mut UnitOfWork<'_>,
+        trx: &Transaction,
+    ) -> Result<&'w mut Account, EngineError> {
+        let Some(destination) = trx.destination else {
+            return Err(EngineError::TrxInvalidDestination { tx: trx.tx });
+        };
 
-        let trx4 = ledger.trx.get(&4).expect("transaction 4 not found");
-        assert_eq!(trx4.amount, Some(amount_withdrawal));
-        assert_eq!(trx4.state, State::Ok);
-        assert_eq!(trx4.client, client_id);
+        work.account_mut(destination)?
+            .ok_or(EngineError::AccountNotFound {
+                client: destination,
+            })
     }
 
-    #[test]
-    fn process_trx_ignore_all_operations_after_chargeback() {
-        let client_id = 0;
-        let tx_id = 1;
-        let amount = dec!(1500);
+    /// Helper function to validate the precision of the amount of an input.
+    /// # Errors
+    /// * An error is returned if the amount has more decimal places than allowed for its currency.
+    ///
+    fn validate_precision(
+        &self,
+        tx: u32,
+        amount: Decimal,
+        currency: Option<Currency>,
+    ) -> Result<(), EngineError> {
+        let rounding = self.config.precision.rounding(currency);
+        if !rounding.allows(amount) {
+            return Err(EngineError::AmountPrecisionExceeded {
+                tx,
+                amount,
+                decimal_places: rounding.decimal_places,
+            });
+        }
 
-        let trxs = vec![
-            input(Type::Deposit, client_id, tx_id, Some(amount)),
-            input(Type::Dispute, client_id, tx_id, None),
-            input(Type::Chargeback, client_id, tx_id, None),
-            input(Type::Deposit, client_id, 2, Some(amount)),
-            input(Type::Withdrawal, client_id, 3, Some(amount)),
-            input(Type::Dispute, client_id, 4, None),
-            input(Type::Resolve, client_id, 5, None),
-        ];
+        Ok(())
+    }
 
-        let mut ledger = Ledger::new();
+    /// Helper function to validate the currency of an input referring to an existing transaction.
+    /// Inputs without a currency operate in the currency of the transaction.
+    /// # Errors
+    /// * An error is returned if the input names another currency than the transaction.
+    ///
+    fn validate_currency(input: &Input, trx: &Transaction) -> Result<(), EngineError> {
+        match input.currency {
+            Some(currency) if trx.currency != Some(currency) => {
+                Err(EngineError::TrxCurrencyMismatch { tx: input.tx })
+            }
+            _ => Ok(()),
+        }
+    }
 
-        for t in trxs.into_iter() {
-            _ = ledger.process_trx(&t)
+    /// Applies the input to the unit of work, and stages the events it recorded if it is accepted.
+    fn apply(&self, work: &mut UnitOfWork, input: &Input) -> Result<Outcome, EngineError> {
+        let result = self.apply_input(work, input);
+        match result {
+            Ok(_) => work.attribute_events(input),
+            Err(_) => work.discard_events(),
         }
+        result
+    }
 
-        let account = ledger.accounts.get(&client_id).expect("account not found");
-        assert_eq!(account.client, client_id);
-        assert_eq!(account.available, dec!(0));
-        assert_eq!(account.held, dec!(0));
-        assert_eq!(account.total, dec!(0));
-        assert!(account.locked);
+    fn apply_input(&self, work: &mut UnitOfWork, input: &Input) -> Result<Outcome, EngineError> {
+        match input.transaction_type {
+            Type::Deposit => {
+                // if this transaction is already present in the ledger there is an inconsistent behaviour.
+                let state = State::transition(
+                    input.tx,
+                    work.transaction(input.tx)?.map(|trx| trx.state),
+                    input.transaction_type,
+                )?;
+
+                // validate that the amount has a workable value.
+                let Some(amount) = input.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+                self.validate_precision(input.tx, amount, input.currency)?;
+
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .deposit(input.currency, amount)?;
+                work.record(events);
+                let mut trx = Transaction::new(input);
+                trx.set_state(state);
+                work.insert_transaction(input.tx, trx);
+
+                Ok(Outcome::Deposited {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                })
+            }
+
+            Type::Withdrawal => {
+                // if this transaction is already present in the ledger there is an inconsistent behaviour.
+                let state = State::transition(
+                    input.tx,
+                    work.transaction(input.tx)?.map(|trx| trx.state),
+                    input.transaction_type,
+                )?;
+
+                // validate that the amount has a workable value.
+                let Some(amount) = input.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+                self.validate_precision(input.tx, amount, input.currency)?;
+
+                let account = self.account_mut(work, input.client, input.transaction_type)?;
+                let events = account.withdrawal(input.currency, amount)?;
+                let fee = self.fee(input.client, input.transaction_type, input.currency, amount)?;
+                if let Some(fee) = fee {
+                    // the available funds, credit included, must cover the fee of the withdrawal as well.
+                    let available = account.spendable(input.currency);
+                    if fee > available {
+                        let overflow = EngineError::Overflow {
+                            client: input.client,
+                            amount: fee,
+                        };
+                        return Err(EngineError::InsufficientFunds {
+                            client: input.client,
+                            available: available.checked_add(amount).ok_or(overflow.clone())?,
+                            amount: amount.checked_add(fee).ok_or(overflow)?,
+                        });
+                    }
+                }
+                work.record(events);
+                if let Some(fee) = fee {
+                    self.post_fee(work, input, input.client, input.currency, fee)?;
+                }
+                let mut trx = Transaction::new(input);
+                trx.set_state(state);
+                work.insert_transaction(input.tx, trx);
+
+                Ok(Outcome::Withdrawn {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                })
+            }
+
+            Type::Transfer => {
+                // if this transaction is already present in the ledger there is an inconsistent behaviour.
+                let state = State::transition(
+                    input.tx,
+                    work.transaction(input.tx)?.map(|trx| trx.state),
+                    input.transaction_type,
+                )?;
+
+                // validate that the amount has a workable value.
+                let Some(amount) = input.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+                self.validate_precision(input.tx, amount, input.currency)?;
+
+                // validate that the funds are moved to another client.
+                let destination = match input.destination {
+                    Some(destination) if destination != input.client => destination,
+                    _ => return Err(EngineError::TrxInvalidDestination { tx: input.tx }),
+                };
+
+                // both accounts are staged in the same unit of work,
+                // so the transfer is applied to both of them or to none.
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .withdrawal(input.currency, amount)?;
+                work.record(events);
+                let events = self
+                    .account_mut(work, destination, input.transaction_type)?
+                    .deposit(input.currency, amount)?;
+                work.record(events);
+                let mut trx = Transaction::new(input);
+                trx.set_state(state);
+                work.insert_transaction(input.tx, trx);
+
+                Ok(Outcome::Transferred {
+                    client: input.client,
+                    destination,
+                    tx: input.tx,
+                    amount,
+                })
+            }
+
+            Type::Exchange => {
+                // if this transaction is already present in the ledger there is an inconsistent behaviour.
+                let state = State::transition(
+                    input.tx,
+                    work.transaction(input.tx)?.map(|trx| trx.state),
+                    input.transaction_type,
+                )?;
+
+                // validate that the amount has a workable value.
+                let Some(amount) = input.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+                self.validate_precision(input.tx, amount, input.currency)?;
+
+                // validate that the funds are converted between two different currencies.
+                let (from, to) = match (input.currency, input.target_currency) {
+                    (Some(from), Some(to)) if from != to => (from, to),
+                    _ => return Err(EngineError::TrxInvalidExchange { tx: input.tx }),
+                };
+
+                // a rate provided by the input takes precedence over the rate provider.
+                let rate = input.rate.or_else(|| self.rates.as_ref()?.rate(from, to));
+                let rate = match rate {
+                    Some(rate) if rate > Decimal::ZERO => rate,
+                    Some(rate) => {
+                        return Err(EngineError::ExchangeRateInvalid { tx: input.tx, rate })
+                    }
+                    None => {
+                        return Err(EngineError::ExchangeRateNotFound {
+                            tx: input.tx,
+                            from,
+                            to,
+                        })
+                    }
+                };
+
+                let Some(converted) = amount.checked_mul(rate) else {
+                    return Err(EngineError::Overflow {
+                        client: input.client,
+                        amount,
+                    });
+                };
+                // the converted amount is rounded to the precision of the target currency.
+                let converted = self.config.precision.rounding(Some(to)).round(converted);
+
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .exchange(from, amount, to, converted)?;
+                work.record(events);
+                let mut trx = Transaction::new(input);
+                trx.rate = Some(rate);
+                trx.converted = Some(converted);
+                trx.set_state(state);
+                work.insert_transaction(input.tx, trx);
+
+                Ok(Outcome::Exchanged {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                    rate,
+                    converted,
+                })
+            }
+
+            Type::Authorize => {
+                // if this transaction is already present in the ledger there is an inconsistent behaviour.
+                let state = State::transition(
+                    input.tx,
+                    work.transaction(input.tx)?.map(|trx| trx.state),
+                    input.transaction_type,
+                )?;
+
+                // validate that the amount has a workable value.
+                let Some(amount) = input.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+                self.validate_precision(input.tx, amount, input.currency)?;
+
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .authorize(input.currency, amount)?;
+                work.record(events);
+                let mut trx = Transaction::new(input);
+                trx.set_state(state);
+                work.insert_transaction(input.tx, trx);
+
+                Ok(Outcome::Authorized {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                })
+            }
+
+            Type::Capture => {
+                // find the authorization to be captured.
+                let mut captured_trx = match work.transaction(input.tx)? {
+                    Some(trx) => trx.into_owned(),
+                    None => return Err(EngineError::TrxNotFound { tx: input.tx }),
+                };
+
+                // validate that the retrieved transaction belongs to the same client.
+                if input.client != captured_trx.client {
+                    return Err(EngineError::TrxClientIdInconsistency {
+                        client: input.client,
+                        tx: input.tx,
+                        trx_client: captured_trx.client,
+                    });
+                }
+
+                // validate that the input operates in the currency of the retrieved transaction.
+                Self::validate_currency(input, &captured_trx)?;
+
+                // validate that the transaction is an authorization that was not captured or voided yet.
+                let state =
+                    State::transition(input.tx, Some(captured_trx.state), input.transaction_type)?;
+
+                let Some(authorized) = captured_trx.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+
+                // without an amount the whole authorization is captured.
+                let amount = input.amount.unwrap_or(authorized);
+                self.validate_precision(input.tx, amount, captured_trx.currency)?;
+                if amount > authorized {
+                    return Err(EngineError::CaptureAmountExceeded {
+                        tx: input.tx,
+                        amount,
+                        authorized,
+                    });
+                }
+
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .capture(captured_trx.currency, authorized, amount)?;
+                work.record(events);
+                captured_trx.captured = Some(amount);
+                captured_trx.set_state(state);
+                work.insert_transaction(input.tx, captured_trx);
+
+                Ok(Outcome::Captured {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                    released: authorized - amount,
+                })
+            }
+
+            Type::Void => {
+                // find the authorization to be voided.
+                let mut voided_trx = match work.transaction(input.tx)? {
+                    Some(trx) => trx.into_owned(),
+                    None => return Err(EngineError::TrxNotFound { tx: input.tx }),
+                };
+
+                // validate that the retrieved transaction belongs to the same client.
+                if input.client != voided_trx.client {
+                    return Err(EngineError::TrxClientIdInconsistency {
+                        client: input.client,
+                        tx: input.tx,
+                        trx_client: voided_trx.client,
+                    });
+                }
+
+                // validate that the input operates in the currency of the retrieved transaction.
+                Self::validate_currency(input, &voided_trx)?;
+
+                // validate that the transaction is an authorization that was not captured or voided yet.
+                let state =
+                    State::transition(input.tx, Some(voided_trx.state), input.transaction_type)?;
+
+                let Some(amount) = voided_trx.amount else {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                };
+
+                let events = self
+                    .account_mut(work, input.client, input.transaction_type)?
+                    .void(voided_trx.currency, amount)?;
+                work.record(events);
+                voided_trx.set_state(state);
+                work.insert_transaction(input.tx, voided_trx);
+
+                Ok(Outcome::Voided {
+                    client: input.client,
+                    tx: input.tx,
+                    amount,
+                })
+            }
+
+            Type::Dispute => {
+                // find the transaction to be disputed and if no transaction is found,
+                // assume error from the banking partner. Continue processing the rest of the CSV.
+                let mut disputed_trx = match work.transaction(input.tx)? {
+                    Some(trx) => trx.into_owned(),
+                    None => return Err(EngineError::TrxNotFound { tx: input.tx }),
+                };
+
+                // validate that the retrieved transaction belongs to the same client.
+                if input.client != disputed_trx.client {
+                    return Err(EngineError::TrxClientIdInconsistency {
+                        client: input.client,
+                        tx: input.tx,
+                        trx_client: disputed_trx.client,
+                    });
+                }
+
+                // validate that the input operates in the currency of the retrieved transaction.
+                Self::validate_currency(input, &disputed_trx)?;
+
+                // validate that the transaction to be disputed is a deposit,
+                // or a withdrawal or transfer if the dispute policy allows it.
+                match (disputed_trx.transaction_type, self.config.dispute_policy) {
+                    (Type::Deposit, _) => {}
+                    (Type::Withdrawal | Type::Transfer, DisputePolicy::DepositsAndWithdrawals) => {}
+                    (Type::Withdrawal | Type::Transfer, DisputePolicy::DepositsOnly)
+                    | (
+                        Type::Exchange
+                        | Type::Authorize
+                        | Type::Capture
+                        | Type::Void
+                        | Type::Resolve
+                        | Type::Dispute
+                        | Type::Chargeback
+                        | Type::Unlock
+                        | Type::Freeze
+                        | Type::Unfreeze
+                        | Type::Close,
+                        _,
+                    ) => return Err(EngineError::TrxNotDisputable { tx: input.tx }),
+                }
+
+                // validate that the transaction is still within the dispute window.
+                if let (Some(window), Some(processed_at), Some(disputed_at)) = (
+                    self.config.dispute_window,
+                    disputed_trx.timestamp,
+                    input.timestamp,
+                ) {
+                    if disputed_at > processed_at.saturating_add(window) {
+                        return Err(EngineError::DisputeWindowExpired {
+                            tx: input.tx,
+                            processed_at,
+                            disputed_at,
+                        });
+                    }
+                }
+
+                // validate that the dispute is not already open or if the transaction was chargeback.
+                let dispute = input.dispute_id();
+                let state = disputed_trx.next_dispute_state(dispute, input.transaction_type)?;
+
+                // validate that the amount of the disputed trx has a workable value.
+                if disputed_trx.amount.is_none() {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                }
+
+                // without an amount the dispute claims whatever is not claimed by other disputes.
+                let remaining = disputed_trx.undisputed_amount(dispute);
+                let amount = input.amount.unwrap_or(remaining);
+                self.validate_precision(input.tx, amount, disputed_trx.currency)?;
+                // a dispute of nothing would hold no funds and only block the transaction.
+                if amount.is_zero() {
+                    return Err(EngineError::TrxInvalidAmount { tx: input.tx });
+                }
+                if amount > remaining {
+                    return Err(EngineError::DisputeAmountExceeded {
+                        tx: input.tx,
+                        amount,
+                        remaining,
+                    });
+                }
+
+                let events = match disputed_trx.transaction_type {
+                    Type::Withdrawal => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .dispute_withdrawal(disputed_trx.currency, amount)?,
+                    // the transferred funds are held on the account that received them.
+                    Type::Transfer => Self::destination_mut(work, &disputed_trx)?
+                        .dispute(disputed_trx.currency, amount)?,
+                    _ => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .dispute(disputed_trx.currency, amount)?,
+                };
+                work.record(events);
+                // mark the dispute as open.
+                disputed_trx.set_dispute(
+                    dispute,
+                    Dispute {
+                        amount,
+                        state,
+                        opened_at: input.timestamp,
+                    },
+                );
+                work.insert_transaction(input.tx, disputed_trx);
+
+                Ok(Outcome::DisputeOpened {
+                    client: input.client,
+                    tx: input.tx,
+                    dispute,
+                    amount,
+                })
+            }
+
+            Type::Resolve => {
+                // find the transaction to be resolved and if no transaction is found,
+                // assume error from the banking partner. Continue processing the rest of the CSV.
+                let mut resolved_trx = match work.transaction(input.tx)? {
+                    Some(trx) => trx.into_owned(),
+                    None => return Err(EngineError::TrxNotFound { tx: input.tx }),
+                };
+
+                // validate that the dispute to be resolved is open.
+                let dispute = input.dispute_id();
+                let state = resolved_trx.next_dispute_state(dispute, input.transaction_type)?;
+
+                // validate that the retrieved transaction belongs to the same client.
+                if input.client != resolved_trx.client {
+                    return Err(EngineError::TrxClientIdInconsistency {
+                        client: input.client,
+                        tx: input.tx,
+                        trx_client: resolved_trx.client,
+                    });
+                }
+
+                // validate that the input operates in the currency of the retrieved transaction.
+                Self::validate_currency(input, &resolved_trx)?;
+
+                // only the amount claimed by the dispute is released.
+                let Some(open) = resolved_trx.dispute(dispute).cloned() else {
+                    return Err(EngineError::TrxNotInDispute { tx: input.tx });
+                };
+                let amount = open.amount;
+
+                let events = match resolved_trx.transaction_type {
+                    Type::Withdrawal => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .resolve_withdrawal(resolved_trx.currency, amount)?,
+                    Type::Transfer => Self::destination_mut(work, &resolved_trx)?
+                        .resolve(resolved_trx.currency, amount)?,
+                    _ => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .resolve(resolved_trx.currency, amount)?,
+                };
+                work.record(events);
+                // mark dispute as resolved.
+                resolved_trx.set_dispute(dispute, Dispute { state, ..open });
+                work.insert_transaction(input.tx, resolved_trx);
+
+                Ok(Outcome::DisputeResolved {
+                    client: input.client,
+                    tx: input.tx,
+                    dispute,
+                    amount,
+                })
+            }
+
+            Type::Chargeback => {
+                // find the transaction to be chargeback and if no transaction is found,
+                // assume error from the banking partner. Continue processing the rest of the CSV.
+                let mut chargeback_trx = match work.transaction(input.tx)? {
+                    Some(trx) => trx.into_owned(),
+                    None => return Err(EngineError::TrxNotFound { tx: input.tx }),
+                };
+
+                // validate that the retrieved transaction belongs to the same client.
+                if input.client != chargeback_trx.client {
+                    return Err(EngineError::TrxClientIdInconsistency {
+                        client: input.client,
+                        tx: input.tx,
+                        trx_client: chargeback_trx.client,
+                    });
+                }
+
+                // validate that the input operates in the currency of the retrieved transaction.
+                Self::validate_currency(input, &chargeback_trx)?;
+
+                // validate that the dispute to be chargeback is open.
+                let dispute = input.dispute_id();
+                let state = chargeback_trx.next_dispute_state(dispute, input.transaction_type)?;
+
+                // only the amount claimed by the dispute is chargeback.
+                let Some(open) = chargeback_trx.dispute(dispute).cloned() else {
+                    return Err(EngineError::TrxNotInDispute { tx: input.tx });
+                };
+                let amount = open.amount;
+
+                // the account charged back is the one that received the funds.
+                let charged = match chargeback_trx.transaction_type {
+                    Type::Transfer => chargeback_trx
+                        .destination
+                        .ok_or(EngineError::TrxInvalidDestination { tx: input.tx })?,
+                    _ => input.client,
+                };
+
+                // the fee is charged to the account that is charged back, before it is locked.
+                if let Some(fee) = self.fee(
+                    charged,
+                    input.transaction_type,
+                    chargeback_trx.currency,
+                    amount,
+                )? {
+                    self.post_fee(work, input, charged, chargeback_trx.currency, fee)?;
+                }
+
+                // perform the necessary calculations for chargeback and lock account.
+                let events = match chargeback_trx.transaction_type {
+                    Type::Withdrawal => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .chargeback_withdrawal(chargeback_trx.currency, amount)?,
+                    // the transfer is reversed: the held funds leave the account that received them,
+                    // which is locked, and are credited back to the client that sent them.
+                    Type::Transfer => {
+                        let mut events = Self::destination_mut(work, &chargeback_trx)?
+                            .chargeback(chargeback_trx.currency, amount)?;
+                        events.extend(
+                            self.account_mut(work, input.client, input.transaction_type)?
+                                .deposit(chargeback_trx.currency, amount)?,
+                        );
+                        events
+                    }
+                    _ => self
+                        .account_mut(work, input.client, input.transaction_type)?
+                        .chargeback(chargeback_trx.currency, amount)?,
+                };
+                work.record(events);
+
+                // chargebacks are not initiated by the client, so they are applied even if they take
+                // the account over its credit limit. The account is flagged in the report instead.
+                if let Some(account) = work.account_mut(charged)? {
+                    if account.over_limit(chargeback_trx.currency) {
+                        warn!(
+                            "chargeback took the account over its credit limit (client: {}, tx: {})",
+                            charged, input.tx
+                        );
+                    }
+                }
+
+                // mark dispute, and therefore the transaction, as chargeback.
+                chargeback_trx.set_dispute(dispute, Dispute { state, ..open });
+                work.insert_transaction(input.tx, chargeback_trx);
+
+                Ok(Outcome::ChargedBack {
+                    client: input.client,
+                    tx: input.tx,
+                    dispute,
+                    amount,
+                })
+            }
+
+            Type::Unlock => self.change_status(work, input, Account::unlock),
+            Type::Freeze => self.change_status(work, input, Account::freeze),
+            Type::Unfreeze => self.change_status(work, input, Account::unfreeze),
+            Type::Close => self.change_status(work, input, Account::close),
+        }
+    }
+
+    /// Fee of the provided transaction, rounded to the precision of its currency,
+    /// or `None` if the fee schedule does not charge it.
+    /// # Errors
+    /// * An error is returned if the fee overflows.
+    ///
+    fn fee(
+        &self,
+        client: u16,
+        transaction_type: Type,
+        currency: Option<Currency>,
+        amount: Decimal,
+    ) -> Result<Option<Decimal>, EngineError> {
+        let Some(rule) = self
+            .config
+            .fees
+            .as_ref()
+            .and_then(|fees| fees.rule(transaction_type))
+        else {
+            return Ok(None);
+        };
+        let fee = rule
+            .compute(amount)
+            .ok_or(EngineError::Overflow { client, amount })?;
+        let fee = self.config.precision.rounding(currency).round(fee);
+
+        Ok((!fee.is_zero()).then_some(fee))
+    }
+
+    /// Posts the fee of the input: debited from the account of `client` as part of the input,
+    /// and credited to the house account.
+    fn post_fee(
+        &self,
+        work: &mut UnitOfWork,
+        input: &Input,
+        client: u16,
+        currency: Option<Currency>,
+        fee: Decimal,
+    ) -> Result<(), EngineError> {
+        let Some(schedule) = &self.config.fees else {
+            return Ok(());
+        };
+
+        let events = work
+            .account_mut(client)?
+            .ok_or(EngineError::AccountNotFound { client })?
+            .charge_fee(currency, fee, input.transaction_type)?;
+        work.record(events);
+        let events = work
+            .open_account_mut(schedule.house_account)?
+            .deposit(currency, fee)?;
+        work.record(events);
+        work.post_fee(FeeTransaction {
+            tx: input.tx,
+            transaction_type: input.transaction_type,
+            client,
+            house_account: schedule.house_account,
+            currency,
+            amount: fee,
+        });
+
+        Ok(())
+    }
+
+    /// Applies an administrative operation to the account of the client.
+    /// Administrative operations are stored like any other transaction, so their ids stay unique,
+    /// and never open an account whatever the account creation policy.
+    fn change_status(
+        &self,
+        work: &mut UnitOfWork,
+        input: &Input,
+        change: fn(&mut Account) -> Result<Vec<Event>, EngineError>,
+    ) -> Result<Outcome, EngineError> {
+        let state = State::transition(
+            input.tx,
+            work.transaction(input.tx)?.map(|trx| trx.state),
+            input.transaction_type,
+        )?;
+
+        let Some(account) = work.account_mut(input.client)? else {
+            return Err(EngineError::AccountNotFound {
+                client: input.client,
+            });
+        };
+        let events = change(account)?;
+        let status = account.status;
+        work.record(events);
+
+        let mut trx = Transaction::new(input);
+        trx.set_state(state);
+        work.insert_transaction(input.tx, trx);
+
+        Ok(Outcome::StatusChanged {
+            client: input.client,
+            tx: input.tx,
+            status,
+        })
+    }
+}
+
+/// Folds the events of the provided store into the projection, in the order they were recorded.
+fn project<P: Projection>(store: &dyn EventStore, projection: &mut P) -> io::Result<()> {
+    for record in store.events() {
+        let record = record?;
+        projection
+            .apply(&record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trx_engine::account::Balance;
+    use crate::trx_engine::account_store::FileAccountStore;
+    use crate::trx_engine::fees::{Fee, FeeRule};
+    use crate::trx_engine::journal;
+    use crate::trx_engine::journal::{FileJournal, SyncPolicy};
+    use crate::trx_engine::rounding::{Rounding, RoundingMode};
+    use crate::trx_engine::transaction::DEFAULT_DISPUTE;
+    use rust_decimal::prelude::ToPrimitive;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::fs::File;
+
+    /// helper func to provide an input fixture to use in the tests
+    fn input(trx_type: Type, client: u16, tx: u32, amount: Option<Decimal>) -> Input {
+        Input {
+            transaction_type: trx_type,
+            client,
+            tx,
+            amount,
+            dispute: None,
+            destination: None,
+            timestamp: None,
+            currency: None,
+            target_currency: None,
+            rate: None,
+        }
+    }
+
+    #[test]
+    fn process_trx_successful_deposit_dispute_chargeback() {
+        let client_id = 0;
+        let tx_id = 1;
+        let amount = dec!(1500);
+        let amount_not_disputed = dec!(500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, tx_id, Some(amount)),
+            input(Type::Deposit, client_id, 2, Some(amount_not_disputed)),
+            input(Type::Dispute, client_id, tx_id, None),
+            input(Type::Chargeback, client_id, tx_id, None),
+        ];
+
+        let mut ledger = Ledger::new();
+
+        for t in trxs.into_iter() {
+            ledger
+                .process_trx(&t)
+                .expect("failed to process transaction");
+        }
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.balance(None).available, amount_not_disputed);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, amount_not_disputed);
+        assert!(account.locked());
+
+        let trx = ledger.transaction(tx_id).expect("transaction not found");
+        assert_eq!(trx.state, State::Chargeback);
+    }
+
+    #[test]
+    fn process_trx_chargeback_fail_when_trx_was_resolved() {
+        let client_id = 0;
+        let tx_id = 1;
+        let amount = dec!(1500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, tx_id, Some(amount)),
+            input(Type::Dispute, client_id, tx_id, None),
+            input(Type::Resolve, client_id, tx_id, None),
+        ];
+
+        let mut ledger = Ledger::new();
+
+        for t in trxs.into_iter() {
+            ledger
+                .process_trx(&t)
+                .expect("failed to process transaction");
+        }
+
+        let result = ledger.process_trx(&input(Type::Chargeback, client_id, tx_id, None));
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDispute { tx: tx_id }
+        );
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, amount);
+        assert!(!account.locked());
+
+        let trx = ledger.transaction(tx_id).expect("transaction not found");
+        assert_eq!(trx.state, State::Ok);
+    }
+
+    #[test]
+    fn process_trx_chargeback_fail_when_trx_not_in_dispute() {
+        let client_id = 0;
+        let tx_id = 1;
+        let amount = dec!(1500);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input(Type::Deposit, client_id, tx_id, Some(amount)));
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input(Type::Chargeback, client_id, tx_id, None));
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDispute { tx: tx_id }
+        );
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, amount);
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_trx_already_in_dispute() {
+        let client_id = 0;
+        let tx_id = 1;
+        let amount = dec!(1500);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input(Type::Deposit, client_id, tx_id, Some(amount)));
+        assert!(result.is_ok());
+        let result = ledger.process_trx(&input(Type::Dispute, client_id, tx_id, None));
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input(Type::Dispute, client_id, tx_id, None));
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxAlreadyInDispute { tx: tx_id }
+        );
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, amount);
+        assert_eq!(account.balance(None).total, amount);
+    }
+
+    /// helper func to provide a ledger that accepts withdrawal disputes
+    fn ledger_with_withdrawal_disputes() -> Ledger {
+        Ledger::with_config(LedgerConfig {
+            dispute_policy: DisputePolicy::DepositsAndWithdrawals,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn process_trx_successful_withdrawal_dispute_chargeback() {
+        let client_id = 0;
+        let tx_id = 2;
+        let amount_deposit = dec!(1500);
+        let amount_withdrawal = dec!(500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, 1, Some(amount_deposit)),
+            input(Type::Withdrawal, client_id, tx_id, Some(amount_withdrawal)),
+            input(Type::Dispute, client_id, tx_id, None),
+        ];
+
+        let mut ledger = ledger_with_withdrawal_disputes();
+
+        for t in trxs.into_iter() {
+            ledger
+                .process_trx(&t)
+                .expect("failed to process transaction");
+        }
+
+        // the disputed withdrawal is provisionally credited as held funds.
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(
+            account.balance(None).available,
+            amount_deposit - amount_withdrawal
+        );
+        assert_eq!(account.balance(None).held, amount_withdrawal);
+        assert_eq!(account.balance(None).total, amount_deposit);
+        assert!(!account.locked());
+
+        let result = ledger.process_trx(&input(Type::Chargeback, client_id, tx_id, None));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::ChargedBack {
+                client: client_id,
+                tx: tx_id,
+                dispute: DEFAULT_DISPUTE,
+                amount: amount_withdrawal
+            }
+        );
+
+        // the withdrawal is reversed and the account locked.
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.balance(None).available, amount_deposit);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, amount_deposit);
+        assert!(account.locked());
+
+        let trx = ledger.transaction(tx_id).expect("transaction not found");
+        assert_eq!(trx.state, State::Chargeback);
+    }
+
+    #[test]
+    fn process_trx_successful_withdrawal_dispute_resolve() {
+        let client_id = 0;
+        let tx_id = 2;
+        let amount_deposit = dec!(1500);
+        let amount_withdrawal = dec!(500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, 1, Some(amount_deposit)),
+            input(Type::Withdrawal, client_id, tx_id, Some(amount_withdrawal)),
+            input(Type::Dispute, client_id, tx_id, None),
+            input(Type::Resolve, client_id, tx_id, None),
+            input(Type::Withdrawal, client_id, 3, Some(amount_withdrawal)),
+        ];
+
+        let mut ledger = ledger_with_withdrawal_disputes();
+
+        for t in trxs.into_iter() {
+            ledger
+                .process_trx(&t)
+                .expect("failed to process transaction");
+        }
+
+        // the withdrawal stands and the provisional credit is removed.
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(500));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(500));
+        assert!(!account.locked());
+
+        let trx = ledger.transaction(tx_id).expect("transaction not found");
+        assert_eq!(trx.state, State::Ok);
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_withdrawal_and_policy_is_deposits_only() {
+        let client_id = 0;
+        let tx_id = 2;
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input(Type::Deposit, client_id, 1, Some(dec!(1500))));
+        assert!(result.is_ok());
+        let result =
+            ledger.process_trx(&input(Type::Withdrawal, client_id, tx_id, Some(dec!(500))));
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input(Type::Dispute, client_id, tx_id, None));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotDisputable { tx: tx_id }
+        );
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(1000));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(1000));
+    }
+
+    #[test]
+    fn process_trx_successful_after_dispute_resolve() {
+        let client_id = 0;
+        let tx = 1;
+        let amount_deposit = dec!(1500);
+        let amount_withdrawal = dec!(500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, tx, Some(amount_deposit)),
+            input(Type::Withdrawal, client_id, 2, Some(amount_withdrawal)),
+            input(Type::Dispute, client_id, tx, None),
+            input(Type::Resolve, client_id, tx, None),
+            input(Type::Deposit, client_id, 3, Some(amount_deposit)),
+            input(Type::Withdrawal, client_id, 4, Some(amount_withdrawal)),
+        ];
+
+        let mut ledger = Ledger::new();
+
+        for trx in trxs.into_iter() {
+            _ = ledger.process_trx(&trx)
+        }
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.balance(None).available, dec!(2000));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(2000));
+        assert!(!account.locked());
+
+        assert_eq!(4, ledger.trx.len());
+        let trx1 = ledger.transaction(tx).expect("transaction 1 not found");
+        assert_eq!(trx1.amount, Some(amount_deposit));
+        assert_eq!(trx1.state, State::Ok);
+        assert_eq!(trx1.client, client_id);
+
+        let trx2 = ledger.transaction(2).expect("transaction 2 not found");
+        assert_eq!(trx2.amount, Some(amount_withdrawal));
+        assert_eq!(trx2.state, State::Ok);
+        assert_eq!(trx2.client, client_id);
+
+        let trx3 = ledger.transaction(3).expect("transaction 3 not found");
+        assert_eq!(trx3.amount, Some(amount_deposit));
+        assert_eq!(trx3.state, State::Ok);
+        assert_eq!(trx3.client, client_id);
+
+        let trx4 = ledger.transaction(4).expect("transaction 4 not found");
+        assert_eq!(trx4.amount, Some(amount_withdrawal));
+        assert_eq!(trx4.state, State::Ok);
+        assert_eq!(trx4.client, client_id);
+    }
+
+    #[test]
+    fn process_trx_ignore_all_operations_after_chargeback() {
+        let client_id = 0;
+        let tx_id = 1;
+        let amount = dec!(1500);
+
+        let trxs = vec![
+            input(Type::Deposit, client_id, tx_id, Some(amount)),
+            input(Type::Dispute, client_id, tx_id, None),
+            input(Type::Chargeback, client_id, tx_id, None),
+            input(Type::Deposit, client_id, 2, Some(amount)),
+            input(Type::Withdrawal, client_id, 3, Some(amount)),
+            input(Type::Dispute, client_id, 4, None),
+            input(Type::Resolve, client_id, 5, None),
+        ];
+
+        let mut ledger = Ledger::new();
+
+        for t in trxs.into_iter() {
+            _ = ledger.process_trx(&t)
+        }
+
+        let account = ledger.account(client_id).expect("account not found");
+        assert_eq!(account.client, client_id);
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(account.locked());
+
+        assert_eq!(1, ledger.trx.len());
+        let trx = ledger.transaction(tx_id).expect("transaction not found");
+        assert_eq!(trx.amount, Some(amount));
+        assert_eq!(trx.state, State::Chargeback);
+        assert_eq!(trx.client, client_id);
+    }
+
+    #[test]
+    fn process_trx_deposit_fail_when_trx_already_processed() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = Some(dec!(1500));
+        let input = input(Type::Deposit, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        // validate that preconditions are verified
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client).is_some());
+
+        // repeat the same transaction to assert the expected behaviour
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxAlreadyProcessed { tx });
+    }
+
+    #[test]
+    fn process_trx_deposit_fail_when_amount_is_not_present() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = None;
+        let input = input(Type::Deposit, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxInvalidAmount { tx });
+    }
+
+    #[test]
+    fn process_trx_deposit_fail_when_account_method_returns_error() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = Some(dec!(-1500));
+        let input = input(Type::Deposit, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client,
+                amount: dec!(-1500)
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_deposit_fail_when_balance_overflows() {
+        let client = 1234;
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input(Type::Deposit, client, 1, Some(Decimal::MAX)));
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input(Type::Deposit, client, 2, Some(dec!(1))));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client,
+                amount: dec!(1)
+            }
+        );
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, Decimal::MAX);
+        assert_eq!(account.balance(None).total, Decimal::MAX);
+        assert!(ledger.transaction(2).is_none());
+    }
+
+    #[test]
+    fn process_trx_rejected_input_leaves_ledger_untouched() {
+        let client = 1234;
+        let amount = dec!(1500);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input(Type::Deposit, client, 1, Some(amount)));
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(2000))));
+        assert!(result.is_err());
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, amount);
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, amount);
+        assert_eq!(1, ledger.trx.len());
+        assert!(ledger.transaction(2).is_none());
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_trx_already_processed() {
+        let tx_deposit = 123;
+        let tx = 123456789;
+        let client = 1234;
+        let amount = Some(dec!(1500));
+        let input_deposit = input(Type::Deposit, client, tx_deposit, amount);
+        let input_withdrawal = input(Type::Withdrawal, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        // load balance in the account, so it can be withdrawal
+        let result = ledger.process_trx(&input_deposit);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx_deposit).is_some());
+        assert!(ledger.account(client).is_some());
+
+        let result = ledger.process_trx(&input_withdrawal);
+        // validate that preconditions are verified (withdrawal was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client).is_some());
+
+        // repeat the same transaction to assert the expected behaviour
+        let result = ledger.process_trx(&input_withdrawal);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxAlreadyProcessed { tx });
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_amount_is_not_present() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = None;
+        let input = input(Type::Withdrawal, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxInvalidAmount { tx });
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_account_method_returns_error() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = Some(dec!(-1500));
+        let input_deposit = input(Type::Deposit, client, 1, Some(dec!(1500)));
+        let input = input(Type::Withdrawal, client, tx, amount);
+
+        let mut ledger = Ledger::new();
+        // open the account, so the withdrawal reaches the account method
+        let result = ledger.process_trx(&input_deposit);
+        assert!(result.is_ok());
+
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::NegativeAmount {
+                client,
+                amount: dec!(-1500)
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_account_not_found() {
+        let client = 1234;
+        let inputs = [
+            input(Type::Withdrawal, client, 1, Some(dec!(1500))),
+            input(Type::Withdrawal, client, 2, Some(dec!(0))),
+        ];
+
+        let mut ledger = Ledger::new();
+        for input in inputs.iter() {
+            let result = ledger.process_trx(input);
+
+            assert_eq!(result.unwrap_err(), EngineError::AccountNotFound { client });
+        }
+
+        assert!(ledger.accounts().next().is_none());
+        assert!(ledger.trx.is_empty());
+    }
+
+    #[test]
+    fn process_trx_withdrawal_opens_account_when_policy_allows_any_transaction() {
+        let client = 1234;
+        let config = LedgerConfig {
+            account_creation: AccountCreation::OnAnyTransaction,
+            ..Default::default()
+        };
+
+        let mut ledger = Ledger::with_config(config);
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 1, Some(dec!(1500))));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(0),
+                amount: dec!(1500)
+            }
+        );
+        // rejected inputs never open an account, regardless of the policy.
+        assert!(ledger.accounts().next().is_none());
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(0))));
+        assert!(result.is_ok());
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).total, dec!(0));
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_disputed_trx_not_found() {
+        let tx = 123456789;
+        let client = 1234;
+        let input = input(Type::Dispute, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotFound { tx });
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_disputed_trx_belongs_to_another_user() {
+        let tx = 123456789;
+        let client_deposit = 1;
+        let client = 1234;
+        let amount = Some(dec!(1500));
+        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
+        let input_dispute = input(Type::Dispute, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        // load balance in the account, so it can be disputed
+        let result = ledger.process_trx(&input_deposit);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        let result = ledger.process_trx(&input_dispute);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxClientIdInconsistency {
+                client,
+                tx,
+                trx_client: client_deposit
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_transaction_type_not_valid() {
+        let invalid_inputs = [
+            input(Type::Withdrawal, 0, 0, Some(dec!(10.0))),
+            input(Type::Dispute, 0, 1, None),
+            input(Type::Resolve, 0, 2, None),
+            input(Type::Chargeback, 0, 3, None),
+        ];
+
+        for (index, invalid_input) in invalid_inputs.iter().enumerate() {
+            let index = index
+                .to_u32()
+                .expect("error while converting from usize to u32");
+
+            // build pre-conditions but inserting a erroneous transaction in the ledger
+            let trx = Transaction::new(invalid_input);
+            let mut ledger = Ledger::new();
+            ledger.trx.insert(trx).expect("failed to insert");
+
+            let input_dispute = input(Type::Dispute, 0, index, None);
+            let result = ledger.process_trx(&input_dispute);
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                EngineError::TrxNotDisputable { tx: index }
+            );
+        }
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_transaction_to_be_disputed_is_not_in_a_disputable_state() {
+        let input_invalid = input(Type::Deposit, 0, 1, None);
+        let input_dispute = input(Type::Dispute, 0, 1, None);
+
+        // build pre-conditions but inserting a erroneous transaction in the ledger
+        let mut trx = Transaction::new(&input_invalid);
+        trx.state = State::Chargeback;
+
+        let mut ledger = Ledger::new();
+        ledger.trx.insert(trx).expect("failed to insert");
+
+        let result = ledger.process_trx(&input_dispute);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDisputableState { tx: 1 }
+        );
+    }
+    #[test]
+    fn process_trx_resolve_fail_when_disputed_trx_not_found() {
+        let tx = 123456789;
+        let client = 1234;
+        let input = input(Type::Resolve, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotFound { tx });
+    }
+
+    #[test]
+    fn process_trx_resolve_fail_when_resolved_trx_not_in_dispute_state() {
+        let tx = 123456789;
+        let client = 1234;
+        let amount = Some(dec!(1500));
+        let input_deposit = input(Type::Deposit, client, tx, amount);
+        let input_resolve = input(Type::Resolve, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        // load balance in the account, so it can be disputed
+        let result = ledger.process_trx(&input_deposit);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client).is_some());
+
+        let result = ledger.process_trx(&input_resolve);
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotInDispute { tx });
+    }
+
+    #[test]
+    fn process_trx_resolve_fail_when_resolved_trx_belongs_to_another_user() {
+        let tx = 123456789;
+        let client = 1234;
+        let client_deposit = 1;
+        let amount = Some(dec!(1500));
+        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
+        let input_dispute = input(Type::Dispute, client_deposit, tx, None);
+        let input_resolve = input(Type::Resolve, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        // load balance in the account, so it can be disputed
+        let result = ledger.process_trx(&input_deposit);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        // dispute transaction, so it can be resolved
+        let result = ledger.process_trx(&input_dispute);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        let result = ledger.process_trx(&input_resolve);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxClientIdInconsistency {
+                client,
+                tx,
+                trx_client: client_deposit
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_chargeback_fail_when_disputed_trx_not_found() {
+        let tx = 123456789;
+        let client = 1234;
+        let input = input(Type::Chargeback, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        let result = ledger.process_trx(&input);
+
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotFound { tx });
+    }
+
+    #[test]
+    fn process_trx_chargeback_fail_when_chargeback_trx_belongs_to_another_user() {
+        let tx = 123456789;
+        let client = 1234;
+        let client_deposit = 1;
+        let amount = Some(dec!(1500));
+        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
+        let input_dispute = input(Type::Dispute, client_deposit, tx, None);
+        let input_resolve = input(Type::Resolve, client_deposit, tx, None);
+        let input_chargeback = input(Type::Chargeback, client, tx, None);
+
+        let mut ledger = Ledger::new();
+        // load balance in the account, so it can be disputed
+        let result = ledger.process_trx(&input_deposit);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        // dispute transaction, so it can be resolved
+        let result = ledger.process_trx(&input_dispute);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        // resolve transaction, so it can be chargeback
+        let result = ledger.process_trx(&input_resolve);
+        // validate that preconditions are verified (deposit was successful)
+        assert!(result.is_ok());
+        assert!(ledger.transaction(tx).is_some());
+        assert!(ledger.account(client_deposit).is_some());
+
+        let result = ledger.process_trx(&input_chargeback);
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxClientIdInconsistency {
+                client,
+                tx,
+                trx_client: client_deposit
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_partial_disputes_are_settled_independently() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+
+        // two partial disputes over the same deposit.
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 1, dec!(30)));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::DisputeOpened {
+                client,
+                tx,
+                dispute: 1,
+                amount: dec!(30)
+            }
+        );
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 2, dec!(50)))
+            .expect("failed to dispute");
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(20));
+        assert_eq!(account.balance(None).held, dec!(80));
+        assert_eq!(ledger.transaction(tx).unwrap().state, State::Disputed);
+
+        // resolving the first dispute only releases its own amount.
+        let result = ledger.process_trx(&Input::resolve(client, tx).with_dispute(1));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::DisputeResolved {
+                client,
+                tx,
+                dispute: 1,
+                amount: dec!(30)
+            }
+        );
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(50));
+        assert_eq!(account.balance(None).held, dec!(50));
+        assert_eq!(ledger.transaction(tx).unwrap().state, State::Disputed);
+
+        // the chargeback of the second dispute only reverses its own amount.
+        let result = ledger.process_trx(&Input::chargeback(client, tx).with_dispute(2));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::ChargedBack {
+                client,
+                tx,
+                dispute: 2,
+                amount: dec!(50)
+            }
+        );
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(50));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(50));
+        assert!(account.locked());
+        assert_eq!(ledger.transaction(tx).unwrap().state, State::Chargeback);
+    }
+
+    #[test]
+    fn process_trx_open_disputes_are_settled_after_chargeback() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 1, dec!(30)))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 2, dec!(20)))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&Input::chargeback(client, tx).with_dispute(2))
+            .expect("failed to chargeback");
+        ledger
+            .process_trx(&input(Type::Unlock, client, 2, None))
+            .expect("failed to unlock");
+
+        // the dispute still open on the chargeback transaction can be resolved.
+        let result = ledger.process_trx(&Input::resolve(client, tx).with_dispute(1));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::DisputeResolved {
+                client,
+                tx,
+                dispute: 1,
+                amount: dec!(30)
+            }
+        );
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(80));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(80));
+        assert_eq!(ledger.transaction(tx).unwrap().state, State::Chargeback);
+
+        // no dispute can be opened again, not even the resolved one.
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 1, dec!(10)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDisputableState { tx }
+        );
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 3, dec!(10)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDisputableState { tx }
+        );
+    }
+
+    #[test]
+    fn process_trx_dispute_without_amount_claims_the_undisputed_amount() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 1, dec!(30)))
+            .expect("failed to dispute");
+
+        let result = ledger.process_trx(&Input::dispute(client, tx).with_dispute(2));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::DisputeOpened {
+                client,
+                tx,
+                dispute: 2,
+                amount: dec!(70)
+            }
+        );
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(100));
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_amount_exceeds_undisputed_amount() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 1, dec!(80)))
+            .expect("failed to dispute");
+
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 2, dec!(30)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::DisputeAmountExceeded {
+                tx,
+                amount: dec!(30),
+                remaining: dec!(20)
+            }
+        );
+
+        // the rejected dispute leaves the account untouched.
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(20));
+        assert_eq!(account.balance(None).held, dec!(80));
+    }
+
+    #[test]
+    fn process_trx_partial_dispute_fail_when_amount_is_zero() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 1, dec!(0)));
+        assert_eq!(result.unwrap_err(), EngineError::TrxInvalidAmount { tx });
+
+        // nothing is left to dispute once the whole amount is claimed, so a dispute without amount is rejected too.
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 2, dec!(100)))
+            .expect("failed to dispute");
+        let result = ledger.process_trx(&Input::dispute(client, tx).with_dispute(3));
+        assert_eq!(result.unwrap_err(), EngineError::TrxInvalidAmount { tx });
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(100));
+        assert!(ledger.transaction(tx).unwrap().dispute(1).is_none());
+    }
+
+    #[test]
+    fn process_trx_partial_dispute_fail_when_dispute_id_already_open() {
+        let client = 1;
+        let tx = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, tx, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::partial_dispute(client, tx, 1, dec!(10)))
+            .expect("failed to dispute");
+
+        let result = ledger.process_trx(&Input::partial_dispute(client, tx, 1, dec!(10)));
+        assert_eq!(result.unwrap_err(), EngineError::TrxAlreadyInDispute { tx });
+
+        // a dispute id that was never opened cannot be resolved.
+        let result = ledger.process_trx(&Input::resolve(client, tx).with_dispute(2));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotInDispute { tx });
+    }
+
+    #[test]
+    fn process_trx_unlock_after_chargeback() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&Input::chargeback(client, 1))
+            .expect("failed to chargeback");
+
+        let result = ledger.process_trx(&input(Type::Unlock, client, 2, None));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::StatusChanged {
+                client,
+                tx: 2,
+                status: AccountStatus::Active
+            }
+        );
+
+        ledger
+            .process_trx(&Input::deposit(client, 3, dec!(5)))
+            .expect("failed to deposit");
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).total, dec!(5));
+        assert!(!account.locked());
+    }
+
+    #[test]
+    fn process_trx_frozen_account_still_accepts_chargebacks() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&input(Type::Freeze, client, 2, None))
+            .expect("failed to freeze");
+
+        let result = ledger.process_trx(&Input::deposit(client, 3, dec!(5)));
+        assert_eq!(result.unwrap_err(), EngineError::AccountFrozen { client });
+
+        ledger
+            .process_trx(&Input::chargeback(client, 1))
+            .expect("failed to chargeback");
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert_eq!(account.status(), AccountStatus::Locked);
+    }
+
+    #[test]
+    fn process_trx_close_fail_when_account_holds_funds() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Close, client, 2, None));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotEmpty {
+                client,
+                held: dec!(0),
+                total: dec!(10)
+            }
+        );
+        // the rejected operation is not stored, so its id can be reused.
+        assert!(ledger.transaction(2).is_none());
+
+        ledger
+            .process_trx(&Input::withdrawal(client, 3, dec!(10)))
+            .expect("failed to withdrawal");
+        let result = ledger.process_trx(&input(Type::Close, client, 2, None));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::StatusChanged {
+                client,
+                tx: 2,
+                status: AccountStatus::Closed
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_administrative_fail_when_account_not_found() {
+        let config = LedgerConfig {
+            account_creation: AccountCreation::OnAnyTransaction,
+            ..Default::default()
+        };
+        let mut ledger = Ledger::with_config(config);
+
+        let result = ledger.process_trx(&input(Type::Freeze, 1, 1, None));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotFound { client: 1 }
+        );
+        assert!(ledger.account(1).is_none());
+    }
+
+    #[test]
+    fn process_trx_administrative_fail_when_trx_already_processed() {
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(10)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Freeze, 1, 1, None));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxAlreadyProcessed { tx: 1 }
+        );
+        assert_eq!(ledger.account(1).unwrap().status(), AccountStatus::Active);
+    }
+
+    #[test]
+    fn process_trx_successful_transfer() {
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::transfer(1, 2, 3, dec!(40)));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::Transferred {
+                client: 1,
+                destination: 2,
+                tx: 3,
+                amount: dec!(40)
+            }
+        );
+
+        assert_eq!(ledger.account(1).unwrap().balance(None).total, dec!(60));
+        assert_eq!(ledger.account(2).unwrap().balance(None).total, dec!(50));
+        assert_eq!(ledger.transaction(3).unwrap().destination(), Some(2));
+    }
+
+    #[test]
+    fn process_trx_transfer_fail_leaves_both_accounts_untouched() {
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&input(Type::Freeze, 2, 3, None))
+            .expect("failed to freeze");
+
+        // the withdrawal from the source succeeds but the destination is frozen.
+        let result = ledger.process_trx(&Input::transfer(1, 2, 4, dec!(40)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountFrozen { client: 2 }
+        );
+        assert_eq!(
+            ledger.account(1).unwrap().balance(None).available,
+            dec!(100)
+        );
+        assert_eq!(ledger.account(2).unwrap().balance(None).available, dec!(10));
+        assert!(ledger.transaction(4).is_none());
+
+        // insufficient funds on the source.
+        let result = ledger.process_trx(&Input::transfer(1, 3, 5, dec!(400)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client: 1,
+                available: dec!(100),
+                amount: dec!(400)
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_transfer_fail_when_destination_is_invalid() {
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::transfer(1, 1, 2, dec!(40)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxInvalidDestination { tx: 2 }
+        );
+
+        let result = ledger.process_trx(&input(Type::Transfer, 1, 2, Some(dec!(40))));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxInvalidDestination { tx: 2 }
+        );
+
+        // the destination must already have an account.
+        let result = ledger.process_trx(&Input::transfer(1, 2, 2, dec!(40)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AccountNotFound { client: 2 }
+        );
+        assert_eq!(
+            ledger.account(1).unwrap().balance(None).available,
+            dec!(100)
+        );
+    }
+
+    #[test]
+    fn process_trx_transfer_dispute_fail_when_policy_is_deposits_only() {
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::transfer(1, 2, 3, dec!(40)))
+            .expect("failed to transfer");
+
+        let result = ledger.process_trx(&Input::dispute(1, 3));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotDisputable { tx: 3 });
+    }
+
+    #[test]
+    fn process_trx_successful_transfer_dispute_resolve() {
+        let mut ledger = ledger_with_withdrawal_disputes();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::transfer(1, 2, 3, dec!(40)))
+            .expect("failed to transfer");
+
+        // only the source client can dispute the transfer.
+        let result = ledger.process_trx(&Input::dispute(2, 3));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxClientIdInconsistency {
+                client: 2,
+                tx: 3,
+                trx_client: 1
+            }
+        );
+
+        ledger
+            .process_trx(&Input::dispute(1, 3))
+            .expect("failed to dispute");
+        let destination = ledger.account(2).unwrap();
+        assert_eq!(destination.balance(None).available, dec!(10));
+        assert_eq!(destination.balance(None).held, dec!(40));
+        assert_eq!(destination.balance(None).total, dec!(50));
+        assert_eq!(ledger.account(1).unwrap().balance(None).total, dec!(60));
+
+        ledger
+            .process_trx(&Input::resolve(1, 3))
+            .expect("failed to resolve");
+        let destination = ledger.account(2).unwrap();
+        assert_eq!(destination.balance(None).available, dec!(50));
+        assert_eq!(destination.balance(None).held, dec!(0));
+        assert_eq!(ledger.account(1).unwrap().balance(None).total, dec!(60));
+    }
+
+    #[test]
+    fn process_trx_successful_transfer_dispute_chargeback() {
+        let mut ledger = ledger_with_withdrawal_disputes();
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::transfer(1, 2, 3, dec!(40)))
+            .expect("failed to transfer");
+        ledger
+            .process_trx(&Input::dispute(1, 3))
+            .expect("failed to dispute");
+
+        let result = ledger.process_trx(&Input::chargeback(1, 3));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::ChargedBack {
+                client: 1,
+                tx: 3,
+                dispute: DEFAULT_DISPUTE,
+                amount: dec!(40)
+            }
+        );
+
+        // the transfer is reversed and the account that received it is locked.
+        let source = ledger.account(1).unwrap();
+        assert_eq!(source.balance(None).available, dec!(100));
+        assert_eq!(source.balance(None).total, dec!(100));
+        assert!(!source.locked());
+        let destination = ledger.account(2).unwrap();
+        assert_eq!(destination.balance(None).available, dec!(10));
+        assert_eq!(destination.balance(None).held, dec!(0));
+        assert_eq!(destination.balance(None).total, dec!(10));
+        assert!(destination.locked());
+    }
+
+    #[test]
+    fn process_trx_successful_authorize_partial_capture() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::authorize(client, 2, dec!(40)));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::Authorized {
+                client,
+                tx: 2,
+                amount: dec!(40)
+            }
+        );
+        assert_eq!(ledger.transaction(2).unwrap().state, State::Authorized);
+
+        let result = ledger.process_trx(&Input::capture(client, 2, Some(dec!(25))));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::Captured {
+                client,
+                tx: 2,
+                amount: dec!(25),
+                released: dec!(15)
+            }
+        );
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(75));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(75));
+        let trx = ledger.transaction(2).unwrap();
+        assert_eq!(trx.state, State::Captured);
+        assert_eq!(trx.captured(), Some(dec!(25)));
+
+        // an authorization can only be captured once.
+        let result = ledger.process_trx(&Input::capture(client, 2, Some(dec!(15))));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxAlreadyCaptured { tx: 2 }
+        );
+    }
+
+    #[test]
+    fn process_trx_capture_fail_when_already_captured() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::authorize(client, 2, dec!(40)))
+            .expect("failed to authorize");
+        ledger
+            .process_trx(&Input::capture(client, 2, None))
+            .expect("failed to capture");
+
+        // a second capture is rejected even without an amount, nothing of the authorization is left to capture.
+        let error = ledger
+            .process_trx(&Input::capture(client, 2, None))
+            .unwrap_err();
+
+        assert_eq!(error, EngineError::TrxAlreadyCaptured { tx: 2 });
+        assert_eq!(error.code(), "trx_already_captured");
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(60));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(ledger.transaction(2).unwrap().captured(), Some(dec!(40)));
+    }
+
+    #[test]
+    fn process_trx_capture_fail_when_amount_exceeds_authorization() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::authorize(client, 2, dec!(40)))
+            .expect("failed to authorize");
+
+        let result = ledger.process_trx(&Input::capture(client, 2, Some(dec!(50))));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::CaptureAmountExceeded {
+                tx: 2,
+                amount: dec!(50),
+                authorized: dec!(40)
+            }
+        );
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(60));
+        assert_eq!(account.balance(None).held, dec!(40));
+        assert_eq!(ledger.transaction(2).unwrap().state, State::Authorized);
+    }
+
+    #[test]
+    fn process_trx_capture_fail_when_trx_is_not_an_authorization() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::capture(client, 1, None));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotAuthorized { tx: 1 });
+
+        let result = ledger.process_trx(&Input::void(client, 2));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotFound { tx: 2 });
+    }
+
+    #[test]
+    fn process_trx_successful_authorize_void() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::authorize(client, 2, dec!(40)))
+            .expect("failed to authorize");
+
+        let result = ledger.process_trx(&Input::void(client, 2));
+        assert_eq!(
+            result.unwrap(),
+            Outcome::Voided {
+                client,
+                tx: 2,
+                amount: dec!(40)
+            }
+        );
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(100));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(ledger.transaction(2).unwrap().state, State::Voided);
+
+        // a voided authorization cannot be captured nor disputed.
+        let result = ledger.process_trx(&Input::capture(client, 2, None));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxAuthorizationClosed { tx: 2 }
+        );
+        let result = ledger.process_trx(&Input::dispute(client, 2));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotDisputable { tx: 2 });
+    }
+
+    /// helper func to provide a ledger expiring holds after the provided lifetime
+    fn ledger_with_hold_lifetime(lifetime: u64) -> Ledger {
+        Ledger::with_config(LedgerConfig {
+            hold_lifetimes: HoldLifetimes {
+                dispute: Some(lifetime),
+                authorization: Some(lifetime),
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn process_trx_releases_expired_dispute() {
+        let client = 1;
+
+        let mut ledger = ledger_with_hold_lifetime(100);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(0))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1).with_timestamp(10))
+            .expect("failed to dispute");
+
+        // the hold is still valid.
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(5)).with_timestamp(109))
+            .expect("failed to deposit");
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(10));
+        assert!(ledger.synthetic_events().is_empty());
+
+        // the hold expires before the input is applied, so the released funds can be withdrawn.
+        ledger
+            .process_trx(&Input::withdrawal(client, 3, dec!(15)).with_timestamp(110))
+            .expect("failed to withdrawal");
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(ledger.transaction(1).unwrap().state, State::Ok);
+        assert_eq!(ledger.clock(), Some(110));
+        assert_eq!(
+            ledger.synthetic_events(),
+            &[Outcome::HoldExpired {
+                client,
+                tx: 1,
+                dispute: Some(DEFAULT_DISPUTE),
+                amount: dec!(10)
+            }]
+        );
+        // drained events are no longer kept by the ledger.
+        assert_eq!(ledger.drain_synthetic_events().count(), 1);
+        assert!(ledger.synthetic_events().is_empty());
+    }
+
+    #[test]
+    fn process_trx_releases_expired_authorization() {
+        let client = 1;
+
+        let mut ledger = ledger_with_hold_lifetime(100);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(0))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::authorize(client, 2, dec!(4)).with_timestamp(10))
+            .expect("failed to authorize");
+        ledger
+            .process_trx(&Input::deposit(client, 3, dec!(1)).with_timestamp(200))
+            .expect("failed to deposit");
+
+        let account = ledger.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(11));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(ledger.transaction(2).unwrap().state, State::Voided);
+        assert_eq!(
+            ledger.synthetic_events(),
+            &[Outcome::HoldExpired {
+                client,
+                tx: 2,
+                dispute: None,
+                amount: dec!(4)
+            }]
+        );
+
+        // the authorization can no longer be captured.
+        let result = ledger.process_trx(&Input::capture(client, 2, None));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxAuthorizationClosed { tx: 2 }
+        );
+    }
+
+    #[test]
+    fn process_trx_does_not_release_settled_or_reopened_holds() {
+        let client = 1;
+
+        let mut ledger = ledger_with_hold_lifetime(100);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(0))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1).with_timestamp(10))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&Input::resolve(client, 1).with_timestamp(20))
+            .expect("failed to resolve");
+        ledger
+            .process_trx(&Input::dispute(client, 1).with_timestamp(90))
+            .expect("failed to dispute");
+
+        // the first dispute would have expired at 110, the second one expires at 190.
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(1)).with_timestamp(150))
+            .expect("failed to deposit");
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(10));
+        assert!(ledger.synthetic_events().is_empty());
+
+        ledger
+            .process_trx(&Input::deposit(client, 3, dec!(1)).with_timestamp(190))
+            .expect("failed to deposit");
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(0));
+        assert_eq!(ledger.synthetic_events().len(), 1);
+    }
+
+    #[test]
+    fn process_trx_rejected_input_does_not_move_the_clock() {
+        let client = 1;
+
+        let mut ledger = ledger_with_hold_lifetime(100);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(0))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1).with_timestamp(10))
+            .expect("failed to dispute");
+
+        let result =
+            ledger.process_trx(&Input::withdrawal(client, 2, dec!(50)).with_timestamp(500));
+        assert!(result.is_err());
+
+        // the release is dropped along with the rejected input.
+        assert_eq!(ledger.clock(), Some(10));
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(10));
+        assert!(ledger.synthetic_events().is_empty());
+    }
+
+    #[test]
+    fn process_trx_without_timestamps_never_releases_holds() {
+        let client = 1;
+
+        let mut ledger = ledger_with_hold_lifetime(0);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1))
+            .expect("failed to dispute");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(10)))
+            .expect("failed to deposit");
+
+        assert_eq!(ledger.clock(), None);
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(10));
+        assert!(ledger.synthetic_events().is_empty());
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_dispute_window_expired() {
+        let client = 1;
+
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_window: Some(120),
+            ..Default::default()
+        });
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(1000))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(10)).with_timestamp(1100))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::dispute(client, 1).with_timestamp(1121));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::DisputeWindowExpired {
+                tx: 1,
+                processed_at: 1000,
+                disputed_at: 1121
+            }
+        );
+        assert_eq!(ledger.account(client).unwrap().balance(None).held, dec!(0));
+
+        // the window is inclusive.
+        let result = ledger.process_trx(&Input::dispute(client, 2).with_timestamp(1220));
+        assert!(result.is_ok());
+
+        // disputes without a timestamp happen at the current clock.
+        let result = ledger.process_trx(&Input::dispute(client, 1));
+        assert!(matches!(
+            result.unwrap_err(),
+            EngineError::DisputeWindowExpired {
+                disputed_at: 1220,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn process_trx_dispute_without_timestamps_ignores_dispute_window() {
+        let client = 1;
+
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_window: Some(0),
+            ..Default::default()
+        });
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
+
+        // the transaction was processed before the clock was known.
+        let result = ledger.process_trx(&Input::dispute(client, 1).with_timestamp(5000));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn process_trx_dispute_in_currency_of_disputed_trx() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(20)).with_currency(usd))
+            .expect("failed to deposit");
+
+        // disputes without a currency operate in the currency of the transaction.
+        ledger
+            .process_trx(&Input::dispute(client, 1))
+            .expect("failed to dispute");
+
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(Some(eur)).available, dec!(0));
+        assert_eq!(account.balance(Some(eur)).held, dec!(10));
+        assert_eq!(account.balance(Some(usd)).available, dec!(20));
+        assert_eq!(account.balance(Some(usd)).held, dec!(0));
+        assert_eq!(account.balances().count(), 2);
+
+        ledger
+            .process_trx(&Input::chargeback(client, 1).with_currency(eur))
+            .expect("failed to chargeback");
+
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(Some(eur)).total, dec!(0));
+        assert_eq!(account.balance(Some(usd)).total, dec!(20));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn process_trx_dispute_fail_when_currency_mismatch() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(10)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::dispute(client, 1).with_currency(usd));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxCurrencyMismatch { tx: 1 }
+        );
+
+        // a transaction without a currency cannot be referred to in a currency either.
+        let result = ledger.process_trx(&Input::dispute(client, 2).with_currency(eur));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxCurrencyMismatch { tx: 2 }
+        );
+
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(Some(eur)).held, dec!(0));
+        assert_eq!(account.balance(None).held, dec!(0));
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_funds_in_other_currency() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::withdrawal(client, 2, dec!(5)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(0),
+                amount: dec!(5)
+            }
+        );
+    }
+
+    #[test]
+    fn process_trx_capture_in_currency_of_authorization() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::authorize(client, 2, dec!(6)).with_currency(eur))
+            .expect("failed to authorize");
+        ledger
+            .process_trx(&Input::capture(client, 2, Some(dec!(4))))
+            .expect("failed to capture");
+
+        let balance = ledger.account(client).unwrap().balance(Some(eur));
+        assert_eq!(balance.available, dec!(6));
+        assert_eq!(balance.held, dec!(0));
+        assert_eq!(balance.total, dec!(6));
+    }
+
+    /// Rate provider returning the same rate for every pair.
+    struct FixedRate(Decimal);
+
+    impl RateProvider for FixedRate {
+        fn rate(&self, _: Currency, _: Currency) -> Option<Decimal> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn process_trx_exchange_with_inline_rate() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::new().with_rate_provider(Box::new(FixedRate(dec!(2))));
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+
+        let outcome = ledger
+            .process_trx(&Input::exchange(client, 2, dec!(4), eur, usd).with_rate(dec!(1.1)))
+            .expect("failed to exchange");
+
+        assert_eq!(
+            outcome,
+            Outcome::Exchanged {
+                client,
+                tx: 2,
+                amount: dec!(4),
+                rate: dec!(1.1),
+                converted: dec!(4.4)
+            }
+        );
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(Some(eur)).available, dec!(6));
+        assert_eq!(account.balance(Some(eur)).total, dec!(6));
+        assert_eq!(account.balance(Some(usd)).available, dec!(4.4));
+        assert_eq!(account.balance(Some(usd)).total, dec!(4.4));
+
+        let trx = ledger.transaction(2).unwrap();
+        assert_eq!(trx.rate(), Some(dec!(1.1)));
+        assert_eq!(trx.converted(), Some(dec!(4.4)));
+        assert_eq!(trx.target_currency(), Some(usd));
+    }
+
+    #[test]
+    fn process_trx_exchange_with_rate_provider_and_rounding() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let jpy: Currency = "JPY".parse().unwrap();
+
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            precision: PrecisionPolicy {
+                currencies: HashMap::from([(
+                    jpy,
+                    Rounding {
+                        decimal_places: 0,
+                        mode: RoundingMode::Truncate,
+                    },
+                )]),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with_rate_provider(Box::new(FixedRate(dec!(161.87))));
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+
+        ledger
+            .process_trx(&Input::exchange(client, 2, dec!(1.5), eur, jpy))
+            .expect("failed to exchange");
+
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(Some(eur)).available, dec!(8.5));
+        assert_eq!(account.balance(Some(jpy)).available, dec!(242));
+        assert_eq!(ledger.transaction(2).unwrap().rate(), Some(dec!(161.87)));
+    }
+
+    #[test]
+    fn process_trx_exchange_fail_without_rate() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&Input::exchange(client, 2, dec!(4), eur, usd));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::ExchangeRateNotFound {
+                tx: 2,
+                from: eur,
+                to: usd
+            }
+        );
+
+        let result =
+            ledger.process_trx(&Input::exchange(client, 2, dec!(4), eur, usd).with_rate(dec!(0)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::ExchangeRateInvalid {
+                tx: 2,
+                rate: dec!(0)
+            }
+        );
+        assert_eq!(
+            ledger.account(client).unwrap().balance(Some(eur)).available,
+            dec!(10)
+        );
+        assert!(ledger.transaction(2).is_none());
+    }
+
+    #[test]
+    fn process_trx_exchange_fail_when_currencies_invalid() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+
+        let result =
+            ledger.process_trx(&Input::exchange(client, 2, dec!(4), eur, eur).with_rate(dec!(1)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxInvalidExchange { tx: 2 }
+        );
+
+        let result = ledger.process_trx(&Input {
+            currency: None,
+            ..Input::exchange(client, 2, dec!(4), eur, eur).with_rate(dec!(1))
+        });
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxInvalidExchange { tx: 2 }
+        );
+    }
+
+    #[test]
+    fn process_trx_exchange_fail_when_insufficient_funds() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::new();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1))
+            .expect("failed to dispute");
+
+        let result =
+            ledger.process_trx(&Input::exchange(client, 2, dec!(4), eur, usd).with_rate(dec!(1.1)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(0),
+                amount: dec!(4)
+            }
+        );
+        assert_eq!(ledger.account(client).unwrap().balances().count(), 1);
+    }
+
+    #[test]
+    fn process_trx_exchange_is_not_disputable() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+        let usd: Currency = "USD".parse().unwrap();
+
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_policy: DisputePolicy::DepositsAndWithdrawals,
+            ..Default::default()
+        });
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_currency(eur))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::exchange(client, 2, dec!(4), eur, usd).with_rate(dec!(1.1)))
+            .expect("failed to exchange");
+
+        let result = ledger.process_trx(&Input::dispute(client, 2));
+        assert_eq!(result.unwrap_err(), EngineError::TrxNotDisputable { tx: 2 });
+    }
+
+    #[test]
+    fn process_trx_deposit_fail_when_precision_exceeded() {
+        let client = 1;
+
+        let mut ledger = Ledger::new();
+
+        let result = ledger.process_trx(&Input::deposit(client, 1, dec!(1.00001)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AmountPrecisionExceeded {
+                tx: 1,
+                amount: dec!(1.00001),
+                decimal_places: 4
+            }
+        );
+        assert!(ledger.account(client).is_none());
+
+        // trailing zeros do not count as decimal places.
+        let result = ledger.process_trx(&Input::deposit(client, 1, dec!(1.000100)));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn process_trx_fail_when_precision_of_currency_exceeded() {
+        let client = 1;
+        let jpy: Currency = "JPY".parse().unwrap();
+
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            precision: PrecisionPolicy {
+                currencies: HashMap::from([(
+                    jpy,
+                    Rounding {
+                        decimal_places: 0,
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)).with_currency(jpy))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(0.5)))
+            .expect("failed to deposit");
+
+        let result =
+            ledger.process_trx(&Input::withdrawal(client, 3, dec!(0.5)).with_currency(jpy));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AmountPrecisionExceeded {
+                tx: 3,
+                amount: dec!(0.5),
+                decimal_places: 0
+            }
+        );
+
+        // partial disputes are validated against the currency of the disputed transaction.
+        let result = ledger.process_trx(&Input::partial_dispute(client, 1, 1, dec!(10.5)));
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::AmountPrecisionExceeded {
+                tx: 1,
+                amount: dec!(10.5),
+                decimal_places: 0
+            }
+        );
+        assert_eq!(
+            ledger.account(client).unwrap().balance(Some(jpy)).held,
+            dec!(0)
+        );
+    }
+
+    const HOUSE_ACCOUNT: u16 = 9999;
+
+    fn ledger_with_fees() -> Ledger {
+        Ledger::with_config(LedgerConfig {
+            fees: Some(FeeSchedule {
+                withdrawal: Some(FeeRule {
+                    fee: Fee::Percentage(dec!(1)),
+                    min: Some(dec!(0.5)),
+                    max: None,
+                }),
+                chargeback: Some(FeeRule::new(Fee::Flat(dec!(15)))),
+                ..FeeSchedule::new(HOUSE_ACCOUNT)
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn process_trx_withdrawal_with_fee() {
+        let client = 1;
+
+        let mut ledger = ledger_with_fees();
+        assert_eq!(
+            ledger.account(HOUSE_ACCOUNT).unwrap().balance(None),
+            Balance::default()
+        );
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(100))))
+            .expect("failed to deposit");
+
+        ledger
+            .process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(20))))
+            .expect("failed to withdrawal");
+
+        let balance = ledger.account(client).unwrap().balance(None);
+        assert_eq!(balance.available, dec!(79.5));
+        assert_eq!(balance.total, dec!(79.5));
+        assert_eq!(
+            ledger.account(HOUSE_ACCOUNT).unwrap().balance(None).total,
+            dec!(0.5)
+        );
+        assert_eq!(
+            ledger.fee_transactions(),
+            &[FeeTransaction {
+                tx: 2,
+                transaction_type: Type::Withdrawal,
+                client,
+                house_account: HOUSE_ACCOUNT,
+                currency: None,
+                amount: dec!(0.5)
+            }]
+        );
+        // drained fees are no longer kept by the ledger.
+        assert_eq!(ledger.drain_fee_transactions().count(), 1);
+        assert!(ledger.fee_transactions().is_empty());
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_fee_not_covered() {
+        let client = 1;
+
+        let mut ledger = ledger_with_fees();
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(100))))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(100))));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(100),
+                amount: dec!(101)
+            }
+        );
+        assert_eq!(
+            ledger.account(client).unwrap().balance(None).total,
+            dec!(100)
+        );
+        assert_eq!(
+            ledger.account(HOUSE_ACCOUNT).unwrap().balance(None).total,
+            dec!(0)
+        );
+        assert!(ledger.fee_transactions().is_empty());
+    }
+
+    fn ledger_with_withdrawal_fee(fee: Fee) -> Ledger {
+        Ledger::with_config(LedgerConfig {
+            fees: Some(FeeSchedule {
+                withdrawal: Some(FeeRule::new(fee)),
+                ..FeeSchedule::new(HOUSE_ACCOUNT)
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_flat_fee_overflows() {
+        let client = 1;
+
+        let mut ledger = ledger_with_withdrawal_fee(Fee::Flat(dec!(1)));
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(Decimal::MAX)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(Decimal::MAX)));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client,
+                amount: dec!(1)
+            }
+        );
+        assert_eq!(
+            ledger.account(client).unwrap().balance(None).total,
+            Decimal::MAX
+        );
+        assert!(ledger.fee_transactions().is_empty());
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_when_percentage_fee_overflows() {
+        let client = 1;
+
+        let mut ledger = ledger_with_withdrawal_fee(Fee::Percentage(dec!(2)));
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(Decimal::MAX)))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(Decimal::MAX)));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::Overflow {
+                client,
+                amount: Decimal::MAX
+            }
+        );
+        assert_eq!(
+            ledger.account(client).unwrap().balance(None).total,
+            Decimal::MAX
+        );
+        assert!(ledger.fee_transactions().is_empty());
+    }
+
+    #[test]
+    fn process_trx_chargeback_with_fee() {
+        let client = 1;
+
+        let mut ledger = ledger_with_fees();
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(100))))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&input(Type::Deposit, client, 2, Some(dec!(10))))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&input(Type::Dispute, client, 1, None))
+            .expect("failed to dispute");
+
+        ledger
+            .process_trx(&input(Type::Chargeback, client, 1, None))
+            .expect("failed to chargeback");
+
+        // the fee can leave the available funds negative.
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(None).available, dec!(-5));
+        assert_eq!(account.balance(None).held, dec!(0));
+        assert_eq!(account.balance(None).total, dec!(-5));
+        assert!(account.locked());
+        assert_eq!(
+            ledger.account(HOUSE_ACCOUNT).unwrap().balance(None).total,
+            dec!(15)
+        );
+        assert_eq!(ledger.fee_transactions().len(), 1);
+        assert_eq!(ledger.fee_transactions()[0].tx(), 1);
+        assert_eq!(
+            ledger.fee_transactions()[0].transaction_type(),
+            Type::Chargeback
+        );
+    }
+
+    #[test]
+    fn process_trx_fee_in_currency_of_transaction() {
+        let client = 1;
+        let eur: Currency = "EUR".parse().unwrap();
+
+        let mut ledger = ledger_with_fees();
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(100)).with_currency(eur))
+            .expect("failed to deposit");
+
+        ledger
+            .process_trx(&Input::withdrawal(client, 2, dec!(55.55)).with_currency(eur))
+            .expect("failed to withdrawal");
+
+        // 1% of 55.55 rounded to the precision of the currency.
+        let house = ledger.account(HOUSE_ACCOUNT).unwrap();
+        assert_eq!(house.balance(Some(eur)).total, dec!(0.5555));
+        assert_eq!(
+            ledger.account(client).unwrap().balance(Some(eur)).total,
+            dec!(43.8945)
+        );
+    }
+
+    fn ledger_with_credit_limit(client: u16, credit_limit: Decimal) -> Ledger {
+        let mut credit_limits = CreditLimits::default();
+        credit_limits.set(client, credit_limit);
+        Ledger::with_config(LedgerConfig {
+            credit_limits,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn process_trx_withdrawal_within_credit_limit() {
+        let client = 1;
+
+        let mut ledger = ledger_with_credit_limit(client, dec!(100));
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(50))))
+            .expect("failed to deposit");
+
+        ledger
+            .process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(150))))
+            .expect("failed to withdrawal");
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 3, Some(dec!(1))));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(0),
+                amount: dec!(1)
+            }
+        );
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.credit_limit(), dec!(100));
+        assert_eq!(account.balance(None).available, dec!(-100));
+        assert!(!account.over_limit(None));
+    }
+
+    #[test]
+    fn process_trx_chargeback_over_credit_limit() {
+        let client = 1;
+
+        let mut ledger = ledger_with_credit_limit(client, dec!(100));
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(50))))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(150))))
+            .expect("failed to withdrawal");
+        ledger
+            .process_trx(&input(Type::Dispute, client, 1, None))
+            .expect("failed to dispute");
+
+        // the chargeback is applied even though it takes the account over its credit limit.
+        ledger
+            .process_trx(&input(Type::Chargeback, client, 1, None))
+            .expect("failed to chargeback");
+
+        let account = ledger.account(client).unwrap();
+        assert_eq!(account.balance(None).available, dec!(-150));
+        assert_eq!(account.balance(None).total, dec!(-150));
+        assert!(account.over_limit(None));
+        assert!(account.locked());
+    }
+
+    #[test]
+    fn process_trx_withdrawal_fail_without_credit_limit() {
+        let client = 1;
+
+        let mut ledger = ledger_with_credit_limit(2, dec!(100));
+        ledger
+            .process_trx(&input(Type::Deposit, client, 1, Some(dec!(50))))
+            .expect("failed to deposit");
+
+        let result = ledger.process_trx(&input(Type::Withdrawal, client, 2, Some(dec!(51))));
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::InsufficientFunds {
+                client,
+                available: dec!(50),
+                amount: dec!(51)
+            }
+        );
+        assert_eq!(ledger.account(client).unwrap().credit_limit(), dec!(0));
+    }
+
+    /// Transaction store failing every write.
+    struct ReadOnlyStore;
+
+    impl TransactionStore for ReadOnlyStore {
+        fn get(&self, _: u32) -> std::io::Result<Option<Cow<'_, Transaction>>> {
+            Ok(None)
+        }
+
+        fn insert(&mut self, _: Transaction) -> std::io::Result<()> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "read only",
+            ))
+        }
+
+        fn remove(&mut self, _: u32) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn transactions(
+            &self,
+        ) -> Box<dyn Iterator<Item = std::io::Result<Cow<'_, Transaction>>> + '_> {
+            Box::new(std::iter::empty())
+        }
 
-        assert_eq!(1, ledger.trx.len());
-        let trx = ledger.trx.get(&tx_id).expect("transaction not found");
-        assert_eq!(trx.amount, Some(amount));
-        assert_eq!(trx.state, State::Chargeback);
-        assert_eq!(trx.client, client_id);
+        fn len(&self) -> usize {
+            0
+        }
     }
 
     #[test]
-    fn process_trx_deposit_fail_when_trx_already_processed() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = Some(dec!(1500));
-        let input = input(Type::Deposit, client, tx, amount);
-
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+    fn process_trx_fail_when_transaction_store_fails() {
+        let client = 1;
 
-        // validate that preconditions are verified
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client));
+        let mut ledger = Ledger::new().with_transaction_store(Box::new(ReadOnlyStore));
 
-        // repeat the same transaction to assert the expected behaviour
-        let result = ledger.process_trx(&input);
+        let result = ledger.process_trx(&input(Type::Deposit, client, 1, Some(dec!(10))));
 
-        assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxAlreadyProcessed.to_string()
+            result.unwrap_err(),
+            EngineError::TrxStoreFailure {
+                tx: 1,
+                reason: "read only".to_string()
+            }
         );
+        assert!(ledger.account(client).is_none());
+        assert!(ledger.transaction(1).is_none());
+    }
+
+    /// Account store failing to write the account of `failing`, if set.
+    struct FailingAccountStore {
+        accounts: HashMap<u16, Account>,
+        failing: std::rc::Rc<std::cell::Cell<Option<u16>>>,
+    }
+
+    impl AccountStore for FailingAccountStore {
+        fn get(&self, client: u16) -> std::io::Result<Option<Cow<'_, Account>>> {
+            AccountStore::get(&self.accounts, client)
+        }
+
+        fn insert(&mut self, account: Account) -> std::io::Result<()> {
+            if self.failing.get() == Some(account.client) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::StorageFull,
+                    "disk full",
+                ));
+            }
+            AccountStore::insert(&mut self.accounts, account)
+        }
+
+        fn remove(&mut self, client: u16) -> std::io::Result<()> {
+            AccountStore::remove(&mut self.accounts, client)
+        }
+
+        fn accounts(&self) -> Box<dyn Iterator<Item = std::io::Result<Cow<'_, Account>>> + '_> {
+            self.accounts.accounts()
+        }
     }
 
     #[test]
-    fn process_trx_deposit_fail_when_amount_is_not_present() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = None;
-        let input = input(Type::Deposit, client, tx, amount);
+    fn process_trx_rolls_back_stores_when_account_store_fails() {
+        let failing = std::rc::Rc::new(std::cell::Cell::new(None));
+        let mut ledger = Ledger::new().with_account_store(Box::new(FailingAccountStore {
+            accounts: HashMap::new(),
+            failing: failing.clone(),
+        }));
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(100)))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(2, 2, dec!(10)))
+            .expect("failed to deposit");
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        failing.set(Some(2));
+        let result = ledger.process_trx(&Input::transfer(1, 2, 3, dec!(40)));
 
-        assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxInvalidAmount.to_string()
+            result.unwrap_err(),
+            EngineError::AccountStoreFailure {
+                client: 2,
+                reason: "disk full".to_string()
+            }
         );
+        // neither the transaction nor the account written before the failure are kept.
+        assert!(ledger.transaction(3).is_none());
+        assert_eq!(ledger.account(1).unwrap().balance(None).total, dec!(100));
+        assert_eq!(ledger.account(2).unwrap().balance(None).total, dec!(10));
+
+        // once the store recovers the same input can be retried.
+        failing.set(None);
+        ledger
+            .process_trx(&Input::transfer(1, 2, 3, dec!(40)))
+            .expect("failed to transfer");
+        assert_eq!(ledger.account(1).unwrap().balance(None).total, dec!(60));
+        assert_eq!(ledger.account(2).unwrap().balance(None).total, dec!(50));
     }
 
     #[test]
-    fn process_trx_deposit_fail_when_account_method_returns_error() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = Some(dec!(-1500));
-        let input = input(Type::Deposit, client, tx, amount);
+    fn process_trx_rolls_back_new_account_when_account_store_fails() {
+        let failing = std::rc::Rc::new(std::cell::Cell::new(Some(1)));
+        let mut ledger = Ledger::new().with_account_store(Box::new(FailingAccountStore {
+            accounts: HashMap::new(),
+            failing,
+        }));
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        let result = ledger.process_trx(&Input::deposit(1, 1, dec!(100)));
 
         assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
-        );
+        assert!(ledger.transaction(1).is_none());
+        assert!(ledger.account(1).is_none());
     }
 
     #[test]
-    fn process_trx_withdrawal_fail_when_trx_already_processed() {
-        let tx_deposit = 123;
-        let tx = 123456789;
-        let client = 1234;
-        let amount = Some(dec!(1500));
-        let input_deposit = input(Type::Deposit, client, tx_deposit, amount);
-        let input_withdrawal = input(Type::Withdrawal, client, tx, amount);
-
-        let mut ledger = Ledger::new();
-        // load balance in the account, so it can be withdrawal
-        let result = ledger.process_trx(&input_deposit);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx_deposit));
-        assert!(ledger.accounts.contains_key(&client));
+    fn recover_replays_journaled_inputs() {
+        let client = 1;
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
 
-        let result = ledger.process_trx(&input_withdrawal);
-        // validate that preconditions are verified (withdrawal was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client));
+        let journal = FileJournal::open(&path, SyncPolicy::Always).expect("failed to open journal");
+        let mut ledger = Ledger::new().with_journal(Box::new(journal));
+        ledger
+            .process_trx_at_line(&Input::deposit(client, 1, dec!(10)).with_timestamp(3), 2)
+            .expect("failed to deposit");
+        // rejected inputs are not journaled.
+        ledger
+            .process_trx_at_line(&Input::withdrawal(client, 2, dec!(50)), 3)
+            .expect_err("withdrawal should fail");
+        ledger
+            .process_trx_at_line(&Input::dispute(client, 1), 4)
+            .expect("failed to dispute");
 
-        // repeat the same transaction to assert the expected behaviour
-        let result = ledger.process_trx(&input_withdrawal);
+        let mut recovered = Ledger::new();
+        let file = File::open(&path).expect("failed to open journal");
+        let last_line = recovered
+            .recover(journal::read_entries(file))
+            .expect("failed to recover");
 
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxAlreadyProcessed.to_string()
-        );
+        assert_eq!(last_line, Some(4));
+        assert_eq!(recovered.account(client), ledger.account(client));
+        assert_eq!(recovered.transaction(1).unwrap().state, State::Disputed);
+        assert!(recovered.transaction(2).is_none());
+        // the dispute was journaled with the timestamp it was processed at.
+        assert_eq!(recovered.clock(), Some(3));
     }
 
     #[test]
-    fn process_trx_withdrawal_fail_when_amount_is_not_present() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = None;
-        let input = input(Type::Withdrawal, client, tx, amount);
+    fn process_trx_does_not_journal_input_rejected_by_store() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        let journal = FileJournal::open(&path, SyncPolicy::Always).expect("failed to open journal");
+        let failing = std::rc::Rc::new(std::cell::Cell::new(None));
+        let mut ledger = Ledger::new()
+            .with_account_store(Box::new(FailingAccountStore {
+                accounts: HashMap::new(),
+                failing: failing.clone(),
+            }))
+            .with_journal(Box::new(journal));
+        ledger
+            .process_trx_at_line(&Input::deposit(1, 1, dec!(10)), 2)
+            .expect("failed to deposit");
+        failing.set(Some(1));
+        ledger
+            .process_trx_at_line(&Input::deposit(1, 2, dec!(5)), 3)
+            .expect_err("deposit should fail");
 
-        assert!(result.is_err());
+        // the entry of the input is discarded once the store fails to write its changes.
+        let entries = journal::read_entries(File::open(&path).expect("failed to open journal"))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read entries");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line, Some(2));
+        assert_eq!(entries[0].sequence, Some(1));
+        assert_eq!(ledger.sequence(), 1);
+
+        // persisting the stores keeps the journal, it is emptied once its inputs no longer need to be recovered.
+        ledger.checkpoint().expect("failed to checkpoint");
+        assert_eq!(
+            journal::read_entries(File::open(&path).expect("failed to open journal")).count(),
+            1
+        );
+        ledger
+            .truncate_journal()
+            .expect("failed to truncate journal");
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxInvalidAmount.to_string()
+            journal::read_entries(File::open(&path).expect("failed to open journal")).count(),
+            0
         );
     }
 
     #[test]
-    fn process_trx_withdrawal_fail_when_account_method_returns_error() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = Some(dec!(-1500));
-        let input = input(Type::Withdrawal, client, tx, amount);
+    fn recover_skips_entries_held_by_account_store() {
+        let client = 1;
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let path = dir.path().join("journal.jsonl");
+        let store_path = dir.path().join("accounts.jsonl");
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        let journal = FileJournal::open(&path, SyncPolicy::Always).expect("failed to open journal");
+        let store = FileAccountStore::open(&store_path).expect("failed to open store");
+        let mut ledger = Ledger::new()
+            .with_account_store(Box::new(store))
+            .with_journal(Box::new(journal));
+        for tx in 1..=2 {
+            ledger
+                .process_trx_at_line(&Input::deposit(client, tx, dec!(10)), u64::from(tx) + 1)
+                .expect("failed to deposit");
+        }
+        // the store is persisted but the journal is not truncated, as if the run stopped in between.
+        ledger.checkpoint().expect("failed to checkpoint");
+        ledger
+            .process_trx_at_line(&Input::deposit(client, 3, dec!(10)), 4)
+            .expect("failed to deposit");
 
-        assert!(result.is_err());
+        let store = FileAccountStore::open(&store_path).expect("failed to reopen store");
+        let mut recovered = Ledger::new().with_account_store(Box::new(store));
+        assert_eq!(recovered.sequence(), 2);
+        let file = File::open(&path).expect("failed to open journal");
+        let last_line = recovered
+            .recover(journal::read_entries(file))
+            .expect("failed to recover");
+
+        // only the deposit the store did not persist is applied again.
+        assert_eq!(last_line, Some(4));
+        assert_eq!(recovered.sequence(), 3);
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::NegativeAmount.to_string()
+            recovered.account(client).unwrap().balance(None).total,
+            dec!(30)
         );
     }
 
+    struct FailingJournal;
+
+    impl Journal for FailingJournal {
+        fn append(&mut self, _: &JournalEntry) -> std::io::Result<()> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::StorageFull,
+                "disk full",
+            ))
+        }
+
+        fn discard(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn truncate(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
     #[test]
-    fn process_trx_dispute_fail_when_disputed_trx_not_found() {
-        let tx = 123456789;
-        let client = 1234;
-        let input = input(Type::Dispute, client, tx, None);
+    fn process_trx_fail_when_journal_fails() {
+        let client = 1;
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        let mut ledger = Ledger::new().with_journal(Box::new(FailingJournal));
+
+        let result = ledger.process_trx(&input(Type::Deposit, client, 1, Some(dec!(10))));
 
-        assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxNotFound.to_string()
+            result.unwrap_err(),
+            EngineError::JournalFailure {
+                tx: 1,
+                reason: "disk full".to_string()
+            }
         );
+        assert!(ledger.account(client).is_none());
+        assert!(ledger.transaction(1).is_none());
     }
 
     #[test]
-    fn process_trx_dispute_fail_when_disputed_trx_belongs_to_another_user() {
-        let tx = 123456789;
-        let client_deposit = 1;
-        let client = 1234;
-        let amount = Some(dec!(1500));
-        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
-        let input_dispute = input(Type::Dispute, client, tx, None);
+    fn project_events_rebuilds_accounts() {
+        let mut ledger = Ledger::new().with_event_store(Box::new(Vec::new()));
+        let inputs = [
+            Input::deposit(1, 1, dec!(100)),
+            Input::deposit(2, 2, dec!(10)),
+            Input::withdrawal(1, 3, dec!(30)),
+            Input::transfer(1, 2, 4, dec!(20)),
+            Input::authorize(2, 5, dec!(5)),
+            Input::capture(2, 5, Some(dec!(3))),
+            Input::dispute(2, 2),
+            Input::resolve(2, 2),
+            Input::dispute(1, 1),
+            Input::chargeback(1, 1),
+        ];
+        for input in &inputs {
+            ledger.process_trx(input).expect("failed to process input");
+        }
+        // rejected inputs record no event.
+        assert!(ledger
+            .process_trx(&Input::withdrawal(2, 6, dec!(1000)))
+            .is_err());
+
+        let mut accounts: HashMap<u16, Account> = HashMap::new();
+        ledger
+            .project(&mut accounts)
+            .expect("failed to project events");
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[&1], *ledger.account(1).unwrap());
+        assert_eq!(accounts[&2], *ledger.account(2).unwrap());
+        assert_eq!(accounts[&1].status(), AccountStatus::Locked);
+        assert!(ledger
+            .events()
+            .all(|record| record.expect("failed to read event").tx != 6));
+    }
 
+    #[test]
+    fn events_are_discarded_without_event_store() {
         let mut ledger = Ledger::new();
-        // load balance in the account, so it can be disputed
-        let result = ledger.process_trx(&input_deposit);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
+        ledger
+            .process_trx(&Input::deposit(1, 1, dec!(10)))
+            .expect("failed to deposit");
 
-        let result = ledger.process_trx(&input_dispute);
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxClientIdInconsistency.to_string()
-        );
+        assert_eq!(ledger.events().count(), 0);
     }
 
     #[test]
-    fn process_trx_dispute_fail_when_transaction_type_not_valid() {
-        let invalid_inputs = [
-            input(Type::Withdrawal, 0, 0, Some(dec!(10.0))),
-            input(Type::Dispute, 0, 1, None),
-            input(Type::Resolve, 0, 2, None),
-            input(Type::Chargeback, 0, 3, None),
-        ];
+    fn from_events_rebuilds_accounts() {
+        let mut ledger = Ledger::new().with_event_store(Box::new(Vec::new()));
+        for input in [
+            Input::deposit(1, 1, dec!(100)),
+            Input::deposit(2, 2, dec!(10)),
+            Input::withdrawal(1, 3, dec!(30)),
+            Input::transfer(1, 2, 4, dec!(20)),
+            Input::dispute(2, 2),
+            Input::chargeback(2, 2),
+        ] {
+            ledger.process_trx(&input).expect("failed to process input");
+        }
+        let events = ledger
+            .events()
+            .map(|record| record.map(Cow::into_owned))
+            .collect::<io::Result<Vec<_>>>()
+            .expect("failed to read events");
 
-        for (index, invalid_input) in invalid_inputs.iter().enumerate() {
-            let index = index
-                .to_u32()
-                .expect("error while converting from usize to u32");
+        let mut rebuilt = Ledger::from_events(LedgerConfig::default(), Box::new(events))
+            .expect("failed to rebuild ledger");
 
-            // build pre-conditions but inserting a erroneous transaction in the ledger
-            let trx = Transaction::new(invalid_input);
-            let mut ledger = Ledger::new();
-            ledger.trx.insert(index, trx);
+        assert_eq!(*rebuilt.account(1).unwrap(), *ledger.account(1).unwrap());
+        assert_eq!(*rebuilt.account(2).unwrap(), *ledger.account(2).unwrap());
+        assert_eq!(rebuilt.account(2).unwrap().status(), AccountStatus::Locked);
+        // the rebuilt ledger continues from the balances and appends to the same store.
+        rebuilt
+            .process_trx(&Input::withdrawal(1, 5, dec!(50)))
+            .expect("failed to withdraw");
+        assert_eq!(rebuilt.account(1).unwrap().balance(None).available, dec!(0));
+        assert_eq!(rebuilt.events().count(), ledger.events().count() + 1);
+    }
 
-            let input_dispute = input(Type::Dispute, 0, index, None);
-            let result = ledger.process_trx(&input_dispute);
-            assert!(result.is_err());
-            assert_eq!(
-                format!("{}", result.unwrap_err()),
-                EngineError::TrxNotDisputable.to_string()
-            );
+    struct FailingEventStore;
+
+    impl EventStore for FailingEventStore {
+        fn append(&mut self, _: Vec<EventRecord>) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+
+        fn events(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, EventRecord>>> + '_> {
+            Box::new(std::iter::empty())
         }
     }
 
     #[test]
-    fn process_trx_dispute_fail_when_transaction_to_be_disputed_is_not_in_a_disputable_state() {
-        let input_invalid = input(Type::Deposit, 0, 1, None);
-        let input_dispute = input(Type::Dispute, 0, 1, None);
+    fn process_trx_fail_when_event_store_fails() {
+        let client = 1;
 
-        // build pre-conditions but inserting a erroneous transaction in the ledger
-        let mut trx = Transaction::new(&input_invalid);
-        trx.state = State::Chargeback;
+        let mut ledger = Ledger::new().with_event_store(Box::new(FailingEventStore));
 
-        let mut ledger = Ledger::new();
-        ledger.trx.insert(1, trx);
+        let result = ledger.process_trx(&input(Type::Deposit, client, 1, Some(dec!(10))));
 
-        let result = ledger.process_trx(&input_dispute);
-        assert!(result.is_err());
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxNotInDisputableState.to_string()
+            result.unwrap_err(),
+            EngineError::EventStoreFailure {
+                tx: 1,
+                reason: "disk full".to_string()
+            }
         );
+        assert!(ledger.account(client).is_none());
+        assert!(ledger.transaction(1).is_none());
     }
+
     #[test]
-    fn process_trx_resolve_fail_when_disputed_trx_not_found() {
-        let tx = 123456789;
-        let client = 1234;
-        let input = input(Type::Resolve, client, tx, None);
+    fn restore_snapshot_resumes_processing() {
+        let client = 1;
 
-        let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
+        let mut ledger = ledger_with_hold_lifetime(100);
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)).with_timestamp(0))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::deposit(client, 2, dec!(5)).with_timestamp(5))
+            .expect("failed to deposit");
+        ledger
+            .process_trx(&Input::dispute(client, 1).with_timestamp(10))
+            .expect("failed to dispute");
+        let mut snapshot = Vec::new();
+        ledger
+            .write_snapshot(&mut snapshot)
+            .expect("failed to write snapshot");
 
-        assert!(result.is_err());
+        let mut restored = ledger_with_hold_lifetime(100);
+        restored
+            .restore(snapshot.as_slice())
+            .expect("failed to restore snapshot");
+
+        assert_eq!(restored.clock(), Some(10));
+        assert_eq!(restored.sequence(), 3);
+        assert_eq!(restored.account(client), ledger.account(client));
+        assert_eq!(restored.transaction(1).unwrap().state, State::Disputed);
+        // processed transactions are still known.
+        let result = restored.process_trx(&Input::deposit(client, 2, dec!(5)));
         assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxNotFound.to_string()
+            result.unwrap_err(),
+            EngineError::TrxAlreadyProcessed { tx: 2 }
         );
+        // the hold of the restored dispute still expires.
+        restored
+            .process_trx(&Input::deposit(client, 3, dec!(1)).with_timestamp(110))
+            .expect("failed to deposit");
+        let account = restored.account(client).expect("account not found");
+        assert_eq!(account.balance(None).available, dec!(16));
+        assert_eq!(account.balance(None).held, dec!(0));
     }
 
     #[test]
-    fn process_trx_resolve_fail_when_resolved_trx_not_in_dispute_state() {
-        let tx = 123456789;
-        let client = 1234;
-        let amount = Some(dec!(1500));
-        let input_deposit = input(Type::Deposit, client, tx, amount);
-        let input_resolve = input(Type::Resolve, client, tx, None);
-
+    fn restore_snapshot_fail_when_version_unsupported() {
         let mut ledger = Ledger::new();
-        // load balance in the account, so it can be disputed
-        let result = ledger.process_trx(&input_deposit);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client));
 
-        let result = ledger.process_trx(&input_resolve);
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxNotInDispute.to_string()
+        let result = ledger.restore(
+            r#"{"version":2,"accounts":[{"client":1,"balances":[],"status":"active"}]}"#.as_bytes(),
         );
-    }
 
-    #[test]
-    fn process_trx_resolve_fail_when_resolved_trx_belongs_to_another_user() {
-        let tx = 123456789;
-        let client = 1234;
-        let client_deposit = 1;
-        let amount = Some(dec!(1500));
-        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
-        let input_dispute = input(Type::Dispute, client_deposit, tx, None);
-        let input_resolve = input(Type::Resolve, client, tx, None);
+        assert!(matches!(
+            result.unwrap_err(),
+            SnapshotError::UnsupportedVersion {
+                version: 2,
+                supported: 1
+            }
+        ));
+        assert!(ledger.account(1).is_none());
+    }
 
-        let mut ledger = Ledger::new();
-        // load balance in the account, so it can be disputed
-        let result = ledger.process_trx(&input_deposit);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
+    /// Writer accepting every write, failing once flushed.
+    struct FailingFlush;
 
-        // dispute transaction, so it can be resolved
-        let result = ledger.process_trx(&input_dispute);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
 
-        let result = ledger.process_trx(&input_resolve);
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxClientIdInconsistency.to_string()
-        );
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
     }
 
     #[test]
-    fn process_trx_chargeback_fail_when_disputed_trx_not_found() {
-        let tx = 123456789;
-        let client = 1234;
-        let input = input(Type::Chargeback, client, tx, None);
+    fn write_snapshot_fail_when_flush_fails() {
+        let ledger = Ledger::new();
+
+        let result = ledger.write_snapshot(std::io::BufWriter::new(FailingFlush));
+
+        assert!(matches!(result, Err(SnapshotError::Io(_))));
+    }
 
+    #[test]
+    fn restore_snapshot_fail_when_snapshot_invalid() {
         let mut ledger = Ledger::new();
-        let result = ledger.process_trx(&input);
 
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxNotFound.to_string()
-        );
+        let result = ledger.restore(r#"{"version":1,"accounts":"#.as_bytes());
+
+        assert!(matches!(result.unwrap_err(), SnapshotError::Format(_)));
     }
 
     #[test]
-    fn process_trx_chargeback_fail_when_chargeback_trx_belongs_to_another_user() {
-        let tx = 123456789;
-        let client = 1234;
-        let client_deposit = 1;
-        let amount = Some(dec!(1500));
-        let input_deposit = input(Type::Deposit, client_deposit, tx, amount);
-        let input_dispute = input(Type::Dispute, client_deposit, tx, None);
-        let input_resolve = input(Type::Resolve, client_deposit, tx, None);
-        let input_chargeback = input(Type::Chargeback, client, tx, None);
+    fn ledger_serde_round_trip() {
+        let client = 1;
 
         let mut ledger = Ledger::new();
-        // load balance in the account, so it can be disputed
-        let result = ledger.process_trx(&input_deposit);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
-
-        // dispute transaction, so it can be resolved
-        let result = ledger.process_trx(&input_dispute);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
+        ledger
+            .process_trx(&Input::deposit(client, 1, dec!(10)))
+            .expect("failed to deposit");
 
-        // resolve transaction, so it can be chargeback
-        let result = ledger.process_trx(&input_resolve);
-        // validate that preconditions are verified (deposit was successful)
-        assert!(result.is_ok());
-        assert!(ledger.trx.contains_key(&tx));
-        assert!(ledger.accounts.contains_key(&client_deposit));
+        let snapshot = serde_json::to_string(&ledger).expect("failed to serialize ledger");
+        let restored: Ledger =
+            serde_json::from_str(&snapshot).expect("failed to deserialize ledger");
 
-        let result = ledger.process_trx(&input_chargeback);
-        assert!(result.is_err());
-        assert_eq!(
-            format!("{}", result.unwrap_err()),
-            EngineError::TrxClientIdInconsistency.to_string()
-        );
+        assert_eq!(restored.account(client), ledger.account(client));
+        assert_eq!(restored.transaction(1).unwrap().amount, Some(dec!(10)));
     }
 }
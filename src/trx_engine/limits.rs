@@ -0,0 +1,86 @@
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Credit limit of each client: how far below zero a withdrawal can take the available funds of the account.
+/// Clients without a limit cannot overdraw their account.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CreditLimits {
+    limits: HashMap<u16, Decimal>,
+}
+
+/// CreditLimitRecord is a line of the client config file.
+#[derive(serde::Deserialize)]
+struct CreditLimitRecord {
+    #[serde(rename = "client")]
+    client: u16,
+
+    #[serde(rename = "credit_limit", deserialize_with = "non_negative")]
+    credit_limit: Decimal,
+}
+
+fn non_negative<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    let limit = <Decimal as serde::Deserialize>::deserialize(deserializer)?;
+    if limit.is_sign_negative() {
+        return Err(serde::de::Error::custom(format!(
+            "credit limit cannot be negative: {limit}"
+        )));
+    }
+
+    Ok(limit)
+}
+
+impl CreditLimits {
+    /// Reads the limits of a csv file with a `client,credit_limit` header.
+    /// A client listed more than once keeps its last limit.
+    /// # Errors
+    /// * An error is returned if any line cannot be read or parsed, or has a negative limit.
+    ///
+    pub fn from_reader<R: Read>(mut rdr: csv::Reader<R>) -> Result<Self, csv::Error> {
+        let mut limits = Self::default();
+        for record in rdr.deserialize() {
+            let record: CreditLimitRecord = record?;
+            limits.set(record.client, record.credit_limit);
+        }
+
+        Ok(limits)
+    }
+
+    pub fn set(&mut self, client: u16, credit_limit: Decimal) {
+        self.limits.insert(client, credit_limit);
+    }
+
+    /// Credit limit of the provided client, zero if the client has none.
+    pub fn limit(&self, client: u16) -> Decimal {
+        self.limits.get(&client).copied().unwrap_or(Decimal::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::Trim::All;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn credit_limits_from_reader_successful() {
+        let input = "client, credit_limit\n1, 100\n2, 50.5\n1, 200\n";
+        let rdr = csv::ReaderBuilder::new()
+            .trim(All)
+            .from_reader(input.as_bytes());
+
+        let limits = CreditLimits::from_reader(rdr).expect("failed to read credit limits");
+
+        assert_eq!(limits.limit(1), dec!(200));
+        assert_eq!(limits.limit(2), dec!(50.5));
+        assert_eq!(limits.limit(3), dec!(0));
+    }
+
+    #[test]
+    fn credit_limits_from_reader_fails_on_negative_limit() {
+        let input = "client,credit_limit\n1,-100\n";
+        let rdr = csv::Reader::from_reader(input.as_bytes());
+
+        assert!(CreditLimits::from_reader(rdr).is_err());
+    }
+}
@@ -1,45 +1,436 @@
-use crate::trx_engine::ledger::Ledger;
+use crate::trx_engine::account_store::AccountStore;
+use crate::trx_engine::errors::SnapshotError;
+use crate::trx_engine::event_store::EventStore;
+use crate::trx_engine::journal;
+use crate::trx_engine::journal::Journal;
+use crate::trx_engine::ledger::{Ledger, LedgerConfig};
+use crate::trx_engine::rates::RateProvider;
+use crate::trx_engine::transaction::Input;
+use crate::trx_engine::transaction_store::TransactionStore;
 use log::{info, warn};
 use std::io::{Read, Write};
 
+/// Error code reported for the lines that could not be parsed into an `Input`.
+pub const INVALID_RECORD_CODE: &str = "invalid_record";
+
+/// Policy deciding how many rejected lines are tolerated before the whole run is aborted.
+/// When a run is aborted no accounts report is written.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum ErrorPolicy {
+    /// Every rejected line is skipped and the run always completes.
+    #[default]
+    Skip,
+    /// The run is aborted on the first rejected line.
+    Strict,
+    /// The run is aborted as soon as more than the provided number of lines are rejected.
+    MaxRejections(u64),
+    /// The run is aborted if, once all lines are read, more than the provided percentage of them were rejected.
+    /// The percentage must be between 0 and 100, see `ErrorPolicy::validate`.
+    MaxRejectedPercent(f64),
+}
+
+impl ErrorPolicy {
+    /// Validates the budget before any line is read.
+    /// # Errors
+    /// * An error is returned if the percentage of `MaxRejectedPercent` is not a number between 0 and 100.
+    ///
+    pub fn validate(&self) -> Result<(), ProcessError> {
+        match self {
+            Self::MaxRejectedPercent(max) if !(0.0..=100.0).contains(max) => {
+                Err(ProcessError::InvalidErrorPolicy(*self))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validates the budget after each rejected line.
+    /// # Errors
+    /// * An error is returned if the rejection exhausts the budget.
+    ///
+    fn on_rejection(
+        &self,
+        rejected: u64,
+        read: u64,
+        rejection: Rejection,
+    ) -> Result<(), ProcessError> {
+        match self {
+            Self::Strict => Err(ProcessError::Rejected(rejection)),
+            Self::MaxRejections(max) if rejected > *max => {
+                Err(ProcessError::ErrorBudgetExceeded { rejected, read })
+            }
+            Self::Skip | Self::MaxRejections(_) | Self::MaxRejectedPercent(_) => Ok(()),
+        }
+    }
+
+    /// Validates the budget once all lines are read.
+    /// # Errors
+    /// * An error is returned if the rejected lines exceed the budget.
+    ///
+    fn on_completion(&self, rejected: u64, read: u64) -> Result<(), ProcessError> {
+        match self {
+            Self::MaxRejectedPercent(max) if read > 0 => {
+                // precision loss is irrelevant for a percentage threshold.
+                let percent = rejected as f64 * 100.0 / read as f64;
+                if percent > *max {
+                    return Err(ProcessError::ErrorBudgetExceeded { rejected, read });
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Options of `process_transactions_file_with_options`.
+#[derive(Default)]
+pub struct ProcessOptions<'a> {
+    /// Writer receiving the rejected-transactions report (one csv row per rejected line).
+    /// If not provided, rejections are only logged.
+    pub rejections: Option<Box<dyn Write + 'a>>,
+
+    pub error_policy: ErrorPolicy,
+
+    /// Business rules applied by the ledger the file is processed with.
+    pub ledger: LedgerConfig,
+
+    /// Rates of the exchanges that do not carry their own rate.
+    pub rates: Option<Box<dyn RateProvider>>,
+
+    /// Store of the processed transactions, they are kept in memory if not provided.
+    pub transaction_store: Option<Box<dyn TransactionStore>>,
+
+    /// Store of the accounts, e.g. to continue from the accounts of a previous run.
+    /// They are kept in memory if not provided.
+    pub account_store: Option<Box<dyn AccountStore>>,
+
+    /// Snapshot restored before the file is processed, e.g. the closing snapshot of the previous day.
+    pub restore: Option<Box<dyn Read + 'a>>,
+
+    /// Writer receiving a snapshot of the ledger once the file is processed.
+    /// As for the report, no snapshot is written when the run is aborted.
+    pub snapshot: Option<Box<dyn Write + 'a>>,
+
+    /// Journal recording every accepted input, so a run that stops midway can be recovered.
+    /// It is not truncated by the processor: the caller should truncate it once the file was processed
+    /// and the snapshot written, if any, is in place, see `Ledger::truncate_journal`. The inputs the account
+    /// or event store persisted before it is truncated are skipped on recovery, see `Ledger::recover`.
+    pub journal: Option<Box<dyn Journal>>,
+
+    /// Journal left by a run that stopped midway, replayed once the snapshot is restored.
+    /// The input file is then read from the line following the last journaled one,
+    /// so the run must be given the same file and options as the one that stopped.
+    pub recover: Option<Box<dyn Read + 'a>>,
+
+    /// Store of the domain events recorded for every accepted input, they are discarded if not provided.
+    /// The accounts are rebuilt from the events it holds, see `Ledger::from_events`, but not the transactions,
+    /// which start empty. It cannot be used along with an account store or a snapshot to restore,
+    /// which would provide the accounts a second time.
+    pub event_store: Option<Box<dyn EventStore>>,
+}
+
+/// Rejection represents a line of the rejected-transactions report.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Rejection {
+    /// Line of the input file the rejected record starts at.
+    #[serde(rename = "line")]
+    pub line: u64,
+
+    /// Stable error code, see `EngineError::code`.
+    #[serde(rename = "code")]
+    pub code: String,
+
+    #[serde(rename = "reason")]
+    pub reason: String,
+
+    /// Fields of the rejected record, trimmed and joined by commas.
+    #[serde(rename = "record")]
+    pub record: String,
+}
+
+/// Reasons for `process_transactions_file` to abort the processing of a file.
+/// Rejections of single inputs are not part of this enum, those are reported as `EngineError`.
+#[derive(Debug)]
+pub enum ProcessError {
+    Csv(csv::Error),
+    /// A line was rejected while processing in strict mode.
+    Rejected(Rejection),
+    /// The number of rejected lines exceeded the configured error budget.
+    ErrorBudgetExceeded {
+        rejected: u64,
+        read: u64,
+    },
+    /// The account store failed to read the accounts to report.
+    AccountStore(std::io::Error),
+    /// The snapshot of the ledger could not be restored or written.
+    Snapshot(SnapshotError),
+    /// The journal to recover from could not be read.
+    Journal(std::io::Error),
+    /// The events to rebuild the accounts from could not be read or applied.
+    EventStore(std::io::Error),
+    /// The account or event store could not be persisted once the file was processed.
+    Persist(std::io::Error),
+    /// The event store was provided along with an account store or a snapshot, which would provide the accounts twice.
+    EventStoreWithAccounts,
+    /// The error policy has a budget that cannot be applied, see `ErrorPolicy::validate`.
+    InvalidErrorPolicy(ErrorPolicy),
+}
+
+impl std::fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv(err) => write!(f, "failed to read or write csv: {err}"),
+            Self::Rejected(rejection) => write!(
+                f,
+                "line {} rejected in strict mode: {} ({})",
+                rejection.line, rejection.reason, rejection.code
+            ),
+            Self::ErrorBudgetExceeded { rejected, read } => write!(
+                f,
+                "error budget exceeded: {rejected} of {read} lines rejected"
+            ),
+            Self::AccountStore(err) => write!(f, "failed to read the account store: {err}"),
+            Self::Snapshot(err) => write!(f, "failed to restore or write snapshot: {err}"),
+            Self::Journal(err) => write!(f, "failed to recover journal: {err}"),
+            Self::EventStore(err) => write!(f, "failed to rebuild accounts from events: {err}"),
+            Self::Persist(err) => write!(f, "failed to persist the account or event store: {err}"),
+            Self::EventStoreWithAccounts => write!(
+                f,
+                "an event store cannot be used with an account store or a snapshot, accounts are rebuilt from its events"
+            ),
+            Self::InvalidErrorPolicy(policy) => write!(
+                f,
+                "invalid error policy: {policy:?}, a rejected percentage must be between 0 and 100"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Csv(err) => Some(err),
+            Self::AccountStore(err)
+            | Self::Journal(err)
+            | Self::EventStore(err)
+            | Self::Persist(err) => Some(err),
+            Self::Snapshot(err) => Some(err),
+            Self::Rejected(_)
+            | Self::ErrorBudgetExceeded { .. }
+            | Self::EventStoreWithAccounts
+            | Self::InvalidErrorPolicy(_) => None,
+        }
+    }
+}
+
+impl From<csv::Error> for ProcessError {
+    fn from(err: csv::Error) -> Self {
+        Self::Csv(err)
+    }
+}
+
+impl From<SnapshotError> for ProcessError {
+    fn from(err: SnapshotError) -> Self {
+        Self::Snapshot(err)
+    }
+}
+
 pub fn process_transactions_file<T: Read, U: Write>(
+    rdr: csv::Reader<T>,
+    writer: U,
+) -> Result<(), ProcessError> {
+    process_transactions_file_with_options(rdr, writer, ProcessOptions::default())
+}
+
+pub fn process_transactions_file_with_options<T: Read, U: Write>(
     mut rdr: csv::Reader<T>,
     writer: U,
-) -> anyhow::Result<()> {
-    let mut ledger = Ledger::new();
+    options: ProcessOptions,
+) -> Result<(), ProcessError> {
+    options.error_policy.validate()?;
 
-    for result in rdr.deserialize() {
-        let trx_input = match result {
-            Ok(input) => input,
-            Err(e) => {
-                info!("failed to parse input from csv: {:?}", e);
+    if options.event_store.is_some()
+        && (options.account_store.is_some() || options.restore.is_some())
+    {
+        return Err(ProcessError::EventStoreWithAccounts);
+    }
 
-                // ignore lines with parsing errors.
-                continue;
-            }
+    let precision = options.ledger.precision.clone();
+    let mut ledger = match options.event_store {
+        Some(store) => {
+            Ledger::from_events(options.ledger, store).map_err(ProcessError::EventStore)?
+        }
+        None => Ledger::with_config(options.ledger),
+    };
+    if let Some(rates) = options.rates {
+        ledger = ledger.with_rate_provider(rates);
+    }
+    if let Some(store) = options.transaction_store {
+        ledger = ledger.with_transaction_store(store);
+    }
+    if let Some(store) = options.account_store {
+        ledger = ledger.with_account_store(store);
+    }
+    if let Some(snapshot) = options.restore {
+        ledger.restore(snapshot)?;
+    }
+    let resume_after = match options.recover {
+        Some(entries) => ledger
+            .recover(journal::read_entries(entries))
+            .map_err(ProcessError::Journal)?,
+        None => None,
+    };
+    if let Some(journal) = options.journal {
+        ledger = ledger.with_journal(journal);
+    }
+    let mut rejections = options.rejections.map(csv::Writer::from_writer);
+
+    // records are read raw and deserialized afterward, so rejected lines can be reported as they were read.
+    let headers = rdr.headers()?.clone();
+
+    let mut read: u64 = 0;
+    let mut rejected: u64 = 0;
+    for result in rdr.records() {
+        // the lines up to the last journaled one were applied by the recovered run.
+        let position = match &result {
+            Ok(record) => record.position(),
+            Err(e) => e.position(),
         };
+        let line = position.map(|position| position.line());
+        if line
+            .zip(resume_after)
+            .is_some_and(|(line, resume_after)| line <= resume_after)
+        {
+            continue;
+        }
 
-        match ledger.process_trx(&trx_input) {
-            Ok(_) => {}
-            Err(e) => {
-                warn!(
-                    "failed to execute transaction: {:?} with error: {:?}",
-                    trx_input, e
-                );
+        read += 1;
 
-                // ignore inputs with business logic errors.
-                continue;
-            }
+        let Err(rejection) = process_record(&mut ledger, &headers, result) else {
+            continue;
+        };
+
+        rejected += 1;
+        report_rejection(&mut rejections, &rejection)?;
+
+        if let Err(e) = options.error_policy.on_rejection(rejected, read, rejection) {
+            flush_rejections(&mut rejections)?;
+            return Err(e);
         }
     }
 
-    // write result to the provided writer.
+    flush_rejections(&mut rejections)?;
+    options.error_policy.on_completion(rejected, read)?;
+
+    if let Some(snapshot) = options.snapshot {
+        ledger.write_snapshot(snapshot)?;
+    }
+
+    // write result to the provided writer, one row per client per currency rounded to its precision.
     let mut output = csv::Writer::from_writer(writer);
-    ledger
-        .get_accounts()
-        .iter()
-        //.try_for_each(|(_, account)| output.serialize(account.format_account_precision_of_decimals_for_report()))?;
-        .try_for_each(|(_, account)| output.serialize(account))?;
+    for account in ledger.account_store().accounts() {
+        let account = account.map_err(ProcessError::AccountStore)?;
+        account
+            .format_account_precision_of_decimals_for_report(&precision)
+            .records()
+            .iter()
+            .try_for_each(|record| output.serialize(record))?;
+    }
+    output.flush().map_err(csv::Error::from)?;
+
+    // the file was processed completely, so its changes outlive the run.
+    // the journal is kept, the caller truncates it once the snapshot is in place as well.
+    ledger.checkpoint().map_err(ProcessError::Persist)?;
+
+    Ok(())
+}
+
+/// Helper function to parse a single record and apply it to the ledger.
+/// # Errors
+/// * A rejection is returned if the record cannot be read, parsed or is rejected by the ledger.
+///
+fn process_record(
+    ledger: &mut Ledger,
+    headers: &csv::StringRecord,
+    result: csv::Result<csv::StringRecord>,
+) -> Result<(), Rejection> {
+    let record = match result {
+        Ok(record) => record,
+        Err(e) => {
+            info!("failed to read record from csv: {:?}", e);
+
+            // ignore lines that cannot be read.
+            return Err(Rejection {
+                line: e.position().map_or(0, |position| position.line()),
+                code: INVALID_RECORD_CODE.to_string(),
+                reason: e.to_string(),
+                record: String::new(),
+            });
+        }
+    };
+
+    let line = record.position().map_or(0, |position| position.line());
+
+    let trx_input: Input = match record.deserialize(Some(headers)) {
+        Ok(input) => input,
+        Err(e) => {
+            info!("failed to parse input from csv: {:?}", e);
+
+            // ignore lines with parsing errors.
+            return Err(Rejection {
+                line,
+                code: INVALID_RECORD_CODE.to_string(),
+                reason: e.to_string(),
+                record: record.iter().collect::<Vec<_>>().join(","),
+            });
+        }
+    };
+
+    let result = ledger.process_trx_at_line(&trx_input, line);
+
+    // the fees and synthetic events are only logged, so they do not pile up in the ledger for the whole file.
+    for fee in ledger.drain_fee_transactions() {
+        info!("fee posted: {:?}", fee);
+    }
+    for event in ledger.drain_synthetic_events() {
+        info!("synthetic event applied: {:?}", event);
+    }
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            warn!(
+                "failed to execute transaction: {:?} with error: {:?}",
+                trx_input, e
+            );
+
+            // ignore inputs with business logic errors.
+            Err(Rejection {
+                line,
+                code: e.code().to_string(),
+                reason: e.to_string(),
+                record: record.iter().collect::<Vec<_>>().join(","),
+            })
+        }
+    }
+}
+
+/// Helper function to append a rejection to the report, if one was requested.
+fn report_rejection<W: Write>(
+    rejections: &mut Option<csv::Writer<W>>,
+    rejection: &Rejection,
+) -> Result<(), ProcessError> {
+    if let Some(rejections) = rejections.as_mut() {
+        rejections.serialize(rejection)?;
+    }
+
+    Ok(())
+}
+
+fn flush_rejections<W: Write>(rejections: &mut Option<csv::Writer<W>>) -> Result<(), ProcessError> {
+    if let Some(rejections) = rejections.as_mut() {
+        rejections.flush().map_err(csv::Error::from)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,91 @@
+use crate::trx_engine::currency::Currency;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Source of the exchange rates used by `exchange` inputs that do not carry their own rate.
+pub trait RateProvider {
+    /// Amount of `to` received for one unit of `from`, if the rate is known.
+    fn rate(&self, from: Currency, to: Currency) -> Option<Decimal>;
+}
+
+/// Rates read from a csv file with a `from,to,rate` header, one row per currency pair.
+/// A pair is only known in the direction it is listed in, inverse rates are never derived.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CsvRateProvider {
+    rates: HashMap<(Currency, Currency), Decimal>,
+}
+
+/// RateRecord is a line of the rates file.
+#[derive(serde::Deserialize)]
+struct RateRecord {
+    #[serde(rename = "from")]
+    from: Currency,
+
+    #[serde(rename = "to")]
+    to: Currency,
+
+    #[serde(rename = "rate")]
+    rate: Decimal,
+}
+
+impl CsvRateProvider {
+    /// Reads every rate of the provided reader. A pair listed more than once keeps its last rate.
+    /// # Errors
+    /// * An error is returned if any line cannot be read or parsed.
+    ///
+    pub fn from_reader<R: Read>(mut rdr: csv::Reader<R>) -> Result<Self, csv::Error> {
+        let mut rates = HashMap::new();
+        for record in rdr.deserialize() {
+            let record: RateRecord = record?;
+            rates.insert((record.from, record.to), record.rate);
+        }
+
+        Ok(Self { rates })
+    }
+}
+
+impl RateProvider for CsvRateProvider {
+    fn rate(&self, from: Currency, to: Currency) -> Option<Decimal> {
+        self.rates.get(&(from, to)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csv::Trim::All;
+    use rust_decimal_macros::dec;
+
+    fn currency(code: &str) -> Currency {
+        code.parse().expect("failed to parse currency")
+    }
+
+    #[test]
+    fn csv_rate_provider_from_reader_successful() {
+        let input = "from, to, rate\nEUR, USD, 1.1\nusd, EUR, 0.9\nEUR, USD, 1.2\n";
+        let rdr = csv::ReaderBuilder::new()
+            .trim(All)
+            .from_reader(input.as_bytes());
+
+        let rates = CsvRateProvider::from_reader(rdr).expect("failed to read rates");
+
+        assert_eq!(
+            rates.rate(currency("EUR"), currency("USD")),
+            Some(dec!(1.2))
+        );
+        assert_eq!(
+            rates.rate(currency("USD"), currency("EUR")),
+            Some(dec!(0.9))
+        );
+        assert_eq!(rates.rate(currency("EUR"), currency("GBP")), None);
+    }
+
+    #[test]
+    fn csv_rate_provider_from_reader_fails_on_invalid_line() {
+        let input = "from,to,rate\nEUR,USD,abc\n";
+        let rdr = csv::Reader::from_reader(input.as_bytes());
+
+        assert!(CsvRateProvider::from_reader(rdr).is_err());
+    }
+}
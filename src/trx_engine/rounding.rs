@@ -0,0 +1,155 @@
+use crate::trx_engine::currency::Currency;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Strategy used to round an amount to the precision of its currency.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum RoundingMode {
+    /// Rounds half-way values to the nearest even digit, so rounding errors do not add up.
+    #[default]
+    Bankers,
+    /// Rounds half-way values away from zero.
+    HalfUp,
+    /// Drops the extra digits.
+    Truncate,
+}
+
+impl RoundingMode {
+    pub fn round(self, amount: Decimal, decimal_places: u32) -> Decimal {
+        let strategy = match self {
+            Self::Bankers => RoundingStrategy::MidpointNearestEven,
+            Self::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            Self::Truncate => RoundingStrategy::ToZero,
+        };
+
+        amount.round_dp_with_strategy(decimal_places, strategy)
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bankers" => Ok(Self::Bankers),
+            "half-up" => Ok(Self::HalfUp),
+            "truncate" => Ok(Self::Truncate),
+            _ => Err(format!("unknown rounding mode: {s}")),
+        }
+    }
+}
+
+/// Precision of the amounts of a currency and rounding mode applied to the amounts computed, or reported, in it.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Rounding {
+    pub decimal_places: u32,
+    pub mode: RoundingMode,
+}
+
+impl Default for Rounding {
+    /// Four decimal places, the precision of the inputs, rounded with the banker's rounding.
+    fn default() -> Self {
+        Self {
+            decimal_places: 4,
+            mode: RoundingMode::Bankers,
+        }
+    }
+}
+
+impl Rounding {
+    pub fn round(&self, amount: Decimal) -> Decimal {
+        self.mode.round(amount, self.decimal_places)
+    }
+
+    /// An amount is allowed if it has no more decimal places than the precision, trailing zeros aside.
+    pub fn allows(&self, amount: Decimal) -> bool {
+        amount.normalize().scale() <= self.decimal_places
+    }
+}
+
+/// Precision of the amounts of each currency.
+/// Currencies without an entry, and amounts without a currency, use the default precision.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PrecisionPolicy {
+    pub default: Rounding,
+    pub currencies: HashMap<Currency, Rounding>,
+}
+
+impl PrecisionPolicy {
+    pub fn rounding(&self, currency: Option<Currency>) -> Rounding {
+        currency
+            .and_then(|currency| self.currencies.get(&currency))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn round_with_every_mode() {
+        let cases = [
+            (RoundingMode::Bankers, dec!(1.125), dec!(1.12)),
+            (RoundingMode::Bankers, dec!(1.135), dec!(1.14)),
+            (RoundingMode::HalfUp, dec!(1.125), dec!(1.13)),
+            (RoundingMode::HalfUp, dec!(-1.125), dec!(-1.13)),
+            (RoundingMode::Truncate, dec!(1.129), dec!(1.12)),
+            (RoundingMode::Truncate, dec!(-1.129), dec!(-1.12)),
+        ];
+
+        for (mode, amount, expected) in cases {
+            assert_eq!(mode.round(amount, 2), expected, "{mode:?} of {amount}");
+        }
+    }
+
+    #[test]
+    fn round_keeps_amounts_within_precision() {
+        let rounding = Rounding::default();
+
+        assert_eq!(rounding.round(dec!(1.5)), dec!(1.5));
+        assert_eq!(rounding.round(dec!(1.23456)), dec!(1.2346));
+    }
+
+    #[test]
+    fn allows_amounts_within_precision() {
+        let rounding = Rounding {
+            decimal_places: 2,
+            mode: RoundingMode::Bankers,
+        };
+
+        assert!(rounding.allows(dec!(1)));
+        assert!(rounding.allows(dec!(1.25)));
+        assert!(rounding.allows(dec!(1.2500)));
+        assert!(!rounding.allows(dec!(1.251)));
+    }
+
+    #[test]
+    fn precision_policy_falls_back_to_default() {
+        let jpy: Currency = "JPY".parse().unwrap();
+        let eur: Currency = "EUR".parse().unwrap();
+        let yen = Rounding {
+            decimal_places: 0,
+            mode: RoundingMode::Truncate,
+        };
+        let policy = PrecisionPolicy {
+            currencies: HashMap::from([(jpy, yen)]),
+            ..Default::default()
+        };
+
+        assert_eq!(policy.rounding(Some(jpy)), yen);
+        assert_eq!(policy.rounding(Some(eur)), Rounding::default());
+        assert_eq!(policy.rounding(None), Rounding::default());
+    }
+
+    #[test]
+    fn parse_rounding_mode() {
+        assert_eq!("bankers".parse(), Ok(RoundingMode::Bankers));
+        assert_eq!("half-up".parse(), Ok(RoundingMode::HalfUp));
+        assert_eq!("truncate".parse(), Ok(RoundingMode::Truncate));
+        assert!("up".parse::<RoundingMode>().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::trx_engine::account::Account;
+use crate::trx_engine::account_store::AccountStore;
+use crate::trx_engine::transaction::Transaction;
+use crate::trx_engine::transaction_store::TransactionStore;
+use serde::de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+/// Version of the snapshot format written by this version of the engine.
+///
+/// A snapshot is a json document holding the state of the ledger:
+/// ```json
+/// {
+///   "version": 1,
+///   "clock": 1700000000,
+///   "sequence": 42,
+///   "accounts": [{ "client": 1, "balances": [[null, { "available": "10", "held": "0", "total": "10" }]], ... }],
+///   "transactions": [{ "tx": 1, "type": "deposit", "client": 1, "amount": "10", "state": "ok", ... }]
+/// }
+/// ```
+/// `clock` is `null` if no input carried a timestamp, `sequence` is the sequence of the last input the snapshot holds,
+/// see `Ledger::recover`, it is optional and missing in older snapshots.
+/// Accounts and transactions are serialized whole, in no particular order.
+///
+/// The format evolves as follows:
+/// * `version` is always the first field, so a snapshot is rejected before anything is restored
+///   if it was written by a newer version of the format.
+/// * Fields added without changing the version must be optional, so older snapshots can still be restored.
+///   Unknown fields are ignored, so older engines can restore the snapshots of newer ones of the same version.
+/// * Any other change increases the version, and snapshots of every previous version can still be restored.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Whether snapshots of the provided version can be restored.
+pub(crate) fn is_supported(version: u32) -> bool {
+    (1..=SNAPSHOT_VERSION).contains(&version)
+}
+
+/// State of the ledger, serialized in the snapshot format.
+pub(crate) struct SnapshotOf<'a> {
+    pub(crate) clock: Option<u64>,
+    pub(crate) sequence: u64,
+    pub(crate) accounts: &'a dyn AccountStore,
+    pub(crate) trx: &'a dyn TransactionStore,
+}
+
+impl Serialize for SnapshotOf<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut snapshot = serializer.serialize_struct("Snapshot", 5)?;
+        snapshot.serialize_field("version", &SNAPSHOT_VERSION)?;
+        snapshot.serialize_field("clock", &self.clock)?;
+        snapshot.serialize_field("sequence", &self.sequence)?;
+        snapshot.serialize_field("accounts", &Entries::new(self.accounts.accounts()))?;
+        snapshot.serialize_field("transactions", &Entries::new(self.trx.transactions()))?;
+        snapshot.end()
+    }
+}
+
+type EntryIter<'a, T> = Box<dyn Iterator<Item = io::Result<Cow<'a, T>>> + 'a>;
+
+/// Entries of a store, serialized as they are read so a store larger than memory can be written whole.
+/// The entries can only be serialized once.
+struct Entries<'a, T: Clone>(Cell<Option<EntryIter<'a, T>>>);
+
+impl<'a, T: Clone> Entries<'a, T> {
+    fn new(entries: EntryIter<'a, T>) -> Self {
+        Self(Cell::new(Some(entries)))
+    }
+}
+
+impl<T: Clone + Serialize> Serialize for Entries<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self
+            .0
+            .take()
+            .ok_or_else(|| serde::ser::Error::custom("entries were already serialized"))?;
+
+        let mut seq = serializer.serialize_seq(None)?;
+        for entry in entries {
+            seq.serialize_element(&entry.map_err(serde::ser::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+/// Restores a snapshot, handing every account and transaction over as soon as it is read,
+/// so a snapshot larger than memory can be restored into stores that are not kept in memory.
+pub(crate) struct SnapshotInto<'a> {
+    pub(crate) clock: &'a mut Option<u64>,
+    /// Sequence of the last input the snapshot holds, left as is if the snapshot has none.
+    pub(crate) sequence: &'a mut u64,
+    pub(crate) account: &'a mut dyn FnMut(Account) -> io::Result<()>,
+    pub(crate) transaction: &'a mut dyn FnMut(Transaction) -> io::Result<()>,
+    /// Version of the snapshot, once read.
+    pub(crate) version: &'a mut Option<u32>,
+    /// First error returned while handing an entry over, the restore stops on it.
+    pub(crate) failure: &'a mut Option<io::Error>,
+}
+
+impl<'de> DeserializeSeed<'de> for SnapshotInto<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SnapshotInto<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a ledger snapshot")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        use serde::de::Error;
+
+        if map.next_key::<String>()?.as_deref() != Some("version") {
+            return Err(A::Error::custom("snapshot must start with its version"));
+        }
+        let version = map.next_value()?;
+        *self.version = Some(version);
+        if !is_supported(version) {
+            return Err(A::Error::custom(format!(
+                "unsupported snapshot version: {version}"
+            )));
+        }
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "clock" => *self.clock = map.next_value()?,
+                "sequence" => *self.sequence = map.next_value()?,
+                "accounts" => map.next_value_seed(Each {
+                    insert: &mut *self.account,
+                    failure: &mut *self.failure,
+                    entry: PhantomData,
+                })?,
+                "transactions" => map.next_value_seed(Each {
+                    insert: &mut *self.transaction,
+                    failure: &mut *self.failure,
+                    entry: PhantomData,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Hands every element of a sequence over as soon as it is read.
+struct Each<'a, T> {
+    insert: &'a mut dyn FnMut(T) -> io::Result<()>,
+    failure: &'a mut Option<io::Error>,
+    entry: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for Each<'_, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Each<'_, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(entry) = seq.next_element()? {
+            if let Err(e) = (self.insert)(entry) {
+                let error = serde::de::Error::custom(&e);
+                *self.failure = Some(e);
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn snapshot_starts_with_version() {
+        let accounts: HashMap<u16, Account> = HashMap::from([(1, Account::new(1))]);
+        let trx: HashMap<u32, Transaction> = HashMap::new();
+
+        let snapshot = serde_json::to_string(&SnapshotOf {
+            clock: Some(10),
+            sequence: 3,
+            accounts: &accounts,
+            trx: &trx,
+        })
+        .expect("failed to serialize snapshot");
+
+        assert!(snapshot
+            .starts_with(r#"{"version":1,"clock":10,"sequence":3,"accounts":[{"client":1,"#));
+        assert!(snapshot.ends_with(r#""transactions":[]}"#));
+    }
+
+    #[test]
+    fn restore_fails_when_version_is_not_first() {
+        let mut clock = None;
+        let mut sequence = 0;
+        let mut version = None;
+        let mut failure = None;
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"clock":1,"version":1}"#);
+
+        let result = SnapshotInto {
+            clock: &mut clock,
+            sequence: &mut sequence,
+            account: &mut |_| Ok(()),
+            transaction: &mut |_| Ok(()),
+            version: &mut version,
+            failure: &mut failure,
+        }
+        .deserialize(&mut deserializer);
+
+        assert!(result.is_err());
+        assert_eq!(clock, None);
+        assert_eq!(version, None);
+    }
+
+    #[test]
+    fn restore_ignores_unknown_fields() {
+        let mut clock = None;
+        let mut sequence = 0;
+        let mut version = None;
+        let mut failure = None;
+        let mut accounts = Vec::new();
+        let snapshot = r#"{"version":1,"added_later":{"a":[1]},"clock":7,"accounts":[{"client":2,"balances":[],"status":"active","credit_limit":"0"}],"transactions":[]}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(snapshot);
+
+        SnapshotInto {
+            clock: &mut clock,
+            sequence: &mut sequence,
+            account: &mut |account: Account| {
+                accounts.push(account.client);
+                Ok(())
+            },
+            transaction: &mut |_| Ok(()),
+            version: &mut version,
+            failure: &mut failure,
+        }
+        .deserialize(&mut deserializer)
+        .expect("failed to restore snapshot");
+
+        assert_eq!(clock, Some(7));
+        // the sequence is optional, older snapshots have none.
+        assert_eq!(sequence, 0);
+        assert_eq!(version, Some(1));
+        assert_eq!(accounts, vec![2]);
+    }
+}
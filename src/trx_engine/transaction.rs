@@ -1,26 +1,151 @@
+use crate::trx_engine::currency::Currency;
+use crate::trx_engine::errors::EngineError;
 use rust_decimal::Decimal;
+use std::collections::BTreeMap;
 
-#[derive(Debug, serde::Deserialize, PartialEq, Copy, Clone)]
+/// Dispute id used by dispute, resolve and chargeback inputs that do not name a dispute.
+/// A dispute without an id and without an amount therefore keeps the original behaviour:
+/// a single dispute covering the whole transaction.
+pub const DEFAULT_DISPUTE: u32 = 0;
+
+/// Type of operation requested by an input.
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Copy, Clone)]
 #[serde(rename_all = "lowercase")]
-pub(crate) enum Type {
+pub enum Type {
     Deposit,
     Withdrawal,
     Dispute,
     Resolve,
     Chargeback,
+    /// Moves funds from the account of the client to the account of the destination client.
+    Transfer,
+    /// Converts funds of the client from one currency to another at an exchange rate.
+    Exchange,
+    /// Card-style hold: funds are authorized first and later captured, fully or partially, or voided.
+    Authorize,
+    Capture,
+    Void,
+    /// Administrative operations on the account of the client, see `AccountStatus::transition`.
+    Unlock,
+    Freeze,
+    Unfreeze,
+    Close,
 }
 
-#[derive(Debug, serde::Deserialize, PartialEq, Copy, Clone)]
+/// State of a transaction or of one of its disputes, see `State::transition` for the allowed transitions.
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Copy, Clone)]
 #[serde(rename_all = "lowercase")]
-pub(crate) enum State {
+pub enum State {
     Ok,
     Disputed,
     Chargeback,
+    /// Authorization holding funds until it is captured or voided.
+    Authorized,
+    Captured,
+    Voided,
+}
+
+impl State {
+    /// Transition table of the lifecycle of a transaction in the ledger.
+    /// `from` is the state of the subject of the action, or `None` if the ledger has no record of it:
+    /// the transaction `tx` for deposits, withdrawals, transfers, exchanges, authorizations, captures, voids and administrative operations,
+    /// one of its disputes for disputes, resolves and chargebacks.
+    /// Every operation must go through this table before mutating any account,
+    /// the match is exhaustive so no transition can be left undefined.
+    ///
+    /// | from                | deposit / withdrawal / transfer / exchange / administrative | authorize           | dispute                 | resolve               | chargeback            | capture                | void                   |
+    /// |---------------------|-------------------------------------------------------------|---------------------|-------------------------|-----------------------|-----------------------|------------------------|------------------------|
+    /// | (none)              | Ok                                                          | Authorized          | Disputed                | TrxNotInDispute       | TrxNotInDispute       | TrxNotAuthorized       | TrxNotAuthorized       |
+    /// | Ok                  | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | Disputed                | TrxNotInDispute       | TrxNotInDispute       | TrxNotAuthorized       | TrxNotAuthorized       |
+    /// | Disputed            | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | TrxAlreadyInDispute     | Ok                    | Chargeback            | TrxNotAuthorized       | TrxNotAuthorized       |
+    /// | Chargeback          | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | TrxNotInDisputableState | TrxAlreadyChargedBack | TrxAlreadyChargedBack | TrxNotAuthorized       | TrxNotAuthorized       |
+    /// | Authorized          | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | TrxNotDisputable        | TrxNotInDispute       | TrxNotInDispute       | Captured               | Voided                 |
+    /// | Captured            | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | TrxNotDisputable        | TrxNotInDispute       | TrxNotInDispute       | TrxAlreadyCaptured     | TrxAuthorizationClosed |
+    /// | Voided              | TrxAlreadyProcessed                                         | TrxAlreadyProcessed | TrxNotDisputable        | TrxNotInDispute       | TrxNotInDispute       | TrxAuthorizationClosed | TrxAuthorizationClosed |
+    ///
+    /// An authorization is captured at most once: a partial capture releases the rest of the authorized amount.
+    ///
+    /// # Errors
+    /// * An error is returned if the transition is not allowed.
+    ///
+    pub(crate) fn transition(
+        tx: u32,
+        from: Option<State>,
+        action: Type,
+    ) -> Result<State, EngineError> {
+        match (from, action) {
+            (
+                None,
+                Type::Deposit
+                | Type::Withdrawal
+                | Type::Transfer
+                | Type::Exchange
+                | Type::Unlock
+                | Type::Freeze
+                | Type::Unfreeze
+                | Type::Close,
+            ) => Ok(State::Ok),
+            (None, Type::Authorize) => Ok(State::Authorized),
+            (None, Type::Dispute) => Ok(State::Disputed),
+            (None, Type::Resolve | Type::Chargeback) => Err(EngineError::TrxNotInDispute { tx }),
+            (None, Type::Capture | Type::Void) => Err(EngineError::TrxNotAuthorized { tx }),
+
+            (
+                Some(_),
+                Type::Deposit
+                | Type::Withdrawal
+                | Type::Transfer
+                | Type::Exchange
+                | Type::Authorize
+                | Type::Unlock
+                | Type::Freeze
+                | Type::Unfreeze
+                | Type::Close,
+            ) => Err(EngineError::TrxAlreadyProcessed { tx }),
+
+            (Some(State::Ok), Type::Dispute) => Ok(State::Disputed),
+            (Some(State::Ok), Type::Resolve | Type::Chargeback) => {
+                Err(EngineError::TrxNotInDispute { tx })
+            }
+
+            (Some(State::Disputed), Type::Dispute) => Err(EngineError::TrxAlreadyInDispute { tx }),
+            (Some(State::Disputed), Type::Resolve) => Ok(State::Ok),
+            (Some(State::Disputed), Type::Chargeback) => Ok(State::Chargeback),
+
+            (Some(State::Chargeback), Type::Dispute) => {
+                Err(EngineError::TrxNotInDisputableState { tx })
+            }
+            (Some(State::Chargeback), Type::Resolve | Type::Chargeback) => {
+                Err(EngineError::TrxAlreadyChargedBack { tx })
+            }
+
+            (Some(State::Ok | State::Disputed | State::Chargeback), Type::Capture | Type::Void) => {
+                Err(EngineError::TrxNotAuthorized { tx })
+            }
+
+            (Some(State::Authorized | State::Captured | State::Voided), Type::Dispute) => {
+                Err(EngineError::TrxNotDisputable { tx })
+            }
+            (
+                Some(State::Authorized | State::Captured | State::Voided),
+                Type::Resolve | Type::Chargeback,
+            ) => Err(EngineError::TrxNotInDispute { tx }),
+            (Some(State::Authorized), Type::Capture) => Ok(State::Captured),
+            (Some(State::Authorized), Type::Void) => Ok(State::Voided),
+            (Some(State::Captured), Type::Capture) => Err(EngineError::TrxAlreadyCaptured { tx }),
+            (Some(State::Captured), Type::Void)
+            | (Some(State::Voided), Type::Capture | Type::Void) => {
+                Err(EngineError::TrxAuthorizationClosed { tx })
+            }
+        }
+    }
 }
 
-/// Input represents a line of the provided input (csv arg from the CLI).
-#[derive(Debug, serde::Deserialize)]
-pub(crate) struct Input {
+/// Input represents a line of the provided input (csv arg from the CLI)
+/// or a single transaction submitted by a library consumer through `Ledger::process_trx`.
+/// Inputs are serialized by the journal of the ledger.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Input {
     #[serde(rename = "type")]
     pub(crate) transaction_type: Type,
 
@@ -30,15 +155,208 @@ pub(crate) struct Input {
     #[serde(rename = "tx")]
     pub(crate) tx: u32,
 
+    /// For disputes and captures, the amount is optional and limits the operation to part of the transaction.
     #[serde(deserialize_with = "csv::invalid_option")]
     #[serde(rename = "amount")]
     pub(crate) amount: Option<Decimal>,
+
+    /// Optional id of the dispute opened, resolved or chargeback, see `DEFAULT_DISPUTE`.
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    #[serde(rename = "dispute")]
+    pub(crate) dispute: Option<u32>,
+
+    /// Client receiving the funds of a transfer.
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    #[serde(rename = "destination")]
+    pub(crate) destination: Option<u16>,
+
+    /// Optional time of the input, in seconds. It drives the logical clock of the ledger.
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    #[serde(rename = "timestamp")]
+    pub(crate) timestamp: Option<u64>,
+
+    /// Optional currency of the amount. Inputs referring to another transaction
+    /// always operate in the currency of that transaction.
+    #[serde(default, rename = "currency")]
+    pub(crate) currency: Option<Currency>,
+
+    /// Currency the amount of an exchange is converted to.
+    #[serde(default, rename = "target_currency")]
+    pub(crate) target_currency: Option<Currency>,
+
+    /// Optional rate of an exchange. Without it the rate is taken from the `RateProvider` of the ledger.
+    #[serde(default, rename = "rate")]
+    pub(crate) rate: Option<Decimal>,
+}
+
+impl Input {
+    pub fn new(transaction_type: Type, client: u16, tx: u32, amount: Option<Decimal>) -> Self {
+        Self {
+            transaction_type,
+            client,
+            tx,
+            amount,
+            dispute: None,
+            destination: None,
+            timestamp: None,
+            currency: None,
+            target_currency: None,
+            rate: None,
+        }
+    }
+
+    pub fn deposit(client: u16, tx: u32, amount: Decimal) -> Self {
+        Self::new(Type::Deposit, client, tx, Some(amount))
+    }
+
+    pub fn withdrawal(client: u16, tx: u32, amount: Decimal) -> Self {
+        Self::new(Type::Withdrawal, client, tx, Some(amount))
+    }
+
+    pub fn transfer(client: u16, destination: u16, tx: u32, amount: Decimal) -> Self {
+        Self {
+            destination: Some(destination),
+            ..Self::new(Type::Transfer, client, tx, Some(amount))
+        }
+    }
+
+    /// Exchange of `amount` from the balance in `from` to the balance in `to`,
+    /// at the rate of the `RateProvider` of the ledger unless one is set with `with_rate`.
+    pub fn exchange(client: u16, tx: u32, amount: Decimal, from: Currency, to: Currency) -> Self {
+        Self {
+            currency: Some(from),
+            target_currency: Some(to),
+            ..Self::new(Type::Exchange, client, tx, Some(amount))
+        }
+    }
+
+    pub fn authorize(client: u16, tx: u32, amount: Decimal) -> Self {
+        Self::new(Type::Authorize, client, tx, Some(amount))
+    }
+
+    /// Capture of the authorization `tx`. Without an amount the whole authorization is captured.
+    pub fn capture(client: u16, tx: u32, amount: Option<Decimal>) -> Self {
+        Self::new(Type::Capture, client, tx, amount)
+    }
+
+    pub fn void(client: u16, tx: u32) -> Self {
+        Self::new(Type::Void, client, tx, None)
+    }
+
+    pub fn dispute(client: u16, tx: u32) -> Self {
+        Self::new(Type::Dispute, client, tx, None)
+    }
+
+    /// Dispute of part of a transaction, identified by `dispute` for the later resolve or chargeback.
+    pub fn partial_dispute(client: u16, tx: u32, dispute: u32, amount: Decimal) -> Self {
+        Self::new(Type::Dispute, client, tx, Some(amount)).with_dispute(dispute)
+    }
+
+    pub fn resolve(client: u16, tx: u32) -> Self {
+        Self::new(Type::Resolve, client, tx, None)
+    }
+
+    pub fn chargeback(client: u16, tx: u32) -> Self {
+        Self::new(Type::Chargeback, client, tx, None)
+    }
+
+    /// Names the dispute a dispute, resolve or chargeback input applies to.
+    pub fn with_dispute(mut self, dispute: u32) -> Self {
+        self.dispute = Some(dispute);
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn with_currency(mut self, currency: Currency) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    pub fn with_rate(mut self, rate: Decimal) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    pub fn transaction_type(&self) -> Type {
+        self.transaction_type
+    }
+
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+
+    pub fn tx(&self) -> u32 {
+        self.tx
+    }
+
+    pub fn amount(&self) -> Option<Decimal> {
+        self.amount
+    }
+
+    pub fn dispute_id(&self) -> u32 {
+        self.dispute.unwrap_or(DEFAULT_DISPUTE)
+    }
+
+    pub fn destination(&self) -> Option<u16> {
+        self.destination
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn currency(&self) -> Option<Currency> {
+        self.currency
+    }
+
+    pub fn target_currency(&self) -> Option<Currency> {
+        self.target_currency
+    }
+
+    pub fn rate(&self) -> Option<Decimal> {
+        self.rate
+    }
+}
+
+/// Dispute represents a claim against part or the whole amount of a transaction.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Dispute {
+    #[serde(rename = "amount")]
+    pub(crate) amount: Decimal,
+
+    #[serde(rename = "state")]
+    pub(crate) state: State,
+
+    /// Time the dispute was opened at, if the ledger clock was known.
+    #[serde(default, rename = "opened_at")]
+    pub(crate) opened_at: Option<u64>,
+}
+
+impl Dispute {
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn opened_at(&self) -> Option<u64> {
+        self.opened_at
+    }
 }
 
 /// Transaction represents a business translation from an input line.
 /// All operations that mutate a transaction should be done through the provided methods.
-#[derive(Debug, serde::Deserialize)]
-pub(crate) struct Transaction {
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Transaction {
+    #[serde(rename = "tx")]
+    pub(crate) tx: u32,
+
     #[serde(rename = "type")]
     pub(crate) transaction_type: Type,
 
@@ -48,30 +366,169 @@ pub(crate) struct Transaction {
     #[serde(rename = "amount")]
     pub(crate) amount: Option<Decimal>,
 
+    /// Client that received the funds, for transfers only.
+    #[serde(default, rename = "destination")]
+    pub(crate) destination: Option<u16>,
+
+    /// Amount settled by the capture of an authorization.
+    #[serde(default, rename = "captured")]
+    pub(crate) captured: Option<Decimal>,
+
+    /// Time the transaction was processed at, if the ledger clock was known.
+    #[serde(default, rename = "timestamp")]
+    pub(crate) timestamp: Option<u64>,
+
+    /// Currency the amount of the transaction was moved in.
+    #[serde(default, rename = "currency")]
+    pub(crate) currency: Option<Currency>,
+
+    /// Currency the amount of an exchange was converted to.
+    #[serde(default, rename = "target_currency")]
+    pub(crate) target_currency: Option<Currency>,
+
+    /// Rate applied by an exchange, whether it was provided by the input or by the `RateProvider`.
+    #[serde(default, rename = "rate")]
+    pub(crate) rate: Option<Decimal>,
+
+    /// Amount credited in the target currency by an exchange, once rounded.
+    #[serde(default, rename = "converted")]
+    pub(crate) converted: Option<Decimal>,
+
+    /// Aggregated state of the disputes of the transaction.
     #[serde(rename = "state")]
     pub(crate) state: State,
+
+    #[serde(default, rename = "disputes")]
+    pub(crate) disputes: BTreeMap<u32, Dispute>,
 }
 
 impl Transaction {
     pub(crate) fn new(input: &Input) -> Self {
         Self {
+            tx: input.tx,
             transaction_type: input.transaction_type,
             client: input.client,
             amount: input.amount,
+            destination: input.destination,
+            captured: None,
+            timestamp: input.timestamp,
+            currency: input.currency,
+            target_currency: input.target_currency,
+            rate: None,
+            converted: None,
             state: State::Ok,
+            disputes: BTreeMap::new(),
+        }
+    }
+
+    /// State of the provided dispute as seen by the state machine.
+    /// Once any part of a transaction was chargeback no dispute can be opened on it,
+    /// so every dispute of it, existing or not, is considered chargeback unless it is still open:
+    /// open disputes can still be resolved or chargeback, releasing the funds they hold.
+    pub(crate) fn dispute_state(&self, dispute: u32) -> Option<State> {
+        let state = self.disputes.get(&dispute).map(|dispute| dispute.state);
+        if self.state == State::Chargeback && state != Some(State::Disputed) {
+            return Some(State::Chargeback);
         }
+
+        state
+    }
+
+    /// Validates the provided action against the state machine and returns the state
+    /// the dispute would move to. The transaction itself is not mutated, so the caller can
+    /// apply the balance changes first and only then commit the new state with `set_dispute`.
+    pub(crate) fn next_dispute_state(
+        &self,
+        dispute: u32,
+        action: Type,
+    ) -> Result<State, EngineError> {
+        State::transition(self.tx, self.dispute_state(dispute), action)
+    }
+
+    /// Amount of the transaction not claimed by any open or chargeback dispute, other than `excluded`.
+    pub(crate) fn undisputed_amount(&self, excluded: u32) -> Decimal {
+        let claimed: Decimal = self
+            .disputes
+            .iter()
+            .filter(|(id, dispute)| **id != excluded && dispute.state != State::Ok)
+            .map(|(_, dispute)| dispute.amount)
+            .sum();
+
+        self.amount.unwrap_or_default() - claimed
+    }
+
+    /// Stores the dispute and refreshes the aggregated state of the transaction:
+    /// chargeback if any dispute was chargeback, disputed if any dispute is open, ok otherwise.
+    pub(crate) fn set_dispute(&mut self, id: u32, dispute: Dispute) {
+        self.disputes.insert(id, dispute);
+
+        let states = self.disputes.values().map(|dispute| dispute.state);
+        self.state = states.fold(State::Ok, |aggregated, state| match (aggregated, state) {
+            (State::Chargeback, _) | (_, State::Chargeback) => State::Chargeback,
+            (State::Disputed, _) | (_, State::Disputed) => State::Disputed,
+            // disputes are never in any of the authorization states.
+            _ => State::Ok,
+        });
+    }
+
+    pub(crate) fn set_state(&mut self, state: State) {
+        self.state = state
+    }
+
+    pub fn tx(&self) -> u32 {
+        self.tx
+    }
+
+    pub fn transaction_type(&self) -> Type {
+        self.transaction_type
+    }
+
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+
+    pub fn amount(&self) -> Option<Decimal> {
+        self.amount
+    }
+
+    pub fn destination(&self) -> Option<u16> {
+        self.destination
+    }
+
+    pub fn captured(&self) -> Option<Decimal> {
+        self.captured
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn currency(&self) -> Option<Currency> {
+        self.currency
+    }
+
+    pub fn target_currency(&self) -> Option<Currency> {
+        self.target_currency
+    }
+
+    pub fn rate(&self) -> Option<Decimal> {
+        self.rate
     }
 
-    pub(crate) fn open_dispute(&mut self) {
-        self.state = State::Disputed
+    pub fn converted(&self) -> Option<Decimal> {
+        self.converted
     }
 
-    pub(crate) fn resolve_dispute(&mut self) {
-        self.state = State::Ok
+    pub fn state(&self) -> State {
+        self.state
     }
 
-    pub(crate) fn chargeback_dispute(&mut self) {
-        self.state = State::Chargeback
+    pub fn dispute(&self, id: u32) -> Option<&Dispute> {
+        self.disputes.get(&id)
+    }
+
+    pub fn disputes(&self) -> impl Iterator<Item = (u32, &Dispute)> {
+        self.disputes.iter().map(|(id, dispute)| (*id, dispute))
     }
 }
 
@@ -87,6 +544,12 @@ mod tests {
             client: 1234,
             tx: 123456789,
             amount,
+            dispute: None,
+            destination: None,
+            timestamp: None,
+            currency: None,
+            target_currency: None,
+            rate: None,
         }
     }
 
@@ -95,6 +558,7 @@ mod tests {
         let input = input(Some(dec!(1500)));
         let transaction = Transaction::new(&input);
 
+        assert_eq!(transaction.tx, input.tx);
         assert_eq!(transaction.transaction_type, input.transaction_type);
         assert_eq!(transaction.client, input.client);
         assert_eq!(transaction.amount, input.amount);
@@ -106,6 +570,7 @@ mod tests {
         let input = input(None);
         let transaction = Transaction::new(&input);
 
+        assert_eq!(transaction.tx, input.tx);
         assert_eq!(transaction.transaction_type, input.transaction_type);
         assert_eq!(transaction.client, input.client);
         assert_eq!(transaction.amount, input.amount);
@@ -113,38 +578,210 @@ mod tests {
     }
 
     #[test]
-    fn transaction_open_dispute() {
-        let input = input(None);
-        let mut transaction = Transaction::new(&input);
+    fn state_transition_allowed() {
+        let allowed = [
+            (None, Type::Deposit, State::Ok),
+            (None, Type::Withdrawal, State::Ok),
+            (None, Type::Dispute, State::Disputed),
+            (Some(State::Ok), Type::Dispute, State::Disputed),
+            (Some(State::Disputed), Type::Resolve, State::Ok),
+            (Some(State::Disputed), Type::Chargeback, State::Chargeback),
+            (None, Type::Authorize, State::Authorized),
+            (Some(State::Authorized), Type::Capture, State::Captured),
+            (Some(State::Authorized), Type::Void, State::Voided),
+        ];
 
-        transaction.open_dispute();
+        for (from, action, expected) in allowed {
+            let result = State::transition(1, from, action);
 
-        assert_eq!(transaction.client, input.client);
-        assert_eq!(transaction.amount, input.amount);
-        assert_eq!(transaction.state, State::Disputed);
+            assert!(result.is_ok());
+            assert_eq!(result.unwrap(), expected);
+        }
     }
 
     #[test]
-    fn transaction_resolve_dispute() {
-        let input = input(None);
-        let mut transaction = Transaction::new(&input);
+    fn state_transition_rejected() {
+        let rejected = [
+            (None, Type::Resolve, EngineError::TrxNotInDispute { tx: 1 }),
+            (
+                None,
+                Type::Chargeback,
+                EngineError::TrxNotInDispute { tx: 1 },
+            ),
+            (
+                Some(State::Ok),
+                Type::Deposit,
+                EngineError::TrxAlreadyProcessed { tx: 1 },
+            ),
+            (
+                Some(State::Disputed),
+                Type::Withdrawal,
+                EngineError::TrxAlreadyProcessed { tx: 1 },
+            ),
+            (
+                Some(State::Ok),
+                Type::Resolve,
+                EngineError::TrxNotInDispute { tx: 1 },
+            ),
+            (
+                Some(State::Ok),
+                Type::Chargeback,
+                EngineError::TrxNotInDispute { tx: 1 },
+            ),
+            (
+                Some(State::Disputed),
+                Type::Dispute,
+                EngineError::TrxAlreadyInDispute { tx: 1 },
+            ),
+            (
+                Some(State::Chargeback),
+                Type::Dispute,
+                EngineError::TrxNotInDisputableState { tx: 1 },
+            ),
+            (
+                Some(State::Chargeback),
+                Type::Resolve,
+                EngineError::TrxAlreadyChargedBack { tx: 1 },
+            ),
+            (
+                Some(State::Chargeback),
+                Type::Chargeback,
+                EngineError::TrxAlreadyChargedBack { tx: 1 },
+            ),
+            (None, Type::Capture, EngineError::TrxNotAuthorized { tx: 1 }),
+            (
+                Some(State::Ok),
+                Type::Void,
+                EngineError::TrxNotAuthorized { tx: 1 },
+            ),
+            (
+                Some(State::Authorized),
+                Type::Dispute,
+                EngineError::TrxNotDisputable { tx: 1 },
+            ),
+            (
+                Some(State::Captured),
+                Type::Capture,
+                EngineError::TrxAlreadyCaptured { tx: 1 },
+            ),
+            (
+                Some(State::Captured),
+                Type::Void,
+                EngineError::TrxAuthorizationClosed { tx: 1 },
+            ),
+            (
+                Some(State::Voided),
+                Type::Capture,
+                EngineError::TrxAuthorizationClosed { tx: 1 },
+            ),
+        ];
 
-        transaction.resolve_dispute();
+        for (from, action, expected) in rejected {
+            let result = State::transition(1, from, action);
 
-        assert_eq!(transaction.client, input.client);
-        assert_eq!(transaction.amount, input.amount);
+            assert!(result.is_err());
+            assert_eq!(result.unwrap_err(), expected);
+        }
+    }
+
+    #[test]
+    fn transaction_next_dispute_state_does_not_mutate() {
+        let input = input(Some(dec!(1500)));
+        let transaction = Transaction::new(&input);
+
+        let result = transaction.next_dispute_state(DEFAULT_DISPUTE, Type::Dispute);
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), State::Disputed);
         assert_eq!(transaction.state, State::Ok);
+        assert!(transaction.disputes.is_empty());
     }
 
     #[test]
-    fn transaction_chargeback_dispute() {
+    fn transaction_next_dispute_state_when_transaction_was_chargeback() {
+        let input = input(Some(dec!(1500)));
+        let mut transaction = Transaction::new(&input);
+        transaction.set_dispute(
+            1,
+            Dispute {
+                amount: dec!(500),
+                state: State::Chargeback,
+                opened_at: None,
+            },
+        );
+
+        // a dispute never seen before is still rejected, the transaction is final.
+        let result = transaction.next_dispute_state(2, Type::Dispute);
+
+        assert_eq!(
+            result.unwrap_err(),
+            EngineError::TrxNotInDisputableState { tx: input.tx }
+        );
+    }
+
+    #[test]
+    fn transaction_set_state() {
         let input = input(None);
         let mut transaction = Transaction::new(&input);
 
-        transaction.chargeback_dispute();
+        transaction.set_state(State::Chargeback);
 
         assert_eq!(transaction.client, input.client);
         assert_eq!(transaction.amount, input.amount);
         assert_eq!(transaction.state, State::Chargeback);
     }
+
+    #[test]
+    fn transaction_set_dispute_aggregates_state() {
+        let input = input(Some(dec!(1500)));
+        let mut transaction = Transaction::new(&input);
+
+        let disputes = [
+            (1, dec!(500), State::Disputed, State::Disputed),
+            (2, dec!(200), State::Disputed, State::Disputed),
+            (1, dec!(500), State::Ok, State::Disputed),
+            (2, dec!(200), State::Ok, State::Ok),
+            (3, dec!(100), State::Chargeback, State::Chargeback),
+        ];
+
+        for (id, amount, state, expected) in disputes {
+            transaction.set_dispute(
+                id,
+                Dispute {
+                    amount,
+                    state,
+                    opened_at: None,
+                },
+            );
+
+            assert_eq!(transaction.state, expected);
+        }
+        assert_eq!(transaction.disputes.len(), 3);
+    }
+
+    #[test]
+    fn transaction_undisputed_amount() {
+        let input = input(Some(dec!(1500)));
+        let mut transaction = Transaction::new(&input);
+        transaction.set_dispute(
+            1,
+            Dispute {
+                amount: dec!(500),
+                state: State::Disputed,
+                opened_at: None,
+            },
+        );
+        transaction.set_dispute(
+            2,
+            Dispute {
+                amount: dec!(200),
+                state: State::Ok,
+                opened_at: None,
+            },
+        );
+
+        // resolved disputes release their claim, the excluded dispute is not counted.
+        assert_eq!(transaction.undisputed_amount(2), dec!(1000));
+        assert_eq!(transaction.undisputed_amount(1), dec!(1500));
+    }
 }
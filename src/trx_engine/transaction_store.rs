@@ -0,0 +1,340 @@
+use crate::trx_engine::transaction::Transaction;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Storage of the transactions processed by the ledger, so they can be found
+/// when a later input (dispute, resolve, capture, ...) refers to them.
+/// The ledger only writes to the store once an input was processed successfully.
+pub trait TransactionStore {
+    /// Returns the latest version of the transaction stored under the provided id, if any.
+    fn get(&self, tx: u32) -> io::Result<Option<Cow<'_, Transaction>>>;
+
+    /// Stores a new transaction, or a new version of an existing one, under its id.
+    fn insert(&mut self, trx: Transaction) -> io::Result<()>;
+
+    /// Removes the transaction stored under the provided id, if any.
+    /// Only used by the ledger to roll back a transaction stored by an input whose changes could not all be stored.
+    fn remove(&mut self, tx: u32) -> io::Result<()>;
+
+    /// Returns the latest version of every transaction of the store, in no particular order.
+    fn transactions(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Transaction>>> + '_>;
+
+    /// Number of distinct transactions stored.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The default store, every transaction is kept in memory for the lifetime of the ledger.
+impl TransactionStore for HashMap<u32, Transaction> {
+    fn get(&self, tx: u32) -> io::Result<Option<Cow<'_, Transaction>>> {
+        Ok(HashMap::get(self, &tx).map(Cow::Borrowed))
+    }
+
+    fn insert(&mut self, trx: Transaction) -> io::Result<()> {
+        HashMap::insert(self, trx.tx, trx);
+        Ok(())
+    }
+
+    fn remove(&mut self, tx: u32) -> io::Result<()> {
+        HashMap::remove(self, &tx);
+        Ok(())
+    }
+
+    fn transactions(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Transaction>>> + '_> {
+        Box::new(self.values().map(|trx| Ok(Cow::Borrowed(trx))))
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// Size of an entry of the index file: the offset and the length of the record in the log file.
+const INDEX_ENTRY_SIZE: u64 = 12;
+
+/// Store keeping the transactions on disk, so the memory used does not grow with the number of transactions.
+///
+/// Every version of a transaction is appended to a log file as a line of json, and an index file
+/// holds the location of the latest version of each transaction: one fixed size entry per transaction id,
+/// at the position of the id. The index is a sparse file, so only the pages of the ids in use take disk space.
+/// Replaced versions are never removed from the log.
+#[derive(Debug)]
+pub struct DiskTransactionStore {
+    /// Path of the log file, reopened to read it sequentially without moving the cursor of `log`.
+    log_path: PathBuf,
+    log: File,
+    index: File,
+    /// Length of the log file, where the next record is appended.
+    log_len: u64,
+    len: usize,
+}
+
+impl DiskTransactionStore {
+    /// Creates an empty store in the provided directory, replacing any store already in it.
+    /// The directory is created if missing.
+    /// # Errors
+    /// * An error is returned if the files of the store cannot be created.
+    ///
+    pub fn create<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let open = |name: &str| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(dir.as_ref().join(name))
+        };
+
+        Ok(Self {
+            log_path: dir.as_ref().join("transactions.log"),
+            log: open("transactions.log")?,
+            index: open("transactions.idx")?,
+            log_len: 0,
+            len: 0,
+        })
+    }
+
+    /// Location of the latest version of the transaction in the log, if it was ever stored.
+    fn location(&self, tx: u32) -> io::Result<Option<(u64, u32)>> {
+        let mut index = &self.index;
+        let position = u64::from(tx) * INDEX_ENTRY_SIZE;
+        if position >= index.metadata()?.len() {
+            return Ok(None);
+        }
+
+        let mut entry = [0; INDEX_ENTRY_SIZE as usize];
+        index.seek(SeekFrom::Start(position))?;
+        index.read_exact(&mut entry)?;
+
+        // offsets are stored shifted by one, so a zeroed entry (a hole of the sparse file) is a missing transaction.
+        let (offset, length) = entry.split_at(8);
+        let offset = u64::from_le_bytes(offset.try_into().expect("offset is 8 bytes"));
+        let length = u32::from_le_bytes(length.try_into().expect("length is 4 bytes"));
+        Ok(offset.checked_sub(1).map(|offset| (offset, length)))
+    }
+}
+
+impl TransactionStore for DiskTransactionStore {
+    fn get(&self, tx: u32) -> io::Result<Option<Cow<'_, Transaction>>> {
+        let Some((offset, length)) = self.location(tx)? else {
+            return Ok(None);
+        };
+
+        let mut log = &self.log;
+        let mut record = vec![0; length as usize];
+        log.seek(SeekFrom::Start(offset))?;
+        log.read_exact(&mut record)?;
+
+        let trx = serde_json::from_slice(&record)?;
+        Ok(Some(Cow::Owned(trx)))
+    }
+
+    fn insert(&mut self, trx: Transaction) -> io::Result<()> {
+        let is_new = self.location(trx.tx)?.is_none();
+
+        let mut record = serde_json::to_vec(&trx)?;
+        record.push(b'\n');
+        let length = u32::try_from(record.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "transaction too large"))?;
+
+        // the record is written before the index entry pointing to it,
+        // so the index never refers to a record that is not fully written.
+        self.log.seek(SeekFrom::Start(self.log_len))?;
+        self.log.write_all(&record)?;
+
+        let mut entry = [0; INDEX_ENTRY_SIZE as usize];
+        entry[..8].copy_from_slice(&(self.log_len + 1).to_le_bytes());
+        entry[8..].copy_from_slice(&length.to_le_bytes());
+        self.index
+            .seek(SeekFrom::Start(u64::from(trx.tx) * INDEX_ENTRY_SIZE))?;
+        self.index.write_all(&entry)?;
+
+        self.log_len += u64::from(length);
+        if is_new {
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, tx: u32) -> io::Result<()> {
+        if self.location(tx)?.is_none() {
+            return Ok(());
+        }
+
+        // a zeroed entry is a missing transaction, its versions are left in the log.
+        self.index
+            .seek(SeekFrom::Start(u64::from(tx) * INDEX_ENTRY_SIZE))?;
+        self.index.write_all(&[0; INDEX_ENTRY_SIZE as usize])?;
+        self.len -= 1;
+
+        Ok(())
+    }
+
+    fn transactions(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Transaction>>> + '_> {
+        let mut log = match File::open(&self.log_path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+
+        // the log is read in order, and a record is only returned if the index points to it,
+        // i.e. it is the latest version of its transaction.
+        let mut offset = 0;
+        let mut line = Vec::new();
+        Box::new(std::iter::from_fn(move || loop {
+            line.clear();
+            let record = match log.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(length) => {
+                    let record = offset;
+                    offset += length as u64;
+                    record
+                }
+                Err(e) => return Some(Err(e)),
+            };
+
+            let trx: Transaction = match serde_json::from_slice(&line) {
+                Ok(trx) => trx,
+                Err(e) => return Some(Err(e.into())),
+            };
+            match self.location(trx.tx) {
+                Ok(Some((latest, _))) if latest == record => return Some(Ok(Cow::Owned(trx))),
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trx_engine::transaction::{Dispute, Input, State};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn hash_map_store_insert_and_get() {
+        let mut store: HashMap<u32, Transaction> = HashMap::new();
+        let trx = Transaction::new(&Input::deposit(1, 7, dec!(10)));
+
+        TransactionStore::insert(&mut store, trx).expect("failed to insert");
+
+        let stored = TransactionStore::get(&store, 7)
+            .expect("failed to get")
+            .expect("transaction not found");
+        assert_eq!(stored.client, 1);
+        assert!(TransactionStore::get(&store, 8)
+            .expect("failed to get")
+            .is_none());
+        assert_eq!(TransactionStore::len(&store), 1);
+    }
+
+    #[test]
+    fn disk_store_insert_and_get() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let mut store = DiskTransactionStore::create(dir.path()).expect("failed to create store");
+        assert!(store.is_empty());
+
+        store
+            .insert(Transaction::new(&Input::deposit(1, 7, dec!(10.5))))
+            .expect("failed to insert");
+        store
+            .insert(Transaction::new(&Input::withdrawal(2, 3, dec!(1))))
+            .expect("failed to insert");
+
+        let deposit = store
+            .get(7)
+            .expect("failed to get")
+            .expect("transaction not found");
+        assert_eq!(deposit.client, 1);
+        assert_eq!(deposit.amount, Some(dec!(10.5)));
+        assert_eq!(store.get(3).expect("failed to get").unwrap().client, 2);
+        assert!(store.get(5).expect("failed to get").is_none());
+        assert!(store.get(1000).expect("failed to get").is_none());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn disk_store_update_returns_latest_version() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let mut store = DiskTransactionStore::create(dir.path()).expect("failed to create store");
+
+        let mut trx = Transaction::new(&Input::deposit(1, 7, dec!(10)));
+        store.insert(trx.clone()).expect("failed to insert");
+        trx.set_dispute(
+            1,
+            Dispute {
+                amount: dec!(4),
+                state: State::Disputed,
+                opened_at: Some(20),
+            },
+        );
+        store.insert(trx).expect("failed to update");
+
+        let stored = store
+            .get(7)
+            .expect("failed to get")
+            .expect("transaction not found");
+        assert_eq!(stored.state, State::Disputed);
+        assert_eq!(stored.dispute(1).unwrap().amount, dec!(4));
+        assert_eq!(stored.dispute(1).unwrap().opened_at, Some(20));
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn disk_store_remove() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let mut store = DiskTransactionStore::create(dir.path()).expect("failed to create store");
+        store
+            .insert(Transaction::new(&Input::deposit(1, 7, dec!(10))))
+            .expect("failed to insert");
+        store
+            .insert(Transaction::new(&Input::deposit(1, 3, dec!(1))))
+            .expect("failed to insert");
+
+        store.remove(7).expect("failed to remove");
+        store
+            .remove(8)
+            .expect("failed to remove missing transaction");
+
+        assert!(store.get(7).expect("failed to get").is_none());
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.transactions().count(), 1);
+    }
+
+    #[test]
+    fn disk_store_transactions_returns_latest_versions() {
+        let dir = tempfile::tempdir().expect("failed to create directory");
+        let mut store = DiskTransactionStore::create(dir.path()).expect("failed to create store");
+
+        let mut trx = Transaction::new(&Input::deposit(1, 7, dec!(10)));
+        store.insert(trx.clone()).expect("failed to insert");
+        store
+            .insert(Transaction::new(&Input::withdrawal(2, 3, dec!(1))))
+            .expect("failed to insert");
+        trx.set_state(State::Disputed);
+        store.insert(trx).expect("failed to update");
+
+        let mut transactions = store
+            .transactions()
+            .map(|trx| trx.expect("failed to read transaction").into_owned())
+            .map(|trx| (trx.tx, trx.state))
+            .collect::<Vec<_>>();
+        transactions.sort_by_key(|(tx, _)| *tx);
+
+        assert_eq!(transactions, vec![(3, State::Ok), (7, State::Disputed)]);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::trx_engine::account::Account;
+use crate::trx_engine::account_store::AccountStore;
+use crate::trx_engine::errors::EngineError;
+use crate::trx_engine::events::{Event, EventRecord};
+use crate::trx_engine::fees::FeeTransaction;
+use crate::trx_engine::transaction::{Input, Transaction};
+use crate::trx_engine::transaction_store::TransactionStore;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// UnitOfWork stages every change caused by a single input.
+/// Accounts and transactions are copied from the ledger the first time they are touched
+/// and all mutations are applied to those copies. The ledger only sees the changes once
+/// they are handed back by `into_changes`, so a rejected input can simply drop the unit of work
+/// and no partial state is ever visible.
+pub(crate) struct UnitOfWork<'a> {
+    accounts: &'a dyn AccountStore,
+    trx: &'a dyn TransactionStore,
+    staged_accounts: HashMap<u16, Account>,
+    staged_trx: HashMap<u32, Transaction>,
+    staged_fees: Vec<FeeTransaction>,
+    staged_events: Vec<EventRecord>,
+    /// Events recorded by the input being applied, not yet attributed to it.
+    pending_events: Vec<Event>,
+}
+
+/// Changes staged by a unit of work, see `UnitOfWork::into_changes`.
+pub(crate) struct Changes {
+    pub(crate) accounts: HashMap<u16, Account>,
+    pub(crate) trx: HashMap<u32, Transaction>,
+    pub(crate) fees: Vec<FeeTransaction>,
+    pub(crate) events: Vec<EventRecord>,
+}
+
+impl<'a> UnitOfWork<'a> {
+    pub(crate) fn new(accounts: &'a dyn AccountStore, trx: &'a dyn TransactionStore) -> Self {
+        Self {
+            accounts,
+            trx,
+            staged_accounts: HashMap::new(),
+            staged_trx: HashMap::new(),
+            staged_fees: Vec::new(),
+            staged_events: Vec::new(),
+            pending_events: Vec::new(),
+        }
+    }
+
+    /// Returns the staged copy of the account of the provided client,
+    /// or `None` if the ledger has no record of the client.
+    /// # Errors
+    /// * An error is returned if the account store fails to read the account.
+    ///
+    pub(crate) fn account_mut(&mut self, client: u16) -> Result<Option<&mut Account>, EngineError> {
+        if !self.staged_accounts.contains_key(&client) {
+            let Some(account) = self.stored_account(client)? else {
+                return Ok(None);
+            };
+            self.staged_accounts.insert(client, account);
+        }
+
+        Ok(self.staged_accounts.get_mut(&client))
+    }
+
+    /// Returns the staged copy of the account of the provided client.
+    /// If the ledger has no record of the client a new account is staged.
+    /// # Errors
+    /// * An error is returned if the account store fails to read the account.
+    ///
+    pub(crate) fn open_account_mut(&mut self, client: u16) -> Result<&mut Account, EngineError> {
+        if !self.staged_accounts.contains_key(&client) {
+            let account = self
+                .stored_account(client)?
+                .unwrap_or_else(|| Account::new(client));
+            self.staged_accounts.insert(client, account);
+        }
+
+        Ok(self
+            .staged_accounts
+            .get_mut(&client)
+            .expect("account was just staged"))
+    }
+
+    /// Copy of the account of the provided client as found in the account store.
+    fn stored_account(&self, client: u16) -> Result<Option<Account>, EngineError> {
+        self.accounts
+            .get(client)
+            .map(|account| account.map(Cow::into_owned))
+            .map_err(|e| EngineError::AccountStoreFailure {
+                client,
+                reason: e.to_string(),
+            })
+    }
+
+    /// Returns the transaction as seen by this unit of work, staged changes included.
+    /// # Errors
+    /// * An error is returned if the transaction store fails to read the transaction.
+    ///
+    pub(crate) fn transaction(&self, tx: u32) -> Result<Option<Cow<'_, Transaction>>, EngineError> {
+        if let Some(trx) = self.staged_trx.get(&tx) {
+            return Ok(Some(Cow::Borrowed(trx)));
+        }
+
+        self.trx.get(tx).map_err(|e| EngineError::TrxStoreFailure {
+            tx,
+            reason: e.to_string(),
+        })
+    }
+
+    /// Stages a new or updated transaction, replacing any previous version of it.
+    pub(crate) fn insert_transaction(&mut self, tx: u32, trx: Transaction) {
+        self.staged_trx.insert(tx, trx);
+    }
+
+    /// Stages a fee posted by the ledger.
+    pub(crate) fn post_fee(&mut self, fee: FeeTransaction) {
+        self.staged_fees.push(fee);
+    }
+
+    /// Records the events returned by an operation on a staged account.
+    pub(crate) fn record(&mut self, events: Vec<Event>) {
+        self.pending_events.extend(events);
+    }
+
+    /// Stages the events recorded since the last input was settled, attributed to the provided input.
+    pub(crate) fn attribute_events(&mut self, input: &Input) {
+        let records = self.pending_events.drain(..).map(|event| EventRecord {
+            tx: input.tx,
+            timestamp: input.timestamp,
+            event,
+        });
+        self.staged_events.extend(records);
+    }
+
+    /// Drops the events recorded since the last input was settled, the input was rejected.
+    pub(crate) fn discard_events(&mut self) {
+        self.pending_events.clear();
+    }
+
+    /// Consumes the unit of work and returns the staged accounts and transactions,
+    /// ready to replace their counterparts in the ledger, and the fees posted and events recorded, in order.
+    pub(crate) fn into_changes(self) -> Changes {
+        Changes {
+            accounts: self.staged_accounts,
+            trx: self.staged_trx,
+            fees: self.staged_fees,
+            events: self.staged_events,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trx_engine::transaction::{Input, State};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn account_mut_stages_copy_of_existing_account() {
+        let mut accounts = HashMap::new();
+        let mut account = Account::new(1);
+        account.deposit(None, dec!(10)).expect("failed to deposit");
+        accounts.insert(1, account);
+        let trx = HashMap::new();
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+        work.account_mut(1)
+            .expect("failed to read account")
+            .expect("account not found")
+            .withdrawal(None, dec!(4))
+            .expect("failed to withdrawal");
+        work.open_account_mut(1)
+            .expect("failed to read account")
+            .withdrawal(None, dec!(1))
+            .expect("failed to withdrawal");
+
+        // the ledger copy is not touched until the changes are applied.
+        assert_eq!(accounts.get(&1).unwrap().balance(None).available, dec!(10));
+
+        let changes = work.into_changes();
+        assert_eq!(
+            changes.accounts.get(&1).unwrap().balance(None).available,
+            dec!(5)
+        );
+        assert!(changes.trx.is_empty());
+    }
+
+    #[test]
+    fn account_mut_when_account_not_found() {
+        let accounts = HashMap::new();
+        let trx = HashMap::new();
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+
+        assert!(work.account_mut(1).unwrap().is_none());
+        assert!(work.into_changes().accounts.is_empty());
+    }
+
+    #[test]
+    fn open_account_mut_stages_new_account_when_not_found() {
+        let accounts = HashMap::new();
+        let trx = HashMap::new();
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+        let account = work.open_account_mut(1).expect("failed to read account");
+
+        assert_eq!(account.client, 1);
+        assert_eq!(account.balance(None).total, dec!(0));
+        assert!(accounts.is_empty());
+    }
+
+    #[test]
+    fn insert_transaction_replaces_existing_transaction() {
+        let accounts = HashMap::new();
+        let mut trx = HashMap::new();
+        trx.insert(1, Transaction::new(&Input::deposit(1, 1, dec!(10))));
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+        assert!(work.transaction(2).unwrap().is_none());
+        let mut disputed_trx = work
+            .transaction(1)
+            .unwrap()
+            .expect("transaction not found")
+            .into_owned();
+        disputed_trx.set_state(State::Disputed);
+        work.insert_transaction(1, disputed_trx);
+
+        assert_eq!(work.transaction(1).unwrap().unwrap().state, State::Disputed);
+        assert_eq!(trx.get(&1).unwrap().state, State::Ok);
+
+        let changes = work.into_changes();
+        assert_eq!(changes.trx.len(), 1);
+        assert_eq!(changes.trx.get(&1).unwrap().state, State::Disputed);
+    }
+
+    #[test]
+    fn insert_transaction_is_visible_to_the_unit_of_work_only() {
+        let accounts = HashMap::new();
+        let trx = HashMap::new();
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+        work.insert_transaction(1, Transaction::new(&Input::deposit(1, 1, dec!(10))));
+
+        assert!(work.transaction(1).unwrap().is_some());
+        assert!(trx.is_empty());
+    }
+
+    #[test]
+    fn events_are_staged_for_accepted_inputs_only() {
+        let accounts = HashMap::new();
+        let trx = HashMap::new();
+        let deposit = |client| Event::FundsDeposited {
+            client,
+            currency: None,
+            amount: dec!(10),
+        };
+
+        let mut work = UnitOfWork::new(&accounts, &trx);
+        work.record(vec![deposit(1)]);
+        work.attribute_events(&Input::deposit(1, 1, dec!(10)).with_timestamp(5));
+        work.record(vec![deposit(2)]);
+        work.discard_events();
+
+        let events = work.into_changes().events;
+        assert_eq!(
+            events,
+            vec![EventRecord {
+                tx: 1,
+                timestamp: Some(5),
+                event: deposit(1),
+            }]
+        );
+    }
+}
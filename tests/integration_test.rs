@@ -1,9 +1,23 @@
 use csv::Trim::All;
 use csv::{Reader, ReaderBuilder};
+use rust_decimal_macros::dec;
 use std::collections::HashMap;
 use std::io::{BufWriter, Read};
 use trx_service::trx_engine;
-use trx_service::trx_engine::account::Account;
+use trx_service::trx_engine::account::AccountRecord;
+use trx_service::trx_engine::account_store::{AccountStore, FileAccountStore};
+use trx_service::trx_engine::currency::Currency;
+use trx_service::trx_engine::errors::EngineError;
+use trx_service::trx_engine::event_store::FileEventStore;
+use trx_service::trx_engine::fees::{Fee, FeeRule, FeeSchedule};
+use trx_service::trx_engine::journal::{FileJournal, SyncPolicy};
+use trx_service::trx_engine::ledger::{HoldLifetimes, Ledger, LedgerConfig, Outcome};
+use trx_service::trx_engine::limits::CreditLimits;
+use trx_service::trx_engine::processor::{ErrorPolicy, ProcessError, ProcessOptions, Rejection};
+use trx_service::trx_engine::rates::CsvRateProvider;
+use trx_service::trx_engine::rounding::{PrecisionPolicy, Rounding, RoundingMode};
+use trx_service::trx_engine::transaction::{Input, State, Type};
+use trx_service::trx_engine::transaction_store::DiskTransactionStore;
 
 #[test]
 fn process_input_invalid() {
@@ -95,24 +109,1007 @@ fn process_after_dispute() {
     compare_actual_with_expectations(actual_accounts, expected_accounts)
 }
 
+#[test]
+fn process_without_phantom_accounts() {
+    const FILE_PATH: &str = "phantom_accounts";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_partial_disputes() {
+    const FILE_PATH: &str = "partial_disputes";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_admin_operations() {
+    const FILE_PATH: &str = "admin_operations";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_transfers() {
+    const FILE_PATH: &str = "transfers";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_authorizations() {
+    const FILE_PATH: &str = "authorizations";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_multi_currency() {
+    const FILE_PATH: &str = "multi_currency";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let writer = BufWriter::new(&mut actual);
+
+    trx_engine::processor::process_transactions_file(rdr, writer)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_with_rejections_report() {
+    const FILE_PATH: &str = "trx_invalid_format";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let mut rejections = Vec::new();
+    let options = ProcessOptions {
+        rejections: Some(Box::new(&mut rejections)),
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut rejections_reader = ReaderBuilder::new().from_reader(rejections.as_slice());
+    let rejections: Vec<Rejection> = rejections_reader
+        .deserialize()
+        .map(|rejection| rejection.expect("failed to extract rejection"))
+        .collect();
+
+    // the reason is meant for humans, only the stable fields are compared.
+    let actual_rejections: Vec<(u64, &str, &str)> = rejections
+        .iter()
+        .map(|r| (r.line, r.code.as_str(), r.record.as_str()))
+        .collect();
+    assert_eq!(
+        actual_rejections,
+        vec![
+            (4, "invalid_record", "deposit,1,,2.0"),
+            (5, "invalid_record", "withdrawal,1,4a,1.5"),
+            (6, "insufficient_funds", "withdrawal,2,5,3.0"),
+        ]
+    );
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_strict_aborts_on_first_rejection() {
+    const FILE_PATH: &str = "trx_invalid_format";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let options = ProcessOptions {
+        error_policy: ErrorPolicy::Strict,
+        ..Default::default()
+    };
+
+    let result =
+        trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options);
+
+    match result {
+        Err(ProcessError::Rejected(rejection)) => {
+            assert_eq!(rejection.line, 4);
+            assert_eq!(rejection.code, "invalid_record");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // no partial report is written when the run is aborted.
+    assert!(actual.is_empty());
+}
+
+#[test]
+fn process_with_error_budget() {
+    const FILE_PATH: &str = "trx_invalid_format";
+
+    // the fixture has 5 lines, 3 of them rejected.
+    let cases = [
+        (ErrorPolicy::MaxRejections(2), false),
+        (ErrorPolicy::MaxRejections(3), true),
+        (ErrorPolicy::MaxRejectedPercent(50.0), false),
+        (ErrorPolicy::MaxRejectedPercent(60.0), true),
+    ];
+
+    for (error_policy, succeeds) in cases {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}.csv", FILE_PATH))
+            .expect("failed to fixture file");
+
+        let mut actual = Vec::new();
+        let options = ProcessOptions {
+            error_policy,
+            ..Default::default()
+        };
+
+        let result = trx_engine::processor::process_transactions_file_with_options(
+            rdr,
+            &mut actual,
+            options,
+        );
+
+        if succeeds {
+            assert!(result.is_ok(), "{:?} should succeed", error_policy);
+            assert!(!actual.is_empty());
+        } else {
+            assert!(
+                matches!(result, Err(ProcessError::ErrorBudgetExceeded { .. })),
+                "{:?} should exceed the budget",
+                error_policy
+            );
+            assert!(actual.is_empty());
+        }
+    }
+}
+
+#[test]
+fn process_rejects_invalid_rejected_percent() {
+    for max in [f64::NAN, -1.0, 100.5] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path("tests/trx_invalid_format.csv")
+            .expect("failed to fixture file");
+        let options = ProcessOptions {
+            error_policy: ErrorPolicy::MaxRejectedPercent(max),
+            ..Default::default()
+        };
+
+        let result = trx_engine::processor::process_transactions_file_with_options(
+            rdr,
+            std::io::sink(),
+            options,
+        );
+
+        assert!(
+            matches!(result, Err(ProcessError::InvalidErrorPolicy(_))),
+            "{max} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn process_with_hold_expiry() {
+    const FILE_PATH: &str = "hold_expiry";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let options = ProcessOptions {
+        ledger: LedgerConfig {
+            hold_lifetimes: HoldLifetimes {
+                dispute: Some(100),
+                authorization: Some(100),
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_with_fees() {
+    const FILE_PATH: &str = "fees";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let options = ProcessOptions {
+        ledger: LedgerConfig {
+            fees: Some(FeeSchedule {
+                withdrawal: Some(FeeRule {
+                    fee: Fee::Percentage(dec!(1)),
+                    min: Some(dec!(0.5)),
+                    max: Some(dec!(5)),
+                }),
+                chargeback: Some(FeeRule::new(Fee::Flat(dec!(15)))),
+                ..FeeSchedule::new(100)
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_with_disk_transaction_store() {
+    const FILE_PATH: &str = "partial_disputes";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let store = DiskTransactionStore::create(dir.path()).expect("failed to create store");
+    let options = ProcessOptions {
+        transaction_store: Some(Box::new(store)),
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_recovers_from_journal() {
+    const FILE_PATH: &str = "partial_disputes";
+
+    let input = std::fs::read_to_string(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let journal_path = dir.path().join("journal.jsonl");
+
+    // the first run aborts on the sixth line, only the inputs accepted before it are journaled.
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let journal =
+        FileJournal::open(&journal_path, SyncPolicy::Always).expect("failed to open journal");
+    let options = ProcessOptions {
+        error_policy: ErrorPolicy::Strict,
+        journal: Some(Box::new(journal)),
+        ..Default::default()
+    };
+    let result = trx_engine::processor::process_transactions_file_with_options(
+        rdr,
+        std::io::sink(),
+        options,
+    );
+    assert!(matches!(result, Err(ProcessError::Rejected(_))));
+
+    // the second run replays the journal and resumes after the last journaled line.
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let mut actual = Vec::new();
+    let mut rejections = Vec::new();
+    let recover = std::fs::File::open(&journal_path).expect("failed to open journal");
+    let journal =
+        FileJournal::open(&journal_path, SyncPolicy::Always).expect("failed to open journal");
+    let options = ProcessOptions {
+        rejections: Some(Box::new(&mut rejections)),
+        journal: Some(Box::new(journal)),
+        recover: Some(Box::new(recover)),
+        ..Default::default()
+    };
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts);
+
+    // the sixth line was rejected, so it is the only journaled line processed again.
+    let rejections = Reader::from_reader(rejections.as_slice())
+        .deserialize()
+        .collect::<Result<Vec<Rejection>, _>>()
+        .expect("failed to parse rejections report");
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].line, 6);
+
+    // the processor keeps the journal, the caller empties it once the state of the run is in place.
+    assert!(!std::fs::read(&journal_path)
+        .expect("failed to read journal")
+        .is_empty());
+}
+
+#[test]
+fn process_recovers_from_journal_with_account_store() {
+    const FILE_PATH: &str = "partial_disputes";
+
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let journal_path = dir.path().join("journal.jsonl");
+    let store_path = dir.path().join("accounts.jsonl");
+
+    // the first run aborts on the sixth line, the store is not persisted and the journal holds the accepted inputs.
+    // the second run recovers them and persists the store, then stops before the journal is truncated,
+    // the third run skips the journaled inputs the store already holds.
+    for (run, error_policy) in [
+        ("first", ErrorPolicy::Strict),
+        ("second", ErrorPolicy::Skip),
+        ("third", ErrorPolicy::Skip),
+    ] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}.csv", FILE_PATH))
+            .expect("failed to fixture file");
+        let recover = std::fs::File::open(&journal_path).ok();
+        let journal =
+            FileJournal::open(&journal_path, SyncPolicy::Always).expect("failed to open journal");
+        let store = FileAccountStore::open(&store_path).expect("failed to open store");
+        let options = ProcessOptions {
+            error_policy,
+            journal: Some(Box::new(journal)),
+            recover: recover.map(|file| Box::new(file) as Box<dyn std::io::Read>),
+            account_store: Some(Box::new(store)),
+            ..Default::default()
+        };
+
+        let mut actual = Vec::new();
+        let result = trx_engine::processor::process_transactions_file_with_options(
+            rdr,
+            &mut actual,
+            options,
+        );
+        if run == "first" {
+            assert!(matches!(result, Err(ProcessError::Rejected(_))));
+            assert!(!store_path.exists());
+            continue;
+        }
+        result.unwrap_or_else(|e| panic!("failed to process the {run} run: {e}"));
+
+        let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+        let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+        let mut expected_rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+            .expect("failed to fixture file");
+        let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+        compare_actual_with_expectations(actual_accounts, expected_accounts);
+    }
+}
+
+#[test]
+fn process_continues_from_account_store() {
+    const FILE_PATH: &str = "account_store";
+
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let store_path = dir.path().join("accounts.jsonl");
+
+    // each run opens the store left by the previous one.
+    let mut actual = Vec::new();
+    for run in ["first", "second"] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}_{}.csv", FILE_PATH, run))
+            .expect("failed to fixture file");
+
+        let store = FileAccountStore::open(&store_path).expect("failed to open store");
+        let options = ProcessOptions {
+            account_store: Some(Box::new(store)),
+            ..Default::default()
+        };
+
+        actual.clear();
+        trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+            .expect("failed read file to process");
+    }
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_aborted_run_leaves_account_store_untouched() {
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let store_path = dir.path().join("accounts.jsonl");
+
+    // the strict run is aborted by the withdrawal, the same file is then processed again skipping it.
+    let mut results = Vec::new();
+    for error_policy in [ErrorPolicy::Strict, ErrorPolicy::Skip] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path("tests/account_store_aborted.csv")
+            .expect("failed to fixture file");
+
+        let store = FileAccountStore::open(&store_path).expect("failed to open store");
+        let options = ProcessOptions {
+            error_policy,
+            account_store: Some(Box::new(store)),
+            ..Default::default()
+        };
+
+        let mut actual = Vec::new();
+        let result = trx_engine::processor::process_transactions_file_with_options(
+            rdr,
+            &mut actual,
+            options,
+        );
+        results.push((result, actual));
+    }
+
+    assert!(matches!(results[0].0, Err(ProcessError::Rejected(_))));
+    let (result, actual) = &results[1];
+    assert!(result.is_ok());
+    // the deposit accepted by the aborted run was not persisted, so it is only applied once.
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+    assert_eq!(actual_accounts.len(), 1);
+    assert_eq!(actual_accounts.values().next().unwrap().available, dec!(10));
+    let store = FileAccountStore::open(&store_path).expect("failed to reopen store");
+    let account = store
+        .get(1)
+        .expect("failed to get")
+        .expect("account not found");
+    assert_eq!(account.balance(None).available(), dec!(10));
+}
+
+#[test]
+fn process_continues_from_event_store() {
+    const FILE_PATH: &str = "account_store";
+
+    let dir = tempfile::tempdir().expect("failed to create directory");
+    let store_path = dir.path().join("events.jsonl");
+
+    // each run rebuilds the accounts from the events recorded by the previous one.
+    let mut actual = Vec::new();
+    for run in ["first", "second"] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}_{}.csv", FILE_PATH, run))
+            .expect("failed to fixture file");
+
+        let store = FileEventStore::open(&store_path).expect("failed to open store");
+        let options = ProcessOptions {
+            event_store: Some(Box::new(store)),
+            ..Default::default()
+        };
+
+        actual.clear();
+        trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+            .expect("failed read file to process");
+    }
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_rejects_event_store_with_account_store() {
+    let dir = tempfile::tempdir().expect("failed to create directory");
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path("tests/account_store_first.csv")
+        .expect("failed to fixture file");
+    let events =
+        FileEventStore::open(dir.path().join("events.jsonl")).expect("failed to open store");
+    let store =
+        FileAccountStore::open(dir.path().join("accounts.jsonl")).expect("failed to open store");
+    let options = ProcessOptions {
+        event_store: Some(Box::new(events)),
+        account_store: Some(Box::new(store)),
+        ..Default::default()
+    };
+
+    let result = trx_engine::processor::process_transactions_file_with_options(
+        rdr,
+        std::io::sink(),
+        options,
+    );
+
+    assert!(matches!(result, Err(ProcessError::EventStoreWithAccounts)));
+}
+
+#[test]
+fn process_resumes_from_snapshot() {
+    const FILE_PATH: &str = "snapshot";
+
+    // the second run restores the snapshot written by the first one,
+    // so the dispute opened by the first run can be charged back.
+    let mut snapshot = Vec::new();
+    let mut actual = Vec::new();
+    for run in ["first", "second"] {
+        let rdr = ReaderBuilder::new()
+            .trim(All)
+            .flexible(true)
+            .from_path(format!("tests/{}_{}.csv", FILE_PATH, run))
+            .expect("failed to fixture file");
+
+        let previous = std::mem::take(&mut snapshot);
+        let options = ProcessOptions {
+            restore: (run == "second").then(|| Box::new(previous.as_slice()) as Box<dyn Read>),
+            snapshot: Some(Box::new(&mut snapshot)),
+            ..Default::default()
+        };
+
+        actual.clear();
+        trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+            .expect("failed read file to process");
+    }
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts);
+
+    // the snapshot of the second run starts with the version of the format.
+    let snapshot = String::from_utf8(snapshot).expect("snapshot is not utf-8");
+    assert!(snapshot.starts_with(r#"{"version":1,"#));
+}
+
+#[test]
+fn process_with_credit_limits() {
+    const FILE_PATH: &str = "credit_limits";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let limits_rdr = ReaderBuilder::new()
+        .trim(All)
+        .from_path("tests/client_config.csv")
+        .expect("failed to fixture file");
+    let options = ProcessOptions {
+        ledger: LedgerConfig {
+            credit_limits: CreditLimits::from_reader(limits_rdr)
+                .expect("failed to read credit limits"),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_with_currency_precision() {
+    const FILE_PATH: &str = "currency_precision";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let mut actual = Vec::new();
+    let options = ProcessOptions {
+        ledger: LedgerConfig {
+            precision: PrecisionPolicy {
+                default: Rounding {
+                    decimal_places: 2,
+                    mode: RoundingMode::HalfUp,
+                },
+                currencies: HashMap::from([(
+                    "JPY".parse().unwrap(),
+                    Rounding {
+                        decimal_places: 0,
+                        mode: RoundingMode::HalfUp,
+                    },
+                )]),
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_exchanges() {
+    const FILE_PATH: &str = "exchanges";
+
+    let rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let rates_rdr = ReaderBuilder::new()
+        .trim(All)
+        .from_path("tests/exchange_rates.csv")
+        .expect("failed to fixture file");
+    let rates = CsvRateProvider::from_reader(rates_rdr).expect("failed to read rates");
+
+    let mut actual = Vec::new();
+    let options = ProcessOptions {
+        rates: Some(Box::new(rates)),
+        ..Default::default()
+    };
+
+    trx_engine::processor::process_transactions_file_with_options(rdr, &mut actual, options)
+        .expect("failed read file to process");
+
+    let mut actual_reader = ReaderBuilder::new().from_reader(actual.as_slice());
+    let actual_accounts = parse_from_csv_to_accounts_map(&mut actual_reader);
+
+    let mut expected_rdr = ReaderBuilder::new()
+        .trim(All)
+        .flexible(true)
+        .from_path(format!("tests/{}_expected.csv", FILE_PATH))
+        .expect("failed to fixture file");
+
+    let expected_accounts = parse_from_csv_to_accounts_map(&mut expected_rdr);
+
+    compare_actual_with_expectations(actual_accounts, expected_accounts)
+}
+
+#[test]
+fn process_through_embedded_ledger() {
+    let mut ledger = Ledger::new();
+
+    let outcome = ledger
+        .process_trx(&Input::deposit(1, 1, dec!(10.0)))
+        .expect("failed to deposit");
+    assert_eq!(
+        outcome,
+        Outcome::Deposited {
+            client: 1,
+            tx: 1,
+            amount: dec!(10.0)
+        }
+    );
+
+    let outcome = ledger
+        .process_trx(&Input::dispute(1, 1))
+        .expect("failed to dispute");
+    assert_eq!(
+        outcome,
+        Outcome::DisputeOpened {
+            client: 1,
+            tx: 1,
+            dispute: 0,
+            amount: dec!(10.0)
+        }
+    );
+
+    let error = ledger
+        .process_trx(&Input::withdrawal(1, 2, dec!(5.0)))
+        .expect_err("withdrawal should fail while funds are held");
+    assert_eq!(
+        error,
+        EngineError::InsufficientFunds {
+            client: 1,
+            available: dec!(0),
+            amount: dec!(5.0)
+        }
+    );
+
+    let account = ledger.account(1).expect("account not found");
+    let balance = account.balance(None);
+    assert_eq!(balance.available(), dec!(0));
+    assert_eq!(balance.held(), dec!(10.0));
+    assert_eq!(balance.total(), dec!(10.0));
+    assert!(!account.locked());
+    assert_eq!(ledger.accounts().count(), 1);
+
+    let transaction = ledger.transaction(1).expect("transaction not found");
+    assert_eq!(transaction.transaction_type(), Type::Deposit);
+    assert_eq!(transaction.client(), 1);
+    assert_eq!(transaction.amount(), Some(dec!(10.0)));
+    assert_eq!(transaction.state(), State::Disputed);
+    assert!(ledger.transaction(2).is_none());
+}
+
 // Helper func to compare account maps.
-fn compare_actual_with_expectations(actual: HashMap<u16, Account>, expect: HashMap<u16, Account>) {
+fn compare_actual_with_expectations(
+    actual: HashMap<(u16, Option<Currency>), AccountRecord>,
+    expect: HashMap<(u16, Option<Currency>), AccountRecord>,
+) {
     assert_eq!(actual.len(), expect.len());
 
-    for (key, actual_account) in actual.into_iter() {
-        let expected_account = expect
+    for (key, actual_record) in actual.into_iter() {
+        let expected_record = expect
             .get(&key)
             .expect("missing account entry in expectations");
-        assert_eq!(actual_account, *expected_account);
+        assert_eq!(actual_record.available, expected_record.available);
+        assert_eq!(actual_record.held, expected_record.held);
+        assert_eq!(actual_record.total, expected_record.total);
+        assert_eq!(actual_record.locked, expected_record.locked);
+        assert_eq!(actual_record.status(), expected_record.status());
+        assert_eq!(actual_record.over_limit, expected_record.over_limit);
     }
 }
 
-// Helper method to convert from a CSV in a Reader to a HashMap containing the accounts
-fn parse_from_csv_to_accounts_map<R: Read>(reader: &mut Reader<R>) -> HashMap<u16, Account> {
-    let mut accounts: HashMap<u16, Account> = HashMap::new();
-    for account in reader.deserialize() {
-        let account: Account = account.expect("failed to extract account");
-        accounts.insert(account.client, account);
+// Helper method to convert from a CSV in a Reader to a HashMap containing the balance of the accounts per currency
+fn parse_from_csv_to_accounts_map<R: Read>(
+    reader: &mut Reader<R>,
+) -> HashMap<(u16, Option<Currency>), AccountRecord> {
+    let mut accounts: HashMap<(u16, Option<Currency>), AccountRecord> = HashMap::new();
+    for record in reader.deserialize() {
+        let record: AccountRecord = record.expect("failed to extract account");
+        accounts.insert((record.client, record.currency), record);
     }
 
     accounts